@@ -0,0 +1,132 @@
+use std::fmt;
+
+/// Header carrying the key checked by `ApiKeyAuth`.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Who sent a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// Nobody in particular; authentication is off.
+    Anonymous,
+    /// A client holding one of the configured API keys, by its position in
+    /// the list, so the key itself never ends up in logs.
+    ApiKey(usize),
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Principal::Anonymous => write!(f, "anonymous"),
+            Principal::ApiKey(index) => write!(f, "api key #{}", index),
+        }
+    }
+}
+
+/// Why a request could not be authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    MissingCredentials,
+    InvalidCredentials,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::MissingCredentials => write!(f, "missing credentials"),
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+        }
+    }
+}
+
+/// Decides who sent a request from its headers, before it is routed.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, headers: &[(&str, &str)]) -> Result<Principal, AuthError>;
+
+    /// The `WWW-Authenticate` challenge sent with a `401`.
+    fn challenge(&self) -> String;
+}
+
+/// Lets every request through.
+pub struct NoAuth;
+
+impl Authenticator for NoAuth {
+    fn authenticate(&self, _headers: &[(&str, &str)]) -> Result<Principal, AuthError> {
+        Ok(Principal::Anonymous)
+    }
+
+    fn challenge(&self) -> String {
+        String::new()
+    }
+}
+
+/// Accepts requests whose `X-Api-Key` header holds one of a fixed set of keys.
+pub struct ApiKeyAuth {
+    keys: Vec<String>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(keys: Vec<String>) -> ApiKeyAuth {
+        ApiKeyAuth { keys }
+    }
+}
+
+impl Authenticator for ApiKeyAuth {
+    fn authenticate(&self, headers: &[(&str, &str)]) -> Result<Principal, AuthError> {
+        let provided = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(API_KEY_HEADER))
+            .map(|&(_, value)| value)
+            .ok_or(AuthError::MissingCredentials)?;
+        self.keys
+            .iter()
+            .position(|key| key == provided)
+            .map(Principal::ApiKey)
+            .ok_or(AuthError::InvalidCredentials)
+    }
+
+    fn challenge(&self) -> String {
+        format!("ApiKey header=\"{}\"", API_KEY_HEADER)
+    }
+}
+
+/// Compares two secrets in time that depends only on their lengths, not on
+/// where they first differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= (x ^ y) as usize;
+    }
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_auth_lets_everything_through() {
+        assert_eq!(NoAuth.authenticate(&[]), Ok(Principal::Anonymous));
+        assert_eq!(NoAuth.authenticate(&[("X-Api-Key", "anything")]), Ok(Principal::Anonymous));
+    }
+
+    #[test]
+    fn test_api_key_auth() {
+        let auth = ApiKeyAuth::new(vec!["first".to_string(), "second".to_string()]);
+
+        assert_eq!(auth.authenticate(&[("x-api-key", "second")]), Ok(Principal::ApiKey(1)));
+        assert_eq!(auth.authenticate(&[("X-Api-Key", "third")]), Err(AuthError::InvalidCredentials));
+        assert_eq!(auth.authenticate(&[("Authorization", "Bearer first")]), Err(AuthError::MissingCredentials));
+        assert_eq!(auth.challenge(), "ApiKey header=\"X-Api-Key\"");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret\0"));
+        assert!(!constant_time_eq(b"secret", b""));
+    }
+}
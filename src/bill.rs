@@ -0,0 +1,113 @@
+use serde::Serialize;
+
+use super::menu::Menu;
+use super::order::{Modifier, Order};
+
+/// One order on a bill with its price after modifiers and quantity.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BillLine {
+    pub item_id: u32,
+    /// The menu price; items without one are billed at zero.
+    pub price_cents: i64,
+    pub modifiers: Vec<Modifier>,
+    pub quantity: u32,
+    pub line_total_cents: i64,
+}
+
+/// What a table owes for its orders.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Bill {
+    pub table_id: u32,
+    pub lines: Vec<BillLine>,
+    pub total_cents: i64,
+    /// Whether the total is under the venue's minimum spend.
+    pub below_minimum: bool,
+    /// How much more must be ordered to reach the minimum.
+    pub shortfall_cents: i64,
+}
+
+impl BillLine {
+    pub fn new(order: &Order, menu: &Menu) -> BillLine {
+        let price_cents = menu
+            .get(order.item_id)
+            .and_then(|item| item.price_cents)
+            .unwrap_or(0) as i64;
+        let modifier_cents: i64 = order.modifiers.iter().map(|m| m.price_delta_cents).sum();
+
+        BillLine {
+            item_id: order.item_id,
+            price_cents,
+            modifiers: order.modifiers.clone(),
+            quantity: order.quantity,
+            line_total_cents: (price_cents + modifier_cents) * order.quantity as i64,
+        }
+    }
+}
+
+impl Bill {
+    /// Prices `orders` with `menu`, listing the lines in item id order.
+    pub fn new(table_id: u32, orders: &[&Order], menu: &Menu) -> Bill {
+        let mut lines: Vec<BillLine> = orders.iter().map(|order| BillLine::new(order, menu)).collect();
+        lines.sort_by_key(|line| line.item_id);
+        let total_cents = lines.iter().map(|line| line.line_total_cents).sum();
+
+        Bill {
+            table_id,
+            lines,
+            total_cents,
+            below_minimum: false,
+            shortfall_cents: 0,
+        }
+    }
+
+    /// Flags the bill if its total is under `min_order_cents`.
+    pub fn with_minimum(mut self, min_order_cents: i64) -> Bill {
+        self.shortfall_cents = (min_order_cents - self.total_cents).max(0);
+        self.below_minimum = self.shortfall_cents > 0;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::menu::MenuItem;
+
+    #[test]
+    fn test_modifiers_in_line_and_bill_total() {
+        let menu = Menu::new(vec![
+            MenuItem::new(1, "Ramen", Some("main")).with_price_cents(1200),
+            MenuItem::new(2, "Tea", Some("drink")).with_price_cents(300),
+        ]);
+        let mut ramen = Order::new(1, 4, 10, 0);
+        ramen.modifiers = vec![
+            Modifier { name: "extra egg".to_string(), price_delta_cents: 150 },
+            Modifier { name: "no nori".to_string(), price_delta_cents: -50 },
+        ];
+        let mut tea = Order::new(2, 4, 5, 0);
+        tea.quantity = 2;
+        let unpriced = Order::new(99, 4, 5, 0);
+
+        let bill = Bill::new(4, &[&unpriced, &ramen, &tea], &menu);
+
+        assert_eq!(bill.lines.iter().map(|l| l.item_id).collect::<Vec<_>>(), vec![1, 2, 99]);
+        assert_eq!(bill.lines[0].line_total_cents, 1300);
+        assert_eq!(bill.lines[1].line_total_cents, 600);
+        assert_eq!(bill.lines[2].line_total_cents, 0);
+        assert_eq!(bill.total_cents, 1900);
+    }
+
+    #[test]
+    fn test_minimum_order() {
+        let menu = Menu::new(vec![MenuItem::new(1, "Ramen", Some("main")).with_price_cents(1200)]);
+        let ramen = Order::new(1, 4, 10, 0);
+
+        let bill = Bill::new(4, &[&ramen], &menu).with_minimum(2000);
+        assert!(bill.below_minimum);
+        assert_eq!(bill.shortfall_cents, 800);
+
+        let bill = Bill::new(4, &[&ramen], &menu).with_minimum(1200);
+        assert!(!bill.below_minimum);
+        assert_eq!(bill.shortfall_cents, 0);
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A source of the current time, so time-dependent behavior can be tested deterministically.
+pub trait Clock: Send + Sync {
+    /// Returns the current time in milliseconds since the Unix epoch.
+    fn now_ms(&self) -> u64;
+}
+
+/// The wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// A clock that only moves when told to.
+#[cfg(test)]
+pub struct FakeClock {
+    now: AtomicU64,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(start_ms: u64) -> FakeClock {
+        FakeClock {
+            now: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock;
+        let first = clock.now_ms();
+
+        assert!(first > 0);
+        assert!(clock.now_ms() >= first);
+    }
+
+    #[test]
+    fn test_fake_clock_advances() {
+        let clock = FakeClock::new(1_000);
+        assert_eq!(clock.now_ms(), 1_000);
+
+        clock.advance(250);
+        assert_eq!(clock.now_ms(), 1_250);
+    }
+}
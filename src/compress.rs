@@ -0,0 +1,141 @@
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Default size below which responses are sent uncompressed.
+pub const DEFAULT_MIN_COMPRESS_BYTES: usize = 1024;
+
+/// A content coding the server can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Parses a coding name such as `gzip` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Encoding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Compresses `data`; `deflate` is the zlib format as HTTP expects.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        // Writing into a Vec cannot fail
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).expect("in-memory write");
+                encoder.finish().expect("in-memory write")
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).expect("in-memory write");
+                encoder.finish().expect("in-memory write")
+            }
+        }
+    }
+}
+
+/// Parses a comma-separated preference list such as `deflate,gzip`.
+///
+/// Unknown names are skipped, so `none` or an empty value disables compression.
+pub fn parse_preference(value: &str) -> Vec<Encoding> {
+    value.split(',').filter_map(Encoding::parse).collect()
+}
+
+/// Picks the encoding for a response from the client's `Accept-Encoding`.
+///
+/// The encoding with the highest `q` value wins; ties go to the one earlier
+/// in `preference`. A `*` entry covers codings the client does not name.
+pub fn negotiate(accept_encoding: &str, preference: &[Encoding]) -> Option<Encoding> {
+    let mut weights = HashMap::new();
+    let mut wildcard = None;
+    for entry in accept_encoding.split(',') {
+        let mut params = entry.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name == "*" {
+            wildcard = Some(q);
+        } else if let Some(encoding) = Encoding::parse(name) {
+            weights.insert(encoding, q);
+        }
+    }
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for &encoding in preference {
+        let q = weights.get(&encoding).copied().or(wildcard).unwrap_or(0.0);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::io::Read;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let json = r#"{"item_id":16,"table_id":15,"status":"Pending"}"#.repeat(40);
+
+        let mut decoded = String::new();
+        GzDecoder::new(&Encoding::Gzip.encode(json.as_bytes())[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, json);
+
+        decoded.clear();
+        let zlib = Encoding::Deflate.encode(json.as_bytes());
+        ZlibDecoder::new(&zlib[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, json);
+        assert!(zlib.len() < json.len() / 10);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let both = [Encoding::Gzip, Encoding::Deflate];
+        let deflate_first = [Encoding::Deflate, Encoding::Gzip];
+
+        // Equal weights follow the server's preference
+        assert_eq!(negotiate("gzip, deflate", &both), Some(Encoding::Gzip));
+        assert_eq!(
+            negotiate("gzip, deflate", &deflate_first),
+            Some(Encoding::Deflate)
+        );
+        // Client weights win over the server's preference
+        assert_eq!(
+            negotiate("gzip;q=0.5, deflate", &both),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(
+            negotiate("identity, *;q=0.1", &deflate_first),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(negotiate("gzip;q=0, identity", &both), None);
+        assert_eq!(negotiate("identity", &both), None);
+        assert_eq!(negotiate("gzip", &[]), None);
+        assert_eq!(negotiate("gzip", &parse_preference("none")), None);
+        assert_eq!(parse_preference("deflate, gzip"), deflate_first);
+    }
+}
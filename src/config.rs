@@ -0,0 +1,295 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::auth::ApiKeyAuth;
+use super::compress::{self, Encoding};
+use super::confirm;
+use super::events;
+use super::log::{self, Format, Level, Logger};
+use super::menu::{CategoryPriorities, ItemNames, Menu, Substitutions};
+use super::restaurant::{self, OversizedBatch, Restaurant};
+use super::webhook::Webhook;
+
+/// Default address the server listens on.
+pub const DEFAULT_BIND_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 8080);
+
+/// Default number of tables in the restaurant.
+pub const DEFAULT_TABLE_COUNT: usize = 150;
+
+/// Default time to wait for in-flight connections on shutdown.
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
+/// Default number of concurrent connections allowed from one peer IP.
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 64;
+
+/// Every tunable of the server, read once at startup.
+///
+/// `from_env` reads the `RESTO_*` environment variables; `from_vars` takes
+/// any lookup, so tests can build a configuration from explicit overrides.
+/// Unset or invalid values fall back to their defaults.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind_addr: SocketAddr,
+    /// Unix domain socket to listen on instead of `bind_addr`.
+    pub uds_path: Option<PathBuf>,
+    pub table_count: usize,
+    /// Tables kept in memory before idle ones are evicted; `0` keeps them all.
+    pub max_resident_tables: usize,
+    pub shutdown_timeout: Duration,
+    pub reject_orders_while_draining: bool,
+    pub max_connections_per_ip: usize,
+    pub summary_path: Option<PathBuf>,
+    pub auto_occupancy: bool,
+    pub max_path_length: usize,
+    pub max_path_segments: usize,
+    pub max_decoded_path_length: usize,
+    pub log_level: Level,
+    pub log_format: Format,
+    pub log_bodies: bool,
+    pub log_body_limit: usize,
+    pub admin_token: Option<String>,
+    /// Keys accepted in `X-Api-Key`; when empty, requests are not authenticated.
+    pub api_keys: Vec<String>,
+    pub delete_no_content: bool,
+    pub server_header: bool,
+    pub dev_mode: bool,
+    pub artificial_delay_ms: u64,
+    pub strict_query: bool,
+    pub strict_json: bool,
+    pub compression: Vec<Encoding>,
+    pub compression_min_bytes: usize,
+    pub min_order_cents: i64,
+    pub coalesce_window_ms: u64,
+    pub event_log_capacity: usize,
+    pub order_rate_limit: u32,
+    pub max_in_flight_per_table: usize,
+    pub max_total_orders: usize,
+    pub max_quantity_per_item: u32,
+    pub keepalive_max: usize,
+    pub header_timeout_ms: u64,
+    pub max_response_bytes: usize,
+    pub max_items_per_request: usize,
+    pub oversized_batch: OversizedBatch,
+    pub menu_file: Option<PathBuf>,
+    pub category_priorities: CategoryPriorities,
+    pub substitutions: Substitutions,
+    pub confirmation_code_length: usize,
+    pub item_names_file: Option<PathBuf>,
+    pub webhook_url: Option<String>,
+}
+
+impl Config {
+    /// Reads the configuration from the process environment.
+    pub fn from_env() -> Config {
+        Config::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the configuration through `var`, which returns the value of a
+    /// `RESTO_*` variable if it is set.
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Config {
+        let flag = |name: &str| var(name).is_some_and(|v| v == "1");
+
+        Config {
+            bind_addr: parsed(&var, "RESTO_BIND_ADDR").unwrap_or_else(|| SocketAddr::from(DEFAULT_BIND_ADDR)),
+            uds_path: var("RESTO_UDS_PATH").map(PathBuf::from),
+            table_count: parsed(&var, "RESTO_TABLE_COUNT").unwrap_or(DEFAULT_TABLE_COUNT),
+            max_resident_tables: parsed(&var, "RESTO_MAX_RESIDENT_TABLES").unwrap_or(0),
+            shutdown_timeout: parse_shutdown_timeout(var("RESTO_SHUTDOWN_TIMEOUT").as_deref()),
+            reject_orders_while_draining: flag("RESTO_REJECT_ORDERS_WHILE_DRAINING"),
+            max_connections_per_ip: parsed(&var, "RESTO_MAX_CONNECTIONS_PER_IP")
+                .unwrap_or(DEFAULT_MAX_CONNECTIONS_PER_IP),
+            summary_path: var("RESTO_SUMMARY_FILE").map(PathBuf::from),
+            auto_occupancy: var("RESTO_AUTO_OCCUPANCY").is_none_or(|v| v != "0"),
+            max_path_length: parsed(&var, "RESTO_MAX_PATH_LENGTH").unwrap_or(restaurant::DEFAULT_MAX_PATH_LENGTH),
+            max_path_segments: parsed(&var, "RESTO_MAX_PATH_SEGMENTS")
+                .unwrap_or(restaurant::DEFAULT_MAX_PATH_SEGMENTS),
+            max_decoded_path_length: parsed(&var, "RESTO_MAX_DECODED_PATH_LENGTH")
+                .unwrap_or(restaurant::DEFAULT_MAX_DECODED_PATH_LENGTH),
+            log_level: var("RESTO_LOG_LEVEL").and_then(|v| Level::parse(&v)).unwrap_or(Level::Info),
+            log_format: var("RESTO_LOG_FORMAT").and_then(|v| Format::parse(&v)).unwrap_or(Format::Pretty),
+            log_bodies: flag("RESTO_LOG_BODIES"),
+            log_body_limit: parsed(&var, "RESTO_LOG_BODY_LIMIT").unwrap_or(log::DEFAULT_BODY_LOG_LIMIT),
+            admin_token: var("RESTO_ADMIN_TOKEN"),
+            api_keys: var("RESTO_API_KEYS")
+                .map(|keys| {
+                    keys.split(',')
+                        .map(str::trim)
+                        .filter(|key| !key.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            delete_no_content: flag("RESTO_DELETE_NO_CONTENT"),
+            server_header: var("RESTO_SERVER_HEADER").is_none_or(|v| v != "0"),
+            dev_mode: flag("RESTO_DEV_MODE"),
+            artificial_delay_ms: parsed(&var, "RESTO_ARTIFICIAL_DELAY_MS").unwrap_or(0),
+            strict_query: flag("RESTO_STRICT_QUERY"),
+            strict_json: flag("RESTO_STRICT_JSON"),
+            compression: var("RESTO_COMPRESSION").map_or(Vec::new(), |v| compress::parse_preference(&v)),
+            compression_min_bytes: parsed(&var, "RESTO_COMPRESSION_MIN_BYTES")
+                .unwrap_or(compress::DEFAULT_MIN_COMPRESS_BYTES),
+            min_order_cents: parsed(&var, "RESTO_MIN_ORDER_CENTS").unwrap_or(0),
+            coalesce_window_ms: parsed(&var, "RESTO_COALESCE_WINDOW_MS").unwrap_or(0),
+            event_log_capacity: parsed(&var, "RESTO_EVENT_LOG_CAPACITY").unwrap_or(events::DEFAULT_EVENT_LOG_CAPACITY),
+            order_rate_limit: parsed(&var, "RESTO_ORDER_RATE_LIMIT").unwrap_or(0),
+            max_in_flight_per_table: parsed(&var, "RESTO_MAX_IN_FLIGHT_PER_TABLE").unwrap_or(0),
+            max_total_orders: parsed(&var, "RESTO_MAX_TOTAL_ORDERS").unwrap_or(0),
+            max_quantity_per_item: parsed(&var, "RESTO_MAX_QUANTITY_PER_ITEM").unwrap_or(0),
+            keepalive_max: parsed(&var, "RESTO_KEEPALIVE_MAX").unwrap_or(restaurant::DEFAULT_KEEPALIVE_MAX),
+            header_timeout_ms: parsed(&var, "RESTO_HEADER_TIMEOUT_MS").unwrap_or(restaurant::DEFAULT_HEADER_TIMEOUT_MS),
+            max_response_bytes: parsed(&var, "RESTO_MAX_RESPONSE_BYTES")
+                .unwrap_or(restaurant::DEFAULT_MAX_RESPONSE_BYTES),
+            max_items_per_request: parsed(&var, "RESTO_MAX_ITEMS_PER_REQUEST")
+                .unwrap_or(restaurant::DEFAULT_MAX_ITEMS_PER_REQUEST),
+            oversized_batch: var("RESTO_OVERSIZED_BATCH")
+                .and_then(|v| OversizedBatch::parse(&v))
+                .unwrap_or(OversizedBatch::Reject),
+            menu_file: var("RESTO_MENU_FILE").map(PathBuf::from),
+            category_priorities: var("RESTO_CATEGORY_PRIORITIES")
+                .and_then(|v| CategoryPriorities::parse(&v))
+                .unwrap_or_default(),
+            substitutions: var("RESTO_SUBSTITUTIONS")
+                .and_then(|v| Substitutions::parse(&v))
+                .unwrap_or_default(),
+            confirmation_code_length: parsed(&var, "RESTO_CONFIRMATION_CODE_LENGTH")
+                .unwrap_or(confirm::DEFAULT_CODE_LENGTH),
+            item_names_file: var("RESTO_ITEM_NAMES").map(PathBuf::from),
+            webhook_url: var("RESTO_WEBHOOK_URL"),
+        }
+    }
+
+    /// Builds the logger described by the configuration.
+    pub fn logger(&self) -> Logger {
+        Logger::new(self.log_level)
+            .with_format(self.log_format)
+            .with_body_logging(self.log_bodies, self.log_body_limit)
+    }
+
+    /// Builds the restaurant described by the configuration, loading the menu
+    /// and item names files and starting the webhook if they are set.
+    ///
+    /// Must be called inside a Tokio runtime when a webhook URL is set.
+    pub fn restaurant(&self) -> Result<Restaurant, String> {
+        let mut restaurant = Restaurant::new(self.table_count)
+            .with_max_resident_tables(self.max_resident_tables)
+            .with_auto_occupancy(self.auto_occupancy)
+            .with_path_limits(self.max_path_length, self.max_path_segments)
+            .with_max_decoded_path_length(self.max_decoded_path_length)
+            .with_logger(self.logger())
+            .with_admin_token(self.admin_token.clone())
+            .with_delete_no_content(self.delete_no_content)
+            .with_server_header(self.server_header)
+            .with_dev_mode(self.dev_mode)
+            .with_reject_orders_while_draining(self.reject_orders_while_draining)
+            .with_artificial_delay_ms(self.artificial_delay_ms)
+            .with_strict_query(self.strict_query)
+            .with_strict_json(self.strict_json)
+            .with_compression(self.compression.clone(), self.compression_min_bytes)
+            .with_min_order_cents(self.min_order_cents)
+            .with_coalesce_window_ms(self.coalesce_window_ms)
+            .with_event_log_capacity(self.event_log_capacity)
+            .with_order_rate_limit(self.order_rate_limit)
+            .with_max_in_flight_per_table(self.max_in_flight_per_table)
+            .with_max_total_orders(self.max_total_orders)
+            .with_max_quantity_per_item(self.max_quantity_per_item)
+            .with_keepalive_max(self.keepalive_max)
+            .with_header_timeout_ms(self.header_timeout_ms)
+            .with_max_response_bytes(self.max_response_bytes)
+            .with_max_items_per_request(self.max_items_per_request, self.oversized_batch)
+            .with_category_priorities(self.category_priorities.clone())
+            .with_substitutions(self.substitutions.clone())
+            .with_confirmation_code_length(self.confirmation_code_length);
+        if !self.api_keys.is_empty() {
+            restaurant = restaurant.with_authenticator(Arc::new(ApiKeyAuth::new(self.api_keys.clone())));
+        }
+        if let Some(path) = &self.menu_file {
+            restaurant = restaurant.with_menu(Menu::load(path)?);
+        }
+        if let Some(path) = &self.item_names_file {
+            restaurant = restaurant.with_item_names(ItemNames::load(path)?);
+        }
+        if let Some(url) = &self.webhook_url {
+            let logger = restaurant.logger().clone();
+            restaurant = restaurant.with_webhook(Webhook::spawn(url, logger)?);
+        }
+        Ok(restaurant)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::from_vars(|_| None)
+    }
+}
+
+/// Parses the variable `name` through `var`, or returns `None` if it is unset or invalid.
+fn parsed<T: FromStr, F: Fn(&str) -> Option<String>>(var: &F, name: &str) -> Option<T> {
+    var(name).and_then(|v| v.trim().parse().ok())
+}
+
+/// Parses the drain timeout from the `RESTO_SHUTDOWN_TIMEOUT` value (in seconds).
+///
+/// Falls back to `DEFAULT_SHUTDOWN_TIMEOUT_SECS` when the value is missing or invalid.
+fn parse_shutdown_timeout(value: Option<&str>) -> Duration {
+    let secs = value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_shutdown_timeout() {
+        assert_eq!(parse_shutdown_timeout(None), Duration::from_secs(10));
+        assert_eq!(parse_shutdown_timeout(Some("3")), Duration::from_secs(3));
+        assert_eq!(parse_shutdown_timeout(Some("soon")), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_overrides_with_defaults() {
+        let overrides: HashMap<&str, &str> = HashMap::from([
+            ("RESTO_BIND_ADDR", "0.0.0.0:9000"),
+            ("RESTO_TABLE_COUNT", "20"),
+            ("RESTO_KEEPALIVE_MAX", "7"),
+            ("RESTO_OVERSIZED_BATCH", "truncate"),
+            ("RESTO_AUTO_OCCUPANCY", "0"),
+            // Invalid values fall back to the default
+            ("RESTO_MAX_PATH_LENGTH", "long"),
+        ]);
+        let config = Config::from_vars(|name| overrides.get(name).map(|v| v.to_string()));
+
+        assert_eq!(config.bind_addr, "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(config.table_count, 20);
+        assert_eq!(config.keepalive_max, 7);
+        assert_eq!(config.oversized_batch, OversizedBatch::Truncate);
+        assert!(!config.auto_occupancy);
+        assert_eq!(config.max_path_length, restaurant::DEFAULT_MAX_PATH_LENGTH);
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT_SECS));
+        assert_eq!(config.max_connections_per_ip, DEFAULT_MAX_CONNECTIONS_PER_IP);
+        assert_eq!(config.max_items_per_request, restaurant::DEFAULT_MAX_ITEMS_PER_REQUEST);
+        assert_eq!(config.log_level, Level::Info);
+        assert!(config.server_header);
+        assert!(config.uds_path.is_none());
+
+        let restaurant = config.restaurant().unwrap();
+        assert!(restaurant.try_get_table(19).is_some());
+        assert!(restaurant.try_get_table(20).is_none());
+        assert_eq!(restaurant.keepalive_max(), 7);
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+
+        assert_eq!(config.bind_addr, SocketAddr::from(DEFAULT_BIND_ADDR));
+        assert_eq!(config.table_count, DEFAULT_TABLE_COUNT);
+        assert!(config.compression.is_empty());
+    }
+}
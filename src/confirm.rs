@@ -0,0 +1,122 @@
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+
+/// Crockford's base32 alphabet, which leaves out I, L, O and U so codes are
+/// hard to misread.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// How many digits a confirmation code has at least, padded with zeros.
+pub const DEFAULT_CODE_LENGTH: usize = 4;
+
+/// How many confirmations are kept before the oldest are forgotten.
+pub const DEFAULT_CONFIRMATION_CAPACITY: usize = 10_000;
+
+/// Where the orders placed by one request ended up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confirmation {
+    pub table_id: u32,
+    pub item_ids: Vec<u32>,
+}
+
+/// Hands out a confirmation code per order request and resolves codes back to
+/// the table and items they were issued for.
+///
+/// Codes are the request's sequence number in base32, so the index is keyed by
+/// sequence number and the oldest entries are dropped first once full.
+pub struct Confirmations {
+    code_length: usize,
+    capacity: usize,
+    state: Mutex<Index>,
+}
+
+#[derive(Default)]
+struct Index {
+    next_seq: u64,
+    entries: BTreeMap<u64, Confirmation>,
+}
+
+impl Default for Confirmations {
+    fn default() -> Confirmations {
+        Confirmations::new(DEFAULT_CODE_LENGTH, DEFAULT_CONFIRMATION_CAPACITY)
+    }
+}
+
+impl Confirmations {
+    pub fn new(code_length: usize, capacity: usize) -> Confirmations {
+        Confirmations {
+            code_length,
+            capacity,
+            state: Mutex::new(Index { next_seq: 1, ..Index::default() }),
+        }
+    }
+
+    /// Records where a request's orders went and returns its code.
+    pub fn issue(&self, confirmation: Confirmation) -> String {
+        let mut state = self.state.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.entries.insert(seq, confirmation);
+        while state.entries.len() > self.capacity {
+            state.entries.pop_first();
+        }
+        encode(seq, self.code_length)
+    }
+
+    /// Looks up a code, ignoring case.
+    pub fn resolve(&self, code: &str) -> Option<Confirmation> {
+        let seq = decode(code)?;
+        self.state.lock().entries.get(&seq).cloned()
+    }
+}
+
+/// Writes `seq` in base32, left-padded with zeros to `min_length` digits.
+fn encode(mut seq: u64, min_length: usize) -> String {
+    let mut digits = Vec::new();
+    while seq > 0 || digits.is_empty() {
+        digits.push(ALPHABET[(seq % 32) as usize]);
+        seq /= 32;
+    }
+    while digits.len() < min_length {
+        digits.push(b'0');
+    }
+    digits.iter().rev().map(|&digit| digit as char).collect()
+}
+
+fn decode(code: &str) -> Option<u64> {
+    if code.is_empty() {
+        return None;
+    }
+    code.bytes().try_fold(0u64, |seq, digit| {
+        let value = ALPHABET.iter().position(|&c| c == digit.to_ascii_uppercase())?;
+        seq.checked_mul(32)?.checked_add(value as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(encode(1, 4), "0001");
+        assert_eq!(encode(32, 4), "0010");
+        assert_eq!(encode(31, 0), "Z");
+        assert_eq!(encode(0, 0), "0");
+        assert_eq!(decode("0010"), Some(32));
+        assert_eq!(decode("00z"), Some(31));
+        assert_eq!(decode("00U"), None);
+        assert_eq!(decode(""), None);
+    }
+
+    #[test]
+    fn test_confirmations_forget_the_oldest() {
+        let confirmations = Confirmations::new(4, 2);
+        let codes: Vec<String> = (1..=3)
+            .map(|table_id| confirmations.issue(Confirmation { table_id, item_ids: vec![7] }))
+            .collect();
+
+        assert_eq!(codes, ["0001", "0002", "0003"]);
+        assert_eq!(confirmations.resolve("0001"), None);
+        assert_eq!(confirmations.resolve("0003").map(|c| c.table_id), Some(3));
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+/// Tracks the number of open connections per peer IP and enforces a cap.
+#[derive(Clone)]
+pub struct IpConnectionLimiter {
+    max_per_ip: usize,
+    active: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// Holds one connection slot for an IP; the slot is released when dropped.
+pub struct IpConnectionGuard {
+    ip: IpAddr,
+    active: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpConnectionLimiter {
+    /// Creates a limiter allowing `max_per_ip` concurrent connections per IP.
+    /// A cap of `0` disables the limit.
+    pub fn new(max_per_ip: usize) -> IpConnectionLimiter {
+        IpConnectionLimiter {
+            max_per_ip,
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserves a connection slot for `ip`, or returns `None` if the IP is at its cap.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<IpConnectionGuard> {
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(ip).or_insert(0);
+
+        if self.max_per_ip > 0 && *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+
+        Some(IpConnectionGuard {
+            ip,
+            active: Arc::clone(&self.active),
+        })
+    }
+}
+
+impl Drop for IpConnectionGuard {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_per_ip() {
+        let limiter = IpConnectionLimiter::new(2);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let first = limiter.try_acquire(ip);
+        let _second = limiter.try_acquire(ip);
+        assert!(first.is_some());
+        assert!(limiter.try_acquire(ip).is_none());
+        assert!(limiter.try_acquire(other).is_some());
+
+        // Releasing a slot lets the IP connect again
+        drop(first);
+        assert!(limiter.try_acquire(ip).is_some());
+    }
+
+    #[test]
+    fn test_zero_disables_limit() {
+        let limiter = IpConnectionLimiter::new(0);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let guards: Vec<_> = (0..100).map(|_| limiter.try_acquire(ip)).collect();
+        assert!(guards.iter().all(Option::is_some));
+    }
+}
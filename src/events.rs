@@ -0,0 +1,183 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use super::order::Order;
+
+/// Default number of events kept before the oldest are dropped.
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 1000;
+
+/// What happened to an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Added,
+    Removed,
+    /// Sent to the kitchen.
+    Fired,
+    /// Sent to the kitchen again, its status unchanged.
+    Resent,
+    Served,
+    /// Moved onto the table from another one.
+    Merged,
+    /// Taken off the table and kept in the void log.
+    Voided,
+}
+
+/// One change to a table's orders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    /// Position in the log, counting up from `1` with every event recorded,
+    /// so events recorded in the same millisecond still page apart.
+    pub seq: u64,
+    pub table_id: u32,
+    pub item_id: u32,
+    pub action: Action,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Why the order was removed, if staff said so.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A restaurant-wide feed of order changes, oldest first.
+///
+/// Only the most recent `capacity` events are kept.
+pub struct EventLog {
+    capacity: usize,
+    events: Mutex<VecDeque<Event>>,
+}
+
+impl EventLog {
+    /// Creates a log keeping up to `capacity` events. A capacity of `0` keeps none.
+    pub fn new(capacity: usize) -> EventLog {
+        EventLog {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Appends an event at `now_ms`, dropping the oldest one when the log is full.
+    ///
+    /// Timestamps never go backwards, so an event recorded by a request
+    /// that read the clock a little earlier still sorts after the last one.
+    pub fn record(&self, table_id: u32, item_id: u32, action: Action, reason: Option<String>, now_ms: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock();
+        let timestamp = events.back().map_or(now_ms, |last| now_ms.max(last.timestamp));
+        let seq = events.back().map_or(1, |last| last.seq + 1);
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(Event {
+            seq,
+            table_id,
+            item_id,
+            action,
+            timestamp,
+            reason,
+        });
+    }
+
+    /// Returns up to `limit` of the oldest events after sequence number
+    /// `since_seq` and recorded after `since_ms`.
+    pub fn page(&self, since_seq: u64, since_ms: u64, limit: usize) -> Vec<Event> {
+        let events = self.events.lock();
+        events
+            .iter()
+            .filter(|event| event.seq > since_seq && event.timestamp > since_ms)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> EventLog {
+        EventLog::new(DEFAULT_EVENT_LOG_CAPACITY)
+    }
+}
+
+/// An order voided by staff.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VoidedOrder {
+    /// The order as it was when voided, with its status set to `Voided`.
+    pub order: Order,
+    pub reason: String,
+    /// Milliseconds since the Unix epoch.
+    pub voided_at: u64,
+    /// Whether the order had been served, so voiding it needed a manager.
+    pub manager_override: bool,
+}
+
+/// Every order voided since the server started, oldest first.
+///
+/// Unlike the event log nothing is ever dropped or changed, so the log
+/// stays complete for audits.
+#[derive(Default)]
+pub struct VoidLog {
+    voids: Mutex<Vec<VoidedOrder>>,
+}
+
+impl VoidLog {
+    pub fn record(&self, voided: VoidedOrder) {
+        self.voids.lock().push(voided);
+    }
+
+    /// Returns the voided orders of `table_id`, oldest first.
+    pub fn for_table(&self, table_id: u32) -> Vec<VoidedOrder> {
+        self.voids
+            .lock()
+            .iter()
+            .filter(|voided| voided.order.table_id == table_id)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let log = EventLog::new(2);
+        log.record(1, 10, Action::Added, None, 100);
+        log.record(2, 11, Action::Added, None, 200);
+        log.record(1, 10, Action::Removed, Some("out_of_stock".to_string()), 300);
+
+        let events = log.page(0, 0, 10);
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].table_id, events[0].item_id), (2, 11));
+        assert_eq!(events[1].action, Action::Removed);
+        assert_eq!(events[1].reason.as_deref(), Some("out_of_stock"));
+    }
+
+    #[test]
+    fn test_timestamps_never_go_backwards() {
+        let log = EventLog::new(10);
+        log.record(1, 10, Action::Added, None, 500);
+        log.record(2, 11, Action::Added, None, 400);
+
+        let events = log.page(0, 0, 10);
+        assert_eq!(events[1].timestamp, 500);
+    }
+
+    #[test]
+    fn test_paging_by_seq_within_one_millisecond() {
+        let log = EventLog::new(10);
+        for item_id in 0..3 {
+            log.record(1, item_id, Action::Added, None, 500);
+        }
+        log.record(2, 9, Action::Added, None, 600);
+
+        // Paging by timestamp would skip the rest of the batch after the first page
+        let first = log.page(0, 0, 2);
+        assert_eq!(first.iter().map(|event| event.seq).collect::<Vec<_>>(), vec![1, 2]);
+        let second = log.page(first[1].seq, 0, 2);
+        assert_eq!(second.iter().map(|event| (event.seq, event.item_id)).collect::<Vec<_>>(), vec![(3, 2), (4, 9)]);
+        assert_eq!(log.page(0, 500, 10).len(), 1);
+    }
+}
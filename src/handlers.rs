@@ -1,58 +1,722 @@
-use crate::{AddOrderRequest, Restaurant};
-use serde_json;
-use serde_json::json;
+use crate::auth::constant_time_eq;
+use crate::bill::Bill;
+use crate::events::{Action, VoidedOrder};
+use crate::http::{self, PathError, PathParams, RawRequest, Response};
+use crate::order::{Order, OrderStatus};
+use crate::webhook::OrderReady;
+use crate::restaurant::{MutationGuard, OversizedBatch, TablePtr};
+use crate::table::{StatusCounts, Table, TableSummary};
+use crate::{internal_server_error, AddOrderRequest, Restaurant};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+struct OccupancyRequest {
+    occupied: bool,
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    from: u32,
+}
+
+#[derive(Deserialize)]
+struct CancelRequest {
+    reason: Option<String>,
+}
+
+/// Longest cancellation reason accepted, in characters.
+const MAX_CANCEL_REASON_CHARS: usize = 200;
+
+/// Reads why orders are being removed from `?reason=` or, failing that, a
+/// `{"reason": ".."}` body. An empty reason counts as none.
+fn cancel_reason(request: &RawRequest<'_>, restaurant: &Restaurant) -> Result<Option<String>, String> {
+    let (_, query) = http::split_target(request.target);
+    let reason = match http::parse_query(query).remove("reason") {
+        Some(reason) => Some(reason),
+        None if request.body.trim().is_empty() => None,
+        None => {
+            let cancel: CancelRequest = serde_json::from_str(request.body).map_err(|err| {
+                let response = json!({
+                    "success": false,
+                    "message": format!("Failed to parse cancel request: {}", err)
+                });
+                response.to_string()
+            })?;
+            reject_unknown_fields::<CancelRequest>(request.body, restaurant)?;
+            cancel.reason
+        }
+    };
+    match reason {
+        Some(reason) if reason.chars().count() > MAX_CANCEL_REASON_CHARS => Err(format!(
+            "{{\"message\":\"reason exceeds {} characters\",\"success\":false}}",
+            MAX_CANCEL_REASON_CHARS
+        )),
+        Some(reason) if reason.is_empty() => Ok(None),
+        reason => Ok(reason),
+    }
+}
+
+#[derive(Deserialize)]
+struct SwapRequest {
+    new_item_id: u32,
+}
+
+#[derive(Deserialize)]
+struct BumpRequest {
+    seconds: u32,
+}
+
+#[derive(Deserialize)]
+struct ResendRequest {
+    items: Option<Vec<u32>>,
+}
+
+#[derive(Deserialize)]
+struct SplitRequest {
+    groups: Vec<Vec<u32>>,
+}
+
+/// An order as returned to clients, named when item names are configured.
+#[derive(Debug, Serialize)]
+struct OrderView<'a> {
+    #[serde(flatten)]
+    order: &'a Order,
+    /// Milliseconds since the Unix epoch when the order should be ready.
+    ready_at: u64,
+    /// `None` without a name map; `Some(None)` serializes as `null` for unknown items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<Option<&'a str>>,
+}
+
+impl<'a> OrderView<'a> {
+    fn new(order: &'a Order, restaurant: &'a Restaurant) -> OrderView<'a> {
+        OrderView {
+            order,
+            ready_at: order.ready_at(),
+            name: restaurant.item_names().map(|names| names.get(order.item_id)),
+        }
+    }
+}
+
+/// An outstanding order in the kitchen queue together with its station.
+#[derive(Debug, Serialize)]
+struct QueueEntry<'a> {
+    #[serde(flatten)]
+    order: Order,
+    station: &'a str,
+}
+
+/// Returns the names of the fields `T` reads from a JSON object, as its
+/// serde derive declares them.
+///
+/// Serde hands a struct's field names to the deserializer, so this one
+/// records them and stops there.
+fn struct_fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// In strict JSON mode, rejects a body naming a field `T` does not have.
+///
+/// Bodies that are not JSON objects are left for `T`'s own parsing to reject.
+fn reject_unknown_fields<T: DeserializeOwned>(body: &str, restaurant: &Restaurant) -> Result<(), String> {
+    if !restaurant.strict_json() {
+        return Ok(());
+    }
+    let Ok(Value::Object(object)) = serde_json::from_str::<Value>(body) else {
+        return Ok(());
+    };
+    let fields = struct_fields::<T>();
+    match object.keys().find(|key| !fields.contains(&key.as_str())) {
+        Some(field) => {
+            let response = json!({
+                "success": false,
+                "message": format!("unknown field '{}'", field)
+            });
+            Err(response.to_string())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Returns the name of the order request field holding a number too large
+/// for a `u32`, so clients get a clearer error than serde's generic one.
+fn overflowing_field(body: &str) -> Option<&'static str> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let too_large = |v: &Value| match v.as_u64() {
+        Some(n) => n > u32::MAX as u64,
+        None => v.as_f64().is_some_and(|f| f > u32::MAX as f64),
+    };
+
+    if value.get("table_id").is_some_and(too_large) {
+        return Some("table_id");
+    }
+    if value.get("items")?.as_array()?.iter().any(too_large) {
+        return Some("item_id");
+    }
+    None
+}
+
+/// Returns when the order is ready, or `None` if it is not on the table or
+/// has already been served.
+fn unserved_ready_at(restaurant: &Restaurant, table_id: u32, item_id: u32) -> Option<u64> {
+    let table = restaurant.find_table(table_id)?;
+    let table = table.read();
+    let order = table.get_order(item_id)?;
+    matches!(order.status, OrderStatus::Pending | OrderStatus::Cooking).then(|| order.ready_at())
+}
+
+/// Arms the webhook to announce the order once its waiting time has elapsed.
+///
+/// An order has at most one timer, so arming it again, as after a bump,
+/// only moves its deadline. When the deadline passes the order is looked up
+/// again and announced only if it is still on the table, unserved and due by
+/// `restaurant.now_ms()`. Must be called with no table lock held.
+fn schedule_ready_notification(restaurant: &Restaurant, table_id: u32, item_id: u32) {
+    let webhook = match restaurant.webhook() {
+        Some(webhook) => webhook.clone(),
+        None => return,
+    };
+    let ready_at = match unserved_ready_at(restaurant, table_id, item_id) {
+        Some(ready_at) => ready_at,
+        None => return webhook.timers().cancel(table_id, item_id),
+    };
+    let wake = match webhook.timers().arm(table_id, item_id, ready_at) {
+        Some(wake) => wake,
+        None => return,
+    };
+
+    let restaurant = restaurant.clone();
+    tokio::spawn(async move {
+        let timers = webhook.timers();
+        while let Some(deadline) = timers.deadline(table_id, item_id) {
+            let now = restaurant.now_ms();
+            if deadline > now {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_millis(deadline - now)) => {}
+                    _ = wake.notified() => {}
+                }
+                continue;
+            }
+
+            match unserved_ready_at(&restaurant, table_id, item_id) {
+                // The order was replaced since it was armed; wait for its new time
+                Some(ready_at) if ready_at > now => {
+                    timers.arm(table_id, item_id, ready_at);
+                }
+                order => {
+                    if timers.finish(table_id, item_id, deadline) {
+                        if order.is_some() {
+                            webhook.notify(OrderReady::new(table_id, item_id));
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Drops the order's pending ready notification, if there is one.
+fn cancel_ready_notification(restaurant: &Restaurant, table_id: u32, item_id: u32) {
+    if let Some(webhook) = restaurant.webhook() {
+        webhook.timers().cancel(table_id, item_id);
+    }
+}
+
+/// Checks the admin token sent as `X-Admin-Token` or `Authorization: Bearer`.
+///
+/// Returns the error response to send when the request is not authorized.
+fn require_admin(request: &RawRequest, restaurant: &Restaurant) -> Result<(), Response> {
+    let expected = match restaurant.admin_token() {
+        Some(token) => token,
+        None => {
+            return Err(Response::new(403)
+                .with_body(json!({"success": false, "message": "admin token not configured"})))
+        }
+    };
+
+    let provided = request.header("X-Admin-Token").or_else(|| {
+        request.header("Authorization").and_then(|value| value.strip_prefix("Bearer "))
+    });
+    if provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), expected.as_bytes())) {
+        Ok(())
+    } else {
+        Err(Response::new(401)
+            .with_body(json!({"success": false, "message": "invalid admin token"})))
+    }
+}
+
+/// How long the verbose health check waits for each table lock.
+const HEALTH_LOCK_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How long the verbose health check waits for a probe task to run.
+const RUNTIME_PROBE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long the accept loop may go without checking in before the verbose
+/// health check calls it wedged. It checks in every second.
+const ACCEPT_LOOP_STALE_MS: u64 = 5_000;
+
+/// Longest a long-polling request may wait for a change.
+const MAX_POLL_SECONDS: u64 = 60;
+
+/// Waits up to `wait` seconds for the table to change.
+///
+/// Returns whether a change happened before the timeout.
+async fn wait_for_change(restaurant: &Restaurant, table_id: u32, wait: &str) -> Result<bool, String> {
+    let seconds = wait.parse::<u64>().map_err(|_| "Invalid wait")?;
+    let timeout = Duration::from_secs(seconds.min(MAX_POLL_SECONDS));
+    Ok(restaurant.wait_for_table_change(table_id, timeout).await)
+}
+
+/// Looks up a table by id before any lock is taken.
+///
+/// Returns the `404` response to send when the table does not exist.
+fn existing_table(restaurant: &Restaurant, table_id: u32) -> Result<TablePtr, Response> {
+    restaurant
+        .try_get_table(table_id)
+        .ok_or_else(|| not_found(format!("table {} does not exist", table_id)))
+}
+
+/// Runs `f` under the table's read lock; see `Restaurant::with_table_read`.
+///
+/// Returns the `404` response to send when the table does not exist.
+fn read_table<R>(restaurant: &Restaurant, table_id: u32, f: impl FnOnce(&Table) -> R) -> Result<R, Response> {
+    restaurant
+        .with_table_read(table_id, f)
+        .ok_or_else(|| not_found(format!("table {} does not exist", table_id)))
+}
+
+/// Runs `f` under the table's write lock; see `Restaurant::with_table_write`.
+///
+/// Returns the `404` response to send when the table does not exist.
+fn write_table<R>(restaurant: &Restaurant, table_id: u32, f: impl FnOnce(&mut Table) -> R) -> Result<R, Response> {
+    restaurant
+        .with_table_write(table_id, f)
+        .ok_or_else(|| not_found(format!("table {} does not exist", table_id)))
+}
+
+/// Builds a `409 Conflict` response with a JSON error message.
+fn conflict(message: String) -> Response {
+    let response = json!({
+        "success": false,
+        "message": message
+    });
+
+    Response::new(409).with_body(response)
+}
+
+/// Builds a `404 Not Found` response with a JSON error message.
+fn not_found(message: String) -> Response {
+    let response = json!({
+        "success": false,
+        "message": message
+    });
+
+    Response::new(404).with_body(response)
+}
+
+/// Builds the JSON error body for a bad path parameter, naming its field.
+fn path_error(err: PathError) -> String {
+    let response = json!({
+        "success": false,
+        "message": err.to_string(),
+        "field": err.field()
+    });
+
+    response.to_string()
+}
+
+/// Builds a `429` response asking the client to retry after `retry_after_secs`.
+fn too_many_requests(message: &str, retry_after_secs: u64) -> Response {
+    let response = json!({
+        "success": false,
+        "message": message
+    });
+
+    Response::new(429)
+        .with_header("Retry-After", retry_after_secs)
+        .with_body(response)
+}
+
+/// Builds a `503 Service Unavailable` response with a JSON error message.
+fn service_unavailable(message: &str) -> Response {
+    let response = json!({
+        "success": false,
+        "message": message
+    });
+
+    Response::new(503).with_body(response)
+}
+
+/// Counts a mutation of the table as in flight until the guard is dropped,
+/// or builds the `429` response if the table is at its concurrent-mutation cap.
+fn begin_mutation(restaurant: &Restaurant, table_id: u32) -> Result<MutationGuard, Response> {
+    restaurant.begin_mutation(table_id).ok_or_else(|| {
+        too_many_requests(&format!("too many concurrent changes to table {}", table_id), 1)
+    })
+}
+
+/// Builds the `413` response for a body larger than `max_bytes`.
+fn too_large(max_bytes: usize) -> Response {
+    let response = json!({
+        "success": false,
+        "message": format!("response exceeds {} bytes", max_bytes)
+    });
+
+    Response::new(413).with_body(response)
+}
+
+/// A serialization buffer that fails instead of growing past `limit` bytes.
+struct LimitedBuffer {
+    bytes: Vec<u8>,
+    limit: usize,
+}
+
+impl io::Write for LimitedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.limit > 0 && self.bytes.len() + buf.len() > self.limit {
+            return Err(io::Error::new(io::ErrorKind::OutOfMemory, "response too large"));
+        }
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes a value for a response body.
+///
+/// Serialization stops as soon as the body outgrows the restaurant's
+/// response limit, so a huge listing is never held in memory whole; the
+/// `413` response to send is returned instead. Returns the `500` response,
+/// after logging the cause, when the value cannot be serialized.
+fn to_json<T: Serialize>(value: &T, restaurant: &Restaurant) -> Result<String, Response> {
+    let max_bytes = restaurant.max_response_bytes();
+    let mut buffer = LimitedBuffer { bytes: Vec::new(), limit: max_bytes };
+    match serde_json::to_writer(&mut buffer, value) {
+        // serde_json only writes valid UTF-8
+        Ok(()) => String::from_utf8(buffer.bytes).map_err(|_| internal_server_error()),
+        Err(err) if err.is_io() => Err(too_large(max_bytes)),
+        Err(err) => {
+            restaurant.logger().error(&format!("Failed to serialize response: {}", err));
+            Err(internal_server_error())
+        }
+    }
+}
+
+/// Builds a `200 OK` response with `value` as its JSON body.
+fn json_ok<T: Serialize>(value: &T, restaurant: &Restaurant) -> Response {
+    match to_json(value, restaurant) {
+        Ok(body) => Response::new(200).with_body(body),
+        Err(response) => response,
+    }
+}
+
+/// Builds a `200 OK` response carrying `data` in the success envelope.
+fn success<T: Serialize>(data: &T, restaurant: &Restaurant) -> Response {
+    match to_json(data, restaurant) {
+        Ok(data) => {
+            let response = json!({
+                "success": true,
+                "message": "Success!",
+                "data": data
+            });
+            Response::new(200).with_body(response)
+        }
+        Err(response) => response,
+    }
+}
 
 /// Handles a POST request for adding an order.
 ///
+/// Items with limited stock are counted down across all tables; an order
+/// for a sold-out item is rejected whole with `409 Conflict`. With
+/// `?allow_substitution=true`, a sold-out item with a configured replacement
+/// in stock is ordered as the replacement instead and listed under
+/// `substituted`.
+///
+/// Tables are created on first use. An order that creates its table, because
+/// nothing has touched the table before, says so with `"created": true`.
+///
+/// Orders that would take the restaurant over its total order cap get
+/// `503 Service Unavailable`, and repeat adds that would take one order past
+/// the per-item quantity cap get `400`.
+///
+/// While the server drains on shutdown, orders are answered with `503` if
+/// the restaurant is configured to reject them.
+///
+/// A first-seat order sent with `If-Table-Empty: true` (or `?if_empty=true`)
+/// is only placed if the table has no orders, and gets `409 Conflict` otherwise.
+///
+/// A request with more items than the restaurant allows is either rejected
+/// with `400` or, when configured to truncate, has the first items added and
+/// the rest listed under `skipped`.
+///
 /// # Arguments
 ///
-/// * `request`: A string containing the HTTP request.
+/// * `request`: The parsed HTTP request.
 /// * `restaurant`: The restaurant instance.
 ///
 /// # Returns
 ///
 /// Returns a `Result` with either an HTTP response or an error message.
 pub async fn handle_post_order(
-    request: &str,
+    request: &RawRequest<'_>,
     restaurant: Restaurant,
-) -> Result<String, String> {
-    let body_start = request.find("\r\n\r\n").ok_or("Invalid request")? + 4;
-    let body = &request[body_start..];
+) -> Result<Response, String> {
+    if !restaurant.accepts_orders() {
+        return Ok(service_unavailable("server is shutting down"));
+    }
+    let body = request.body;
 
-    let order_request: AddOrderRequest = match serde_json::from_str(body) {
+    let mut order_request: AddOrderRequest = match serde_json::from_str(body) {
         Ok(request) => request,
         Err(err) => {
+            let message = match overflowing_field(body) {
+                Some(field) => format!("{} exceeds maximum", field),
+                None => format!("Failed to parse order request: {}", err),
+            };
             let response = json!({
                 "success": false,
-                "message": format!("Failed to parse order request: {}", err)
+                "message": message
             });
-            return Err(serde_json::to_string(&response).unwrap())
+            return Err(response.to_string())
         }
     };
+    reject_unknown_fields::<AddOrderRequest>(body, &restaurant)?;
+    if let Some(item) = order_request.modifiers.keys().find(|item| !order_request.items.contains(item)) {
+        let response = json!({
+            "success": false,
+            "message": format!("modifiers given for item {} which is not in items", item)
+        });
+        return Err(response.to_string());
+    }
+
+    let max_items = restaurant.max_items_per_request();
+    let mut skipped = Vec::new();
+    if max_items > 0 && order_request.items.len() > max_items {
+        match restaurant.oversized_batch() {
+            OversizedBatch::Reject => {
+                let response = json!({
+                    "success": false,
+                    "message": format!("{} items exceeds the maximum of {} per request",
+                        order_request.items.len(), max_items
+                    )
+                });
+                return Err(response.to_string());
+            }
+            OversizedBatch::Truncate => skipped = order_request.items.split_off(max_items),
+        }
+    }
+
+    let (_, query) = http::split_target(request.target);
+    let query = http::parse_query(query);
+    let if_empty = request.header("If-Table-Empty").is_some_and(|value| value.eq_ignore_ascii_case("true"))
+        || query.get("if_empty").map(String::as_str) == Some("true");
+    let allow_substitution = query.get("allow_substitution").map(String::as_str) == Some("true");
 
-    let t = restaurant.get_table(order_request.table_id);
+    if !restaurant.has_table(order_request.table_id) {
+        return Ok(not_found(format!("table {} does not exist", order_request.table_id)));
+    }
+    // The rate limit is checked before the table is created; every rejection
+    // after this point gives its order back
+    if let Err(retry_after_secs) = restaurant.try_accept_order() {
+        return Ok(too_many_requests("order rate limit exceeded", retry_after_secs));
+    }
+    let created = match restaurant.get_or_create_table(order_request.table_id) {
+        Some((_, created)) => created,
+        None => {
+            restaurant.refund_order();
+            return Ok(not_found(format!("table {} does not exist", order_request.table_id)));
+        }
+    };
+    let _mutation = match begin_mutation(&restaurant, order_request.table_id) {
+        Ok(guard) => guard,
+        Err(response) => {
+            restaurant.refund_order();
+            return Ok(response);
+        }
+    };
 
-    let mut table = t.lock().unwrap();
+    let placed = write_table(&restaurant, order_request.table_id, |table| {
+        if if_empty && !table.is_empty() {
+            return Err(conflict(format!("table {} already has orders", order_request.table_id)));
+        }
+        // Take stock for the whole request first, so a sold-out item rejects it untouched
+        let stock = restaurant.stock();
+        let mut substituted = Vec::new();
+        for taken in 0..order_request.items.len() {
+            let item = order_request.items[taken];
+            if stock.take(item, 1) {
+                continue;
+            }
+            let replacement = restaurant
+                .substitutions()
+                .replacement_for(item)
+                .filter(|&replacement| allow_substitution && stock.take(replacement, 1));
+            match replacement {
+                Some(replacement) => {
+                    order_request.items[taken] = replacement;
+                    substituted.push(json!({"original": item, "replacement": replacement}));
+                }
+                None => {
+                    for item in &order_request.items[..taken] {
+                        stock.restore(*item, 1);
+                    }
+                    return Err(conflict("item sold out".to_string()));
+                }
+            }
+        }
+        let restore_stock = || {
+            for item in &order_request.items {
+                stock.restore(*item, 1);
+            }
+        };
+        let max_quantity = restaurant.max_quantity_per_item();
+        if max_quantity > 0 {
+            let quantities = table.quantities_after_adds(&order_request.items, restaurant.coalesce_window_ms());
+            if quantities.values().any(|&quantity| quantity > max_quantity) {
+                restore_stock();
+                return Err(Response::new(400)
+                    .with_body(json!({"success": false, "message": "quantity exceeds maximum"})));
+            }
+        }
+        // Only items not already on the table add orders; the rest replace or coalesce
+        let mut new_items: Vec<u32> = order_request
+            .items
+            .iter()
+            .copied()
+            .filter(|item| table.get_order(*item).is_none())
+            .collect();
+        new_items.sort_unstable();
+        new_items.dedup();
+        if !restaurant.has_room_for_orders(new_items.len()) {
+            restore_stock();
+            return Err(service_unavailable("restaurant at capacity"));
+        }
+        // Items coalesced into an existing order keep that order's notification
+        let mut placed = Vec::new();
+        for item in &order_request.items {
+            if table.coalesce_order(*item, restaurant.coalesce_window_ms()) {
+                continue;
+            }
+            // A new order replaces any earlier one for the item, whose stock goes back
+            if let Some(replaced) = table.get_order(*item) {
+                stock.restore(*item, replaced.quantity);
+            }
+            // Menu items with a known preparation time use it; others get a random wait
+            match restaurant.menu().prep_seconds(*item) {
+                Some(prep_seconds) => table.add_order_with_wait(*item, prep_seconds),
+                None => table.add_order(*item),
+            }
+            if let Some(modifiers) = order_request.modifiers.get(item) {
+                table.set_modifiers(*item, modifiers.clone());
+            }
+            placed.push(*item);
+        }
+        if restaurant.auto_occupancy() && !order_request.items.is_empty() {
+            table.set_occupied(true);
+        }
+        Ok((placed, substituted))
+    });
+    let (placed, substituted) = match placed {
+        Ok(Ok(placed)) => placed,
+        Ok(Err(response)) | Err(response) => {
+            restaurant.refund_order();
+            return Ok(response);
+        }
+    };
+    restaurant.metrics().record_orders_created(order_request.items.len() as u64);
     for item in &order_request.items {
-        table.add_order(*item);
+        restaurant.record_event(order_request.table_id, *item, Action::Added);
+    }
+    restaurant.notify_table_changed(order_request.table_id);
+    let mut confirmed_items = order_request.items.clone();
+    confirmed_items.sort_unstable();
+    confirmed_items.dedup();
+    let confirmation = restaurant.confirm_orders(order_request.table_id, confirmed_items);
+    for item_id in placed {
+        schedule_ready_notification(&restaurant, order_request.table_id, item_id);
     }
 
-    let response = json!({
+    let data = match to_json(&order_request, &restaurant) {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let mut response = json!({
         "success": true,
         "message": "Success!",
-        "data": serde_json::to_string(&order_request).unwrap()
+        "data": data,
+        "confirmation": confirmation
     });
+    if !skipped.is_empty() {
+        response["skipped"] = json!(skipped);
+    }
+    if !substituted.is_empty() {
+        response["substituted"] = json!(substituted);
+    }
+    if created {
+        response["created"] = json!(true);
+    }
 
-    Ok(format!(
-        "HTTP/1.1 200 OK\r\n\r\n{}",
-        serde_json::to_string(&response).unwrap()
-    ))
+    Ok(Response::new(200).with_body(response))
 }
 
-/// Handles a DELETE request for removing an order.
+/// Handles a DELETE request for removing an order or clearing a table.
+///
+/// `/orders/{table_id}/{item_id}` removes one order and `/orders/{table_id}`
+/// removes all of a table's orders.
+///
+/// A clear takes every order out under one hold of the table's write lock,
+/// so an add lands either wholly before the clear (and is cleared) or wholly
+/// after it.
+///
+/// Successful deletes answer `204 No Content` instead of a JSON message when
+/// the restaurant is configured to, or when the client sends
+/// `Prefer: return=minimal`. A missing order is a `404` either way.
+///
+/// An optional reason, given as `?reason=out_of_stock` or in a
+/// `{"reason": ".."}` body, is kept with the removals in the event log.
 ///
 /// # Arguments
 ///
+/// * `request`: The parsed HTTP request.
 /// * `path`: A string containing the HTTP request path.
 /// * `restaurant`: The restaurant instance.
 ///
@@ -60,228 +724,3419 @@ pub async fn handle_post_order(
 ///
 /// Returns a `Result` with either an HTTP response or an error message.
 pub async fn handle_delete_order(
+    request: &RawRequest<'_>,
     path: &str,
     restaurant: Restaurant,
-) -> Result<String, String> {
-    let parts: Vec<&str> = path.split('/').collect();
-
-    if parts.len() == 4 {
-        let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
-        let item_id = parts[3].parse::<u32>().map_err(|_| "Invalid item id")?;
+) -> Result<Response, String> {
+    // The path's shape is checked first, then the table id, then the item id,
+    // so a bad path always reports its first problem
+    let path = PathParams::parse(path);
+    if !(1..=3).contains(&path.segment_count()) {
+        let response = json!({
+            "success": false,
+            "message": "Invalid path: expected /orders/{table_id} or /orders/{table_id}/{item_id}"
+        });
 
-        let t = restaurant.get_table(table_id);
-        let result = t.lock().unwrap().remove_order(item_id);
+        return Err(response.to_string());
+    }
+    let table_id: u32 = path.param(1, "table id").map_err(path_error)?;
+    let item_id: Option<u32> = match path.segment_count() {
+        3 => Some(path.param(2, "item id").map_err(path_error)?),
+        _ => None,
+    };
+    let no_content = restaurant.delete_no_content()
+        || request
+            .header("Prefer")
+            .is_some_and(|prefer| prefer.eq_ignore_ascii_case("return=minimal"));
+    let reason = cancel_reason(request, &restaurant)?;
 
-        match result {
-            Some(_) => {
-                let response = json!({
-                    "success": true,
-                    "message": format!("Removed {} from table {}",
-                        item_id, table_id
-                    )
-                });
+    match item_id {
+        None => {
+            let _mutation = match begin_mutation(&restaurant, table_id) {
+                Ok(guard) => guard,
+                Err(response) => return Ok(response),
+            };
+            let removed = write_table(&restaurant, table_id, |table| {
+                if restaurant.auto_occupancy() {
+                    table.set_occupied(false);
+                }
+                table.take_orders()
+            });
+            let removed = match removed {
+                Ok(removed) => removed,
+                Err(response) => return Ok(response),
+            };
+            for order in &removed {
+                restaurant.stock().restore(order.item_id, order.quantity);
+                restaurant.record_event_with_reason(table_id, order.item_id, Action::Removed, reason.clone());
+                cancel_ready_notification(&restaurant, table_id, order.item_id);
+            }
+            restaurant.notify_table_changed(table_id);
 
-                Ok(format!(
-                    "HTTP/1.1 200 OK\r\n\r\n{}",
-                    serde_json::to_string(&response).unwrap()
-                ))
-            },
-            None => {
-                let response = json!({
-                    "success": false,
-                    "message": "Order not found".to_string()
-                });
-                
-                Err(serde_json::to_string(&response).unwrap())
+            if no_content {
+                return Ok(Response::new(204));
             }
+            let response = json!({
+                "success": true,
+                "message": format!("Cleared {} orders from table {}",
+                    removed.len(), table_id
+                )
+            });
+
+            Ok(Response::new(200).with_body(response))
         }
+        Some(item_id) => {
+            let _mutation = match begin_mutation(&restaurant, table_id) {
+                Ok(guard) => guard,
+                Err(response) => return Ok(response),
+            };
 
-    } else {
-        let response = json!({
-            "success": false,
-            "message": "Invalid path".to_string()
-        });
-        
-        Err(serde_json::to_string(&response).unwrap())
+            let result = write_table(&restaurant, table_id, |table| {
+                let result = table.remove_order(item_id);
+                if let Some(order) = &result {
+                    restaurant.stock().restore(order.item_id, order.quantity);
+                }
+                if result.is_some() && restaurant.auto_occupancy() && table.is_empty() {
+                    table.set_occupied(false);
+                }
+                result
+            });
+            let result = match result {
+                Ok(result) => result,
+                Err(response) => return Ok(response),
+            };
+            if result.is_some() {
+                restaurant.record_event_with_reason(table_id, item_id, Action::Removed, reason);
+                restaurant.notify_table_changed(table_id);
+                cancel_ready_notification(&restaurant, table_id, item_id);
+            }
+
+            match result {
+                Some(_) if no_content => Ok(Response::new(204)),
+                None => Ok(not_found("Order not found".to_string())),
+                Some(_) => {
+                    let response = json!({
+                        "success": true,
+                        "message": format!("Removed {} from table {}",
+                            item_id, table_id
+                        )
+                    });
+
+                    Ok(Response::new(200).with_body(response))
+                }
+            }
+        }
     }
 }
 
-/// Handles a GET request for retrieving order information.
+/// Handles a PUT request for swapping one ordered item for another.
+///
+/// `/orders/{table_id}/items/{item_id}` with a body such as `{"new_item_id":9}`
+/// replaces the order in one step under the table's write lock, so no reader
+/// sees the table with neither item. The new order keeps the old quantity and
+/// is timed from now; its stock is taken before the old item's is put back,
+/// and a sold-out new item leaves the table untouched with `409 Conflict`.
 ///
 /// # Arguments
 ///
+/// * `request`: The parsed HTTP request.
 /// * `path`: A string containing the HTTP request path.
 /// * `restaurant`: The restaurant instance.
 ///
 /// # Returns
 ///
 /// Returns a `Result` with either an HTTP response or an error message.
-pub async fn handle_get_order(path: &str, restaurant: Restaurant) -> Result<String, String> {
+pub async fn handle_swap_item(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
     let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 5 || parts[3] != "items" {
+        return Err("Invalid path".to_string());
+    }
     let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
-    let t = restaurant.get_table(table_id);
-    let table = t.lock().unwrap();
-
-    if parts.len() == 3 {   // `/orders/{table_id}`
-        let orders = table.get_orders();
+    let item_id = parts[4].parse::<u32>().map_err(|_| "Invalid item id")?;
 
+    let swap: SwapRequest = serde_json::from_str(request.body).map_err(|err| {
         let response = json!({
-            "success": true,
-            "message": "Success!",
-            "data": serde_json::to_string(&orders).unwrap()
+            "success": false,
+            "message": format!("Failed to parse swap request: {}", err)
         });
+        response.to_string()
+    })?;
+    reject_unknown_fields::<SwapRequest>(request.body, &restaurant)?;
+    let new_item_id = swap.new_item_id;
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
 
-        Ok(format!(
-            "HTTP/1.1 200 OK\r\n\r\n{}",
-            serde_json::to_string(&response).unwrap()
-        ))
+    let swapped = write_table(&restaurant, table_id, |table| {
+        let quantity = match table.get_order(item_id) {
+            Some(order) => order.quantity,
+            None => return Err(not_found(format!("item {} not found on table {}", item_id, table_id))),
+        };
+        if new_item_id == item_id {
+            return Ok(false);
+        }
+        let stock = restaurant.stock();
+        if !stock.take(new_item_id, quantity) {
+            return Err(conflict("item sold out".to_string()));
+        }
+        stock.restore(item_id, quantity);
+        table.swap_order(item_id, new_item_id, restaurant.menu().prep_seconds(new_item_id));
+        Ok(true)
+    });
+    let swapped = match swapped {
+        Ok(Ok(swapped)) => swapped,
+        Ok(Err(response)) | Err(response) => return Ok(response),
+    };
 
-    } else if parts.len() == 5 { // `/orders/{table_id}/items/{item_id}`
-        let item_id = parts[4].parse::<u32>().map_err(|_| "Invalid item id")?;
-        let order = table.get_order(item_id);
+    if swapped {
+        restaurant.record_event(table_id, item_id, Action::Removed);
+        restaurant.record_event(table_id, new_item_id, Action::Added);
+        restaurant.notify_table_changed(table_id);
+        cancel_ready_notification(&restaurant, table_id, item_id);
+        schedule_ready_notification(&restaurant, table_id, new_item_id);
+    }
 
-        let response = json!({
-            "success": true,
-            "message": "Success!",
-            "data": serde_json::to_string(&order).unwrap()
+    let response = json!({
+        "success": true,
+        "message": format!("Replaced {} with {} on table {}",
+            item_id, new_item_id, table_id
+        )
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a GET request for retrieving order information.
+///
+/// `/orders/{table_id}/count` returns just the number of orders on the table.
+/// `/orders/{table_id}/items` returns just the ordered item ids, sorted.
+/// `/orders/{table_id}/total-wait` sums the orders' remaining waiting times.
+/// `/orders/{table_id}/bill` prices the table's orders, modifiers included,
+/// and flags a total under the minimum spend.
+/// `/orders/{table_id}/oldest` returns the order placed first, or 404 if the
+/// table has none. `/orders/{table_id}/items/{item_id}` returns 404 if the
+/// item was not ordered. `/orders/{table_id}/voids` lists the table's voided
+/// orders, oldest first.
+///
+/// A table's orders are listed along with `count`, how many were listed, and
+/// `empty`, whether the table has no orders at all, so an empty table is
+/// told apart from a filter matching nothing (and a missing table gets
+/// `404`). They are listed by item id, or by remaining waiting time with
+/// `sort=remaining_asc` or `sort=remaining_desc` (ties go to the order placed
+/// first). Listing with `status=Cooking` (or `Pending`, `Served`)
+/// keeps only orders with that status, and with `group_by=category` nests them
+/// under their menu category instead of returning a flat array. With `wait=N` the listing
+/// is long-polled: it is held for up to N seconds until the table changes, and
+/// the response reports whether it did in `changed`.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path.
+/// * `query`: The parsed query parameters.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_order(
+    path: &str,
+    query: &HashMap<String, String>,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    let table_id: u32 = path.param(1, "table id")?;
+    if !restaurant.has_table(table_id) {
+        return Ok(not_found(format!("table {} does not exist", table_id)));
+    }
+    let changed = match query.get("wait") {
+        Some(wait) if path.matches(&["orders", ":table_id"]) => {
+            Some(wait_for_change(&restaurant, table_id, wait).await?)
+        }
+        _ => None,
+    };
+
+    match read_table(&restaurant, table_id, |table| {
+        order_response(table, table_id, &path, query, changed, &restaurant)
+    }) {
+        Ok(result) => result,
+        Err(response) => Ok(response),
+    }
+}
+
+/// Builds the response for `handle_get_order` from the locked table.
+fn order_response(
+    table: &Table,
+    table_id: u32,
+    path: &PathParams,
+    query: &HashMap<String, String>,
+    changed: Option<bool>,
+    restaurant: &Restaurant,
+) -> Result<Response, String> {
+    if path.matches(&["orders", ":table_id", "count"]) {
+        let response = json!({
+            "table_id": table_id,
+            "count": table.order_count()
+        });
+
+        Ok(Response::new(200).with_body(response))
+
+    } else if path.matches(&["orders", ":table_id", "items"]) {
+        let response = json!({
+            "table_id": table_id,
+            "items": table.item_ids()
+        });
+
+        Ok(Response::new(200).with_body(response))
+
+    } else if path.matches(&["orders", ":table_id", "total-wait"]) {
+        let response = json!({
+            "table_id": table_id,
+            "total_remaining_seconds": table.total_remaining_seconds(restaurant.now_ms())
+        });
+
+        Ok(Response::new(200).with_body(response))
+
+    } else if path.matches(&["orders", ":table_id", "bill"]) {
+        let bill = Bill::new(table_id, &table.get_orders(), restaurant.menu())
+            .with_minimum(restaurant.min_order_cents());
+
+        Ok(json_ok(&bill, restaurant))
+
+    } else if path.matches(&["orders", ":table_id", "voids"]) {
+        Ok(success(&restaurant.voids().for_table(table_id), restaurant))
+
+    } else if path.matches(&["orders", ":table_id", "oldest"]) {
+        let order = match table.oldest_order() {
+            Some(order) => order,
+            None => return Ok(not_found(format!("table {} has no orders", table_id))),
+        };
+
+        Ok(success(&OrderView::new(order, restaurant), restaurant))
+
+    } else if path.matches(&["orders", ":table_id"]) {
+        let status = match query.get("status") {
+            Some(status) => Some(OrderStatus::parse(status).ok_or("Invalid status")?),
+            None => None,
+        };
+        let mut orders: Vec<&Order> = table
+            .get_orders()
+            .into_iter()
+            .filter(|order| status.is_none_or(|status| order.status == status))
+            .collect();
+        let count = orders.len();
+        let now = restaurant.now_ms();
+        match query.get("sort").map(String::as_str) {
+            None => orders.sort_by_key(|order| order.item_id),
+            Some("remaining_asc") => orders.sort_by_key(|order| {
+                (order.remaining_seconds(now), order.created_at, order.item_id)
+            }),
+            Some("remaining_desc") => orders.sort_by_key(|order| {
+                (Reverse(order.remaining_seconds(now)), order.created_at, order.item_id)
+            }),
+            Some(_) => return Err("Invalid sort".to_string()),
+        }
+        let orders: Vec<OrderView> = orders
+            .into_iter()
+            .map(|order| OrderView::new(order, restaurant))
+            .collect();
+
+        let data = match query.get("group_by").map(String::as_str) {
+            None => to_json(&orders, restaurant),
+            Some("category") => {
+                let menu = restaurant.menu();
+                let mut groups: BTreeMap<&str, Vec<OrderView>> = BTreeMap::new();
+                for order in orders {
+                    groups.entry(menu.category_of(order.order.item_id)).or_default().push(order);
+                }
+                to_json(&groups, restaurant)
+            }
+            Some(_) => return Err("Invalid group_by".to_string()),
+        };
+        let data = match data {
+            Ok(data) => data,
+            Err(response) => return Ok(response),
+        };
+
+        let mut response = json!({
+            "success": true,
+            "message": "Success!",
+            "data": data,
+            "count": count,
+            "empty": table.is_empty()
+        });
+        if let Some(changed) = changed {
+            response["changed"] = json!(changed);
+        }
+
+        Ok(Response::new(200).with_body(response))
+
+    } else if path.matches(&["orders", ":table_id", "items", ":item_id"]) {
+        let item_id: u32 = path.param(3, "item id")?;
+        let order = match table.get_order(item_id) {
+            Some(order) => order,
+            None => return Ok(not_found(format!("item {} not found on table {}", item_id, table_id))),
+        };
+
+        Ok(success(&OrderView::new(order, restaurant), restaurant))
+
+    } else if path.matches(&["orders", ":table_id", "items", ":item_id", "status"]) {
+        let item_id: u32 = path.param(3, "item id")?;
+        let order = match table.get_order(item_id) {
+            Some(order) => order,
+            None => return Ok(not_found(format!("item {} not found on table {}", item_id, table_id))),
+        };
+        let response = json!({
+            "status": order.status,
+            "remaining_seconds": order.remaining_seconds(restaurant.now_ms())
+        });
+
+        Ok(Response::new(200).with_body(response))
+
+    } else {
+        Err("Invalid path".to_string())
+    }
+}
+
+/// Handles a GET request resolving an order confirmation code.
+///
+/// Lists the orders placed by the request the code was issued for that are
+/// still on their table; orders removed since then are left out.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path (`/orders/confirm/{code}`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_confirmation(path: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["orders", "confirm", ":code"]) {
+        return Err("Invalid path".to_string());
+    }
+    let code: String = path.param(2, "confirmation code")?;
+    let confirmation = match restaurant.resolve_confirmation(&code) {
+        Some(confirmation) => confirmation,
+        None => return Ok(not_found(format!("confirmation {} not found", code))),
+    };
+
+    let orders = read_table(&restaurant, confirmation.table_id, |table| {
+        let orders: Vec<OrderView> = confirmation
+            .item_ids
+            .iter()
+            .filter_map(|item_id| table.get_order(*item_id))
+            .map(|order| OrderView::new(order, &restaurant))
+            .collect();
+        to_json(&orders, &restaurant)
+    });
+    let data = match orders {
+        Ok(Ok(data)) => data,
+        Ok(Err(response)) | Err(response) => return Ok(response),
+    };
+    let response = json!({
+        "success": true,
+        "message": "Success!",
+        "data": data,
+        "table_id": confirmation.table_id
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a GET request for a table's orders as newline-delimited JSON.
+///
+/// Each order is written as one JSON object per line, so streaming clients
+/// can parse the listing incrementally.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_orders_ndjson(path: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 3 {
+        return Err("Invalid path".to_string());
+    }
+    let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
+    let body = read_table(&restaurant, table_id, |table| {
+        let mut body = String::new();
+        for order in table.get_orders() {
+            body.push_str(&to_json(&OrderView::new(order, &restaurant), &restaurant)?);
+            let max_bytes = restaurant.max_response_bytes();
+            if max_bytes > 0 && body.len() > max_bytes {
+                return Err(too_large(max_bytes));
+            }
+            body.push('\n');
+        }
+        Ok(body)
+    });
+    let body = match body {
+        Ok(Ok(body)) => body,
+        Ok(Err(response)) | Err(response) => return Ok(response),
+    };
+
+    Ok(Response::new(200)
+        .with_header("Content-Type", "application/x-ndjson")
+        .with_body(body))
+}
+
+/// Handles a POST request for splitting a table's bill.
+///
+/// The body lists groups of item ids, as in `{"groups":[[16,102],[103]]}`;
+/// each group is billed on its own. Items that are not on the table are
+/// rejected with `400`.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}/split`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_split_bill(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 4 || parts[3] != "split" {
+        return Err("Invalid path".to_string());
+    }
+    let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
+
+    let split: SplitRequest = serde_json::from_str(request.body).map_err(|err| {
+        let response = json!({
+            "success": false,
+            "message": format!("Failed to parse split request: {}", err)
+        });
+        response.to_string()
+    })?;
+    reject_unknown_fields::<SplitRequest>(request.body, &restaurant)?;
+
+    let groups = read_table(&restaurant, table_id, |table| {
+        let mut groups = Vec::with_capacity(split.groups.len());
+        for items in &split.groups {
+            let mut orders = Vec::with_capacity(items.len());
+            for item_id in items {
+                match table.get_order(*item_id) {
+                    Some(order) => orders.push(order),
+                    None => {
+                        let response = json!({
+                            "success": false,
+                            "message": format!("item {} is not on table {}", item_id, table_id)
+                        });
+                        return Err(response.to_string());
+                    }
+                }
+            }
+            let bill = Bill::new(table_id, &orders, restaurant.menu());
+            groups.push(json!({"items": items, "subtotal_cents": bill.total_cents}));
+        }
+        Ok(groups)
+    });
+    let groups = match groups {
+        Ok(groups) => groups?,
+        Err(response) => return Ok(response),
+    };
+
+    let response = json!({
+        "table_id": table_id,
+        "groups": groups
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a GET request for listing tables.
+///
+/// The listing can be narrowed with query parameters, combined with AND:
+/// - `has_orders=true|false`: only tables with (or without) orders.
+/// - `older_than_seconds=N`: only tables with an order at least `N` seconds old.
+///
+/// # Arguments
+///
+/// * `query`: The parsed query parameters.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_tables(
+    query: &HashMap<String, String>,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let has_orders = match query.get("has_orders") {
+        Some(value) => Some(value.parse::<bool>().map_err(|_| "Invalid has_orders")?),
+        None => None,
+    };
+    let older_than_ms = match query.get("older_than_seconds") {
+        Some(value) => {
+            let seconds = value.parse::<u64>().map_err(|_| "Invalid older_than_seconds")?;
+            Some(seconds.checked_mul(1000).ok_or("Invalid older_than_seconds")?)
+        }
+        None => None,
+    };
+    let now = restaurant.now_ms();
+
+    let mut tables = Vec::new();
+    for (_, t) in restaurant.iter_tables() {
+        let table = t.read();
+
+        if has_orders.is_some_and(|has_orders| table.is_empty() == has_orders) {
+            continue;
+        }
+        if older_than_ms.is_some_and(|min_age| !table.has_order_older_than(min_age, now)) {
+            continue;
+        }
+        tables.push(table.summary());
+    }
+    // Tables not created yet are empty and unoccupied; list them without creating them
+    if has_orders != Some(true) && older_than_ms.is_none() {
+        tables.extend(
+            restaurant
+                .uncreated_table_ids()
+                .into_iter()
+                .map(|table_id| TableSummary { table_id, occupied: false, order_count: 0 }),
+        );
+        tables.sort_by_key(|summary| summary.table_id);
+    }
+
+    Ok(success(&tables, &restaurant))
+}
+
+/// Handles a GET request for whether the server takes new work.
+///
+/// Answers `200 {"status":"ready"}`, or `503` with `"status":"draining"` once
+/// shutdown has begun, along with the connections still open (this one
+/// included) and how long the drain has run, so operators can watch it.
+///
+/// # Arguments
+///
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_ready(restaurant: Restaurant) -> Result<Response, String> {
+    let metrics = restaurant.metrics();
+    let response = match metrics.drain_elapsed() {
+        None => Response::new(200).with_body(json!({"status": "ready"})),
+        Some(elapsed) => Response::new(503).with_body(json!({
+            "status": "draining",
+            "active_connections": metrics.active_connections(),
+            "drain_elapsed_seconds": elapsed.as_secs_f64()
+        })),
+    };
+
+    Ok(response.with_header("Content-Type", "application/json"))
+}
+
+/// Handles a GET request for the tables free to seat a new party: those with
+/// no orders that are not marked occupied, by id.
+///
+/// Tables are checked one at a time, each under its own read lock, so the
+/// list is not a snapshot of the whole restaurant at one instant.
+///
+/// # Arguments
+///
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_available_tables(restaurant: Restaurant) -> Result<Response, String> {
+    // Tables not created yet are free too
+    let mut available: Vec<u32> = restaurant
+        .iter_tables()
+        .filter(|(_, table)| table.read().is_idle())
+        .map(|(table_id, _)| table_id)
+        .chain(restaurant.uncreated_table_ids())
+        .collect();
+    available.sort_unstable();
+
+    Ok(success(&available, &restaurant))
+}
+
+/// Handles a PUT request for marking a table occupied or free.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/tables/{table_id}/occupancy`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_put_occupancy(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 4 || parts[3] != "occupancy" {
+        return Err("Invalid path".to_string());
+    }
+    let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
+
+    let occupancy: OccupancyRequest = serde_json::from_str(request.body)
+        .map_err(|err| {
+            let response = json!({
+                "success": false,
+                "message": format!("Failed to parse occupancy request: {}", err)
+            });
+            response.to_string()
+        })?;
+    reject_unknown_fields::<OccupancyRequest>(request.body, &restaurant)?;
+
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = write_table(&restaurant, table_id, |table| table.set_occupied(occupancy.occupied)) {
+        return Ok(response);
+    }
+
+    let response = json!({
+        "success": true,
+        "message": format!("Table {} marked {}",
+            table_id, if occupancy.occupied { "occupied" } else { "free" }
+        )
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a POST request for merging another table into this one.
+///
+/// The body names the source table, as in `{"from":3}`. Its orders move to
+/// the target table; an item ordered on both has the quantities summed. The
+/// source is left empty. Both table locks are taken in id order, so two
+/// opposite merges cannot deadlock.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/tables/{table_id}/merge`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_merge_tables(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 4 || parts[3] != "merge" {
+        return Err("Invalid path".to_string());
+    }
+    let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
+
+    let merge: MergeRequest = serde_json::from_str(request.body).map_err(|err| {
+        let response = json!({
+            "success": false,
+            "message": format!("Failed to parse merge request: {}", err)
+        });
+        response.to_string()
+    })?;
+    reject_unknown_fields::<MergeRequest>(request.body, &restaurant)?;
+    if merge.from == table_id {
+        return Err("{\"message\":\"Cannot merge a table into itself\",\"success\":false}".to_string());
+    }
+
+    let _mutations = match (begin_mutation(&restaurant, table_id), begin_mutation(&restaurant, merge.from)) {
+        (Ok(target), Ok(source)) => (target, source),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+    let (t, s) = match (existing_table(&restaurant, table_id), existing_table(&restaurant, merge.from)) {
+        (Ok(t), Ok(s)) => (t, s),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+    let (mut target, mut source) = if table_id < merge.from {
+        let target = t.write();
+        (target, s.write())
+    } else {
+        let source = s.write();
+        (t.write(), source)
+    };
+
+    let orders = source.take_orders();
+    let moved: Vec<u32> = orders.iter().map(|order| order.item_id).collect();
+    for order in orders {
+        target.merge_order(order);
+    }
+    if restaurant.auto_occupancy() && !moved.is_empty() {
+        source.set_occupied(false);
+        target.set_occupied(true);
+    }
+    drop((target, source));
+    for item_id in &moved {
+        restaurant.record_event(table_id, *item_id, Action::Merged);
+        cancel_ready_notification(&restaurant, merge.from, *item_id);
+        schedule_ready_notification(&restaurant, table_id, *item_id);
+    }
+    restaurant.notify_table_changed(table_id);
+    restaurant.notify_table_changed(merge.from);
+
+    let response = json!({
+        "success": true,
+        "message": format!("Merged {} orders from table {} into table {}",
+            moved.len(), merge.from, table_id
+        )
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a POST request for expediting one order by taking `seconds` off
+/// its remaining waiting time, never going below zero.
+///
+/// Responds with the updated order, or `404` if the item was not ordered.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}/items/{item_id}/bump`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_bump_order(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["orders", ":table_id", "items", ":item_id", "bump"]) {
+        return Err("Invalid path".to_string());
+    }
+    let table_id: u32 = path.param(1, "table id")?;
+    let item_id: u32 = path.param(3, "item id")?;
+
+    let bump: BumpRequest = serde_json::from_str(request.body).map_err(|err| {
+        let response = json!({
+            "success": false,
+            "message": format!("Failed to parse bump request: {}", err)
+        });
+        response.to_string()
+    })?;
+    reject_unknown_fields::<BumpRequest>(request.body, &restaurant)?;
+
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
+    let bumped = write_table(&restaurant, table_id, |table| {
+        table.bump_order(item_id, bump.seconds).map(|order| success(&OrderView::new(order, &restaurant), &restaurant))
+    });
+    match bumped {
+        Ok(Some(response)) => {
+            restaurant.notify_table_changed(table_id);
+            schedule_ready_notification(&restaurant, table_id, item_id);
+            Ok(response)
+        }
+        Ok(None) => Ok(not_found(format!("item {} not found on table {}", item_id, table_id))),
+        Err(response) => Ok(response),
+    }
+}
+
+/// Handles a POST request for voiding one order.
+///
+/// Unlike a delete, a void needs a reason (`?reason=` or a `{"reason": ".."}`
+/// body) and keeps the order, marked `Voided`, in the restaurant's void log,
+/// which nothing ever trims; it leaves the table's listings and bill like a
+/// removed order. Stock goes back unless the order was served. Voiding a
+/// served order is a manager override and needs the admin token, as
+/// `X-Admin-Token` or `Authorization: Bearer`.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}/items/{item_id}/void`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_void_order(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["orders", ":table_id", "items", ":item_id", "void"]) {
+        return Err("Invalid path".to_string());
+    }
+    let table_id: u32 = path.param(1, "table id")?;
+    let item_id: u32 = path.param(3, "item id")?;
+    let reason = cancel_reason(request, &restaurant)?
+        .ok_or("{\"message\":\"reason required\",\"success\":false}")?;
+    let manager_override = require_admin(request, &restaurant);
+
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
+    let voided = write_table(&restaurant, table_id, |table| {
+        let served = table.get_order(item_id).is_some_and(|order| order.status == OrderStatus::Served);
+        if served {
+            manager_override?;
+        }
+        let order = table
+            .remove_order(item_id)
+            .ok_or_else(|| not_found(format!("item {} not found on table {}", item_id, table_id)))?;
+        if restaurant.auto_occupancy() && table.is_empty() {
+            table.set_occupied(false);
+        }
+        Ok((order, served))
+    });
+    let (mut order, served) = match voided {
+        Ok(Ok(voided)) => voided,
+        Ok(Err(response)) | Err(response) => return Ok(response),
+    };
+    if !served {
+        restaurant.stock().restore(order.item_id, order.quantity);
+    }
+    restaurant.record_event_with_reason(table_id, item_id, Action::Voided, Some(reason.clone()));
+    restaurant.notify_table_changed(table_id);
+    cancel_ready_notification(&restaurant, table_id, item_id);
+
+    order.status = OrderStatus::Voided;
+    let voided = VoidedOrder {
+        order,
+        reason,
+        voided_at: restaurant.now_ms(),
+        manager_override: served,
+    };
+    let response = success(&voided, &restaurant);
+    restaurant.record_void(voided);
+
+    Ok(response)
+}
+
+/// Handles a POST request for resending a table's orders to the kitchen,
+/// such as when a ticket got lost.
+///
+/// Orders keep their status; each one resent is recorded as a `resent`
+/// event and watchers of the table are woken, so kitchen displays pick them
+/// up again. A `{"items": [..]}` body picks the orders to resend; without
+/// one, every order not yet served is resent. Items not on the table get `400`.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}/resend`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_resend_orders(
+    request: &RawRequest<'_>,
+    path: &str,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["orders", ":table_id", "resend"]) {
+        return Err("Invalid path".to_string());
+    }
+    let table_id: u32 = path.param(1, "table id")?;
+
+    let items = match request.body.trim() {
+        "" => None,
+        body => {
+            let resend: ResendRequest = serde_json::from_str(body).map_err(|err| {
+                let response = json!({
+                    "success": false,
+                    "message": format!("Failed to parse resend request: {}", err)
+                });
+                response.to_string()
+            })?;
+            reject_unknown_fields::<ResendRequest>(body, &restaurant)?;
+            resend.items
+        }
+    };
+
+    let resent = read_table(&restaurant, table_id, |table| match items {
+        Some(mut items) => {
+            if let Some(item_id) = items.iter().find(|item_id| table.get_order(**item_id).is_none()) {
+                let response = json!({
+                    "success": false,
+                    "message": format!("item {} is not on table {}", item_id, table_id)
+                });
+                return Err(response.to_string());
+            }
+            items.sort_unstable();
+            items.dedup();
+            Ok(items)
+        }
+        None => {
+            let mut items: Vec<u32> = table
+                .get_orders()
+                .into_iter()
+                .filter(|order| order.status != OrderStatus::Served)
+                .map(|order| order.item_id)
+                .collect();
+            items.sort_unstable();
+            Ok(items)
+        }
+    });
+    let resent = match resent {
+        Ok(resent) => resent?,
+        Err(response) => return Ok(response),
+    };
+    for item_id in &resent {
+        restaurant.record_event(table_id, *item_id, Action::Resent);
+    }
+    if !resent.is_empty() {
+        restaurant.notify_table_changed(table_id);
+    }
+
+    Ok(Response::new(200).with_body(json!({"resent": resent})))
+}
+
+/// Handles a POST request for firing a table: sending all of its pending
+/// orders to the kitchen at once.
+///
+/// Every `Pending` order becomes `Cooking` and records when it was fired;
+/// orders already cooking or served are left alone.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path (`/orders/{table_id}/fire`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_fire_table(path: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["orders", ":table_id", "fire"]) {
+        return Err("Invalid path".to_string());
+    }
+    let table_id: u32 = path.param(1, "table id")?;
+
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
+    let fired = match write_table(&restaurant, table_id, |table| table.fire_pending(restaurant.now_ms())) {
+        Ok(fired) => fired,
+        Err(response) => return Ok(response),
+    };
+    if !fired.is_empty() {
+        restaurant.notify_table_changed(table_id);
+    }
+    for item_id in &fired {
+        restaurant.record_event(table_id, *item_id, Action::Fired);
+    }
+
+    Ok(Response::new(200).with_body(json!({"fired": fired.len()})))
+}
+
+/// Handles a POST request for resetting a table.
+///
+/// Unlike clearing the table with `DELETE /orders/{table_id}`, which only
+/// removes its orders, a reset also marks the table unoccupied regardless of
+/// `auto_occupancy`, leaving it as it was when created. Removed orders put
+/// their stock back.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path (`/tables/{table_id}/reset`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_reset_table(path: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let path = PathParams::parse(path);
+    if !path.matches(&["tables", ":table_id", "reset"]) {
+        return Err("Invalid path".to_string());
+    }
+    let table_id: u32 = path.param(1, "table id")?;
+
+    let _mutation = match begin_mutation(&restaurant, table_id) {
+        Ok(guard) => guard,
+        Err(response) => return Ok(response),
+    };
+    let removed = match write_table(&restaurant, table_id, Table::reset) {
+        Ok(removed) => removed,
+        Err(response) => return Ok(response),
+    };
+    for order in &removed {
+        restaurant.stock().restore(order.item_id, order.quantity);
+        restaurant.record_event(table_id, order.item_id, Action::Removed);
+        cancel_ready_notification(&restaurant, table_id, order.item_id);
+    }
+    restaurant.notify_table_changed(table_id);
+
+    let response = json!({
+        "success": true,
+        "message": format!("Reset table {}, removing {} orders", table_id, removed.len())
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a GET request for a table's waiting time statistics.
+///
+/// # Arguments
+///
+/// * `path`: A string containing the HTTP request path (`/tables/{table_id}/wait-stats`).
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_wait_stats(path: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 4 || parts[3] != "wait-stats" {
+        return Err("Invalid path".to_string());
+    }
+    let table_id = parts[2].parse::<u32>().map_err(|_| "Invalid table id")?;
+
+    let stats = match read_table(&restaurant, table_id, |table| table.wait_stats(restaurant.now_ms())) {
+        Ok(stats) => stats,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(json_ok(&stats, &restaurant))
+}
+
+/// Handles a POST request for serving every cooking order in the restaurant.
+///
+/// Requires the admin token. Tables are locked one at a time.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_serve_all(request: &RawRequest<'_>, restaurant: Restaurant) -> Result<Response, String> {
+    if let Err(response) = require_admin(request, &restaurant) {
+        return Ok(response);
+    }
+
+    let mut served = 0;
+    for (table_id, t) in restaurant.iter_tables() {
+        let items = t.write().serve_cooking();
+        served += items.len();
+        if !items.is_empty() {
+            restaurant.notify_table_changed(table_id);
+        }
+        for item_id in &items {
+            restaurant.record_event(table_id, *item_id, Action::Served);
+        }
+
+        if let Some(webhook) = restaurant.webhook() {
+            // Served orders are announced now instead of when their timers run out
+            for item_id in items {
+                webhook.timers().cancel(table_id, item_id);
+                webhook.notify(OrderReady::new(table_id, item_id));
+            }
+        }
+    }
+    restaurant.metrics().record_orders_served(served as u64);
+
+    Ok(Response::new(200).with_body(json!({"served": served})))
+}
+
+/// Handles a GET request for the kitchen queue.
+///
+/// Lists every order that has not been served yet with the station that
+/// prepares it, by the priority of its menu category and then oldest first.
+/// `station=NAME` narrows the queue to one station.
+///
+/// # Arguments
+///
+/// * `query`: The parsed query parameters.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_kitchen_queue(
+    query: &HashMap<String, String>,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    let station = query.get("station").map(String::as_str);
+    let menu = restaurant.menu();
+    let priorities = restaurant.category_priorities();
+
+    let mut queue = Vec::new();
+    for (_, t) in restaurant.iter_tables() {
+        let table = t.read();
+        for order in table.get_orders() {
+            let order_station = menu.station_of(order.item_id);
+            if order.status == OrderStatus::Served
+                || station.is_some_and(|station| station != order_station)
+            {
+                continue;
+            }
+            queue.push(QueueEntry { order: order.clone(), station: order_station });
+        }
+    }
+    queue.sort_by_key(|entry| {
+        let order = &entry.order;
+        let priority = priorities.priority_of(menu.category_of(order.item_id));
+        (priority, order.created_at, order.table_id, order.item_id)
+    });
+
+    Ok(success(&queue, &restaurant))
+}
+
+/// Handles a GET request for the kitchen overview: how many orders of each
+/// status every table with orders has, in table order.
+///
+/// Tables are locked one at a time.
+///
+/// # Arguments
+///
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_kitchen_overview(restaurant: Restaurant) -> Result<Response, String> {
+    let overview: Vec<StatusCounts> = restaurant
+        .iter_tables()
+        .filter_map(|(_, t)| {
+            let table = t.read();
+            (!table.is_empty()).then(|| table.status_counts())
+        })
+        .collect();
+
+    Ok(json_ok(&overview, &restaurant))
+}
+
+/// Handles a GET request for runtime diagnostics.
+///
+/// Requires the admin token. Every figure is read from atomics, so no table
+/// lock is taken and a busy table cannot hold the response up.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_diagnostics(request: &RawRequest<'_>, restaurant: Restaurant) -> Result<Response, String> {
+    if let Err(response) = require_admin(request, &restaurant) {
+        return Ok(response);
+    }
+
+    let metrics = restaurant.metrics();
+    let response = json!({
+        "active_connections": metrics.active_connections(),
+        "table_count": restaurant.table_count(),
+        "total_orders": restaurant.total_orders(),
+        "uptime_seconds": metrics.summary().uptime_seconds
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Default number of events returned by one `GET /events` page.
+const DEFAULT_EVENTS_LIMIT: usize = 100;
+
+/// Most events returned by one `GET /events` page.
+const MAX_EVENTS_LIMIT: usize = 1000;
+
+/// Handles a GET request for the restaurant-wide event log.
+///
+/// Returns events oldest first. `since_seq=N` skips events up to that sequence
+/// number and `limit=N` caps the page, so a client pages through the log by
+/// passing the last `seq` it received as the next `since_seq`. Events recorded
+/// in the same millisecond share a timestamp, so `since=TS`, which skips events
+/// at or before a timestamp, only picks where to start.
+///
+/// # Arguments
+///
+/// * `request`: The parsed HTTP request.
+/// * `query`: The parsed query parameters.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_events(
+    request: &RawRequest<'_>,
+    query: &HashMap<String, String>,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    if let Err(response) = require_admin(request, &restaurant) {
+        return Ok(response);
+    }
+
+    let since = match query.get("since") {
+        Some(value) => value.parse::<u64>().map_err(|_| "Invalid since")?,
+        None => 0,
+    };
+    let since_seq = match query.get("since_seq") {
+        Some(value) => value.parse::<u64>().map_err(|_| "Invalid since_seq")?,
+        None => 0,
+    };
+    let limit = match query.get("limit") {
+        Some(value) => value.parse::<usize>().map_err(|_| "Invalid limit")?.min(MAX_EVENTS_LIMIT),
+        None => DEFAULT_EVENTS_LIMIT,
+    };
+
+    let events = restaurant.events().page(since_seq, since, limit);
+    Ok(json_ok(&json!({"events": events}), &restaurant))
+}
+
+/// Handles a POST request for resetting every table to empty.
+///
+/// Only routed in dev mode; see `Restaurant::reset_tables` for how concurrent
+/// requests are kept from seeing a half-reset table.
+///
+/// # Arguments
+///
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_dev_reset(restaurant: Restaurant) -> Result<Response, String> {
+    if let Some(webhook) = restaurant.webhook() {
+        webhook.timers().cancel_all();
+    }
+    // Waiting on every table lock blocks, so keep it off the async workers
+    let reset = tokio::task::spawn_blocking(move || restaurant.reset_tables())
+        .await
+        .map_err(|_| "Reset failed")?;
+
+    let response = json!({
+        "success": true,
+        "message": format!("Reset {} tables", reset)
+    });
+
+    Ok(Response::new(200).with_body(response))
+}
+
+/// Handles a GET request for the server's health.
+///
+/// Plain requests just report `ok`. With `verbose=true` each subsystem is
+/// checked: the table store must hold tables and every table lock must be
+/// acquirable within `HEALTH_LOCK_TIMEOUT`. Table locks cannot be poisoned,
+/// so a lock that stays held (a stuck or deadlocked writer) is what fails the
+/// check. The accept loop must have checked in within `ACCEPT_LOOP_STALE_MS`,
+/// and a task spawned on the runtime must run within `RUNTIME_PROBE_TIMEOUT`,
+/// so a server that can no longer take or schedule work is caught. There is
+/// no persistence backend, so it is reported as disabled. Any failing
+/// component makes the response a `503` naming it.
+///
+/// # Arguments
+///
+/// * `query`: The parsed query parameters.
+/// * `restaurant`: The restaurant instance.
+///
+/// # Returns
+///
+/// Returns a `Result` with either an HTTP response or an error message.
+pub async fn handle_get_health(
+    query: &HashMap<String, String>,
+    restaurant: Restaurant,
+) -> Result<Response, String> {
+    if query.get("verbose").map(String::as_str) != Some("true") {
+        return Ok(Response::new(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(json!({"status": "ok"})));
+    }
+
+    // Waiting on locks blocks, so keep it off the async workers
+    let stuck_tables = tokio::task::spawn_blocking({
+        let restaurant = restaurant.clone();
+        move || {
+            restaurant
+                .iter_tables()
+                .filter(|(_, t)| t.try_read_for(HEALTH_LOCK_TIMEOUT).is_none())
+                .map(|(table_id, _)| table_id)
+                .collect::<Vec<u32>>()
+        }
+    })
+    .await
+    .map_err(|_| "Health check failed")?;
+
+    let mut failing = Vec::new();
+    let table_store = if restaurant.table_count() > 0 {
+        json!("ok")
+    } else {
+        failing.push("table_store");
+        json!("no tables")
+    };
+    let table_locks = if stuck_tables.is_empty() {
+        json!("ok")
+    } else {
+        failing.push("table_locks");
+        json!({"stuck_tables": stuck_tables})
+    };
+
+    let accept_loop = match restaurant.metrics().accept_loop_seen_ms() {
+        Some(seen) if restaurant.now_ms().saturating_sub(seen) <= ACCEPT_LOOP_STALE_MS => json!("ok"),
+        Some(seen) => {
+            failing.push("accept_loop");
+            json!({"last_seen_ms_ago": restaurant.now_ms().saturating_sub(seen)})
+        }
+        None => {
+            failing.push("accept_loop");
+            json!("not running")
+        }
+    };
+    let runtime = match tokio::time::timeout(RUNTIME_PROBE_TIMEOUT, tokio::spawn(async {})).await {
+        Ok(Ok(())) => json!("ok"),
+        _ => {
+            failing.push("runtime");
+            json!("unresponsive")
+        }
+    };
+
+    let healthy = failing.is_empty();
+    let response = json!({
+        "status": if healthy { "ok" } else { "unhealthy" },
+        "failing": failing,
+        "components": {
+            "table_store": table_store,
+            "table_locks": table_locks,
+            "accept_loop": accept_loop,
+            "runtime": runtime,
+            "persistence": "disabled"
+        }
+    });
+    let status = if healthy { 200 } else { 503 };
+
+    Ok(Response::new(status)
+        .with_header("Content-Type", "application/json")
+        .with_body(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::http;
+    use crate::menu::{CategoryPriorities, ItemNames, Menu, MenuItem};
+    use std::sync::Arc;
+
+    const DELETE: &str = "DELETE /orders HTTP/1.1\r\n\r\n";
+
+    fn parse(request: &str) -> RawRequest<'_> {
+        RawRequest::parse(request).unwrap()
+    }
+
+    fn init_restaurant(tables: usize, items: usize) -> Restaurant {
+        let restaurant = Restaurant::new(tables);
+        let table = restaurant.get_table(1);
+        for i in 0..items {
+            table.write().add_order(i as u32);
+        }
+        restaurant
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_ignores_header_content() {
+        let request = "POST /orders HTTP/1.1\r\nContent-Type: application/json\r\nX-Debug: {\"table_id\": 3, \"items\": [7]}\r\n\r\n{\"table_id\": 2, \"items\": [101]}";
+        let restaurant = Restaurant::new(5);
+
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        assert!(restaurant.get_table(2).read().get_order(101).is_some());
+        assert!(restaurant.get_table(3).read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_ok() {
+        // Create a sample request body
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [101, 102]}";
+
+        // Create a mock Restaurant
+        let restaurant = init_restaurant(10, 5);
+
+        // Call the function
+        let result = handle_post_order(&parse(request), restaurant).await;
+
+        // Check if the result is as expected
+        assert!(result.is_ok());
+        let response = result.unwrap().to_string();
+        println!("test {:?}", response);
+        assert!(response.contains("HTTP/1.1 200 OK"));
+        assert!(response.contains("\\\"table_id\\\":2"));
+        assert!(response.contains("\\\"items\\\":[101,102]"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_uses_prep_time() {
+        let restaurant = init_restaurant(10, 0);
+
+        // Item 8 is on the default menu with a 15 second preparation time; 101 is not
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [8, 101]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        let t = restaurant.get_table(2);
+        let table = t.read();
+        assert_eq!(table.get_order(8).unwrap().waiting_time, 15);
+        assert!((5..16).contains(&table.get_order(101).unwrap().waiting_time));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_ng() {
+        // String table id
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": st, \"items\": [101, 102]}";
+
+        // Create a mock Restaurant
+        let restaurant = init_restaurant(10, 5);
+        let restaurant2 = restaurant.clone();
+
+        // Call the function
+        let result = handle_post_order(&parse(request), restaurant).await;
+
+        // Check if the result is as expected
+        assert!(result.is_err());
+        let response = result.unwrap_err();
+        assert!(response.contains("Failed to parse order request"));
+
+        // String item id
+        let request2 = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [st, 102]}";
+        
+        // Call the function
+        let result2 = handle_post_order(&parse(request2), restaurant2).await;
+        // Check if the result is as expected
+        assert!(result2.is_err());
+        let response2 = result2.unwrap_err();
+        assert!(response2.contains("Failed to parse order request"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_rate_limited() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(10, clock.clone()).with_order_rate_limit(2);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [101]}";
+
+        assert!(handle_post_order(&parse(request), restaurant.clone()).await.is_ok());
+        assert!(handle_post_order(&parse(request), restaurant.clone()).await.is_ok());
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 30\r\n\r\n{\"message\":\"order rate limit exceeded\",\"success\":false}"
+        );
+
+        // The limit is restaurant-wide, not per table
+        let other = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [101]}";
+        let response = handle_post_order(&parse(other), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests"));
+
+        clock.advance(30_000);
+        let response = handle_post_order(&parse(other), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_order_keeps_rate_limit() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(10, clock.clone()).with_order_rate_limit(1);
+        restaurant.get_table(1).write().add_order(7);
+
+        // A 409 gives its order back
+        let request = "POST /orders?if_empty=true HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [101]}";
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 409 Conflict"));
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [101]}";
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        // A rate-limited order does not create its table
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [101]}";
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests"));
+        assert!(restaurant.uncreated_table_ids().contains(&3));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_string_item_ids() {
+        let restaurant = init_restaurant(10, 5);
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [\"101\", 102]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert!(result.is_ok());
+        let table = restaurant.get_table(1);
+        let item_ids = table.read().item_ids();
+        assert!(item_ids.contains(&101) && item_ids.contains(&102));
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [\"abc\"]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        let response = result.unwrap_err();
+        assert!(response.contains("Failed to parse order request: item id \\\"abc\\\" is not a number"));
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [\"-1\"]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert!(result.unwrap_err().contains("is not a number"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_overflow() {
+        let restaurant = init_restaurant(10, 5);
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [101, 4294967296]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap_err(),
+            "{\"message\":\"item_id exceeds maximum\",\"success\":false}"
+        );
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 99999999999999999999999, \"items\": [101]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap_err(),
+            "{\"message\":\"table_id exceeds maximum\",\"success\":false}"
+        );
+
+        // u32::MAX itself is still a valid id
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [4294967295]}";
+        assert!(handle_post_order(&parse(request), restaurant).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_delete_order_ok() {
+        // Create a sample path
+        let path = "/orders/1/2"; // Assuming table_id = 1, item_id = 2
+
+        // Create a mock Restaurant
+        let restaurant = init_restaurant(10, 5);
+
+        // Call the function
+        let result = handle_delete_order(&parse(DELETE), path, restaurant).await;
+
+        // Check if the result is as expected
+        assert!(result.is_ok());
+        let response = result.unwrap().to_string();
+        assert!(response.contains("HTTP/1.1 200 OK"));
+        assert!(response.contains("Removed 2 from table 1"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_clear_table() {
+        let restaurant = init_restaurant(10, 5);
+
+        let result = handle_delete_order(&parse(DELETE), "/orders/1", restaurant.clone()).await;
+        assert!(result.unwrap().body_text().contains("Cleared 5 orders from table 1"));
+        assert!(restaurant.get_table(1).read().is_empty());
+
+        let result = handle_delete_order(&parse(DELETE), "/orders/10", restaurant).await;
+        assert_eq!(result.unwrap().status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_order_paths() {
+        let restaurant = init_restaurant(10, 5);
+        let query = HashMap::new();
+        let get = |path: &'static str| handle_get_order(path, &query, restaurant.clone());
+
+        // Short paths are rejected instead of indexing past the end
+        assert_eq!(get("/orders").await.unwrap_err(), "Missing table id");
+        assert_eq!(get("/orders/").await.unwrap_err(), "Invalid table id");
+        assert_eq!(get("/orders/x").await.unwrap_err(), "Invalid table id");
+        assert_eq!(get("/orders/1/foo").await.unwrap_err(), "Invalid path");
+        assert_eq!(get("/orders/1/foo/2").await.unwrap_err(), "Invalid path");
+        assert_eq!(get("/orders/1/items/x").await.unwrap_err(), "Invalid item id");
+
+        let request = parse(DELETE);
+        let delete = |path: &'static str| handle_delete_order(&request, path, restaurant.clone());
+        assert!(delete("/orders").await.unwrap_err().contains("Missing table id"));
+        assert!(delete("/orders/1/x").await.unwrap_err().contains("Invalid item id"));
+        assert!(delete("/orders/1/2/3").await.unwrap_err().contains("Invalid path"));
+        assert_eq!(restaurant.get_table(1).read().get_orders().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_order_status() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone());
+        let table = restaurant.get_table(1);
+        table.write().add_order_with_wait(7, 120);
+        table.write().fire_pending(0);
+        clock.advance(20_000);
+        let query = HashMap::new();
+
+        let response = handle_get_order("/orders/1/items/7/status", &query, restaurant.clone()).await.unwrap();
+        assert_eq!(response.to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"remaining_seconds\":100,\"status\":\"Cooking\"}");
+
+        let response = handle_get_order("/orders/1/items/8/status", &query, restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_path_segments() {
+        let restaurant = init_restaurant(10, 5);
+        let request = parse(DELETE);
+        let delete = |path: &'static str| handle_delete_order(&request, path, restaurant.clone());
+
+        assert_eq!(
+            delete("/orders").await.unwrap_err(),
+            "{\"field\":\"table_id\",\"message\":\"Missing table id\",\"success\":false}"
+        );
+        assert_eq!(
+            delete("/orders/x").await.unwrap_err(),
+            "{\"field\":\"table_id\",\"message\":\"Invalid table id\",\"success\":false}"
+        );
+        // The table id is checked before the item id
+        assert_eq!(
+            delete("/orders/x/y").await.unwrap_err(),
+            "{\"field\":\"table_id\",\"message\":\"Invalid table id\",\"success\":false}"
+        );
+        assert_eq!(
+            delete("/orders/1/y").await.unwrap_err(),
+            "{\"field\":\"item_id\",\"message\":\"Invalid item id\",\"success\":false}"
+        );
+        // Too deep is reported before either id is looked at
+        assert_eq!(
+            delete("/orders/x/2/3").await.unwrap_err(),
+            "{\"message\":\"Invalid path: expected /orders/{table_id} or /orders/{table_id}/{item_id}\",\"success\":false}"
+        );
+        assert_eq!(restaurant.get_table(1).read().get_orders().len(), 5);
+
+        assert!(delete("/orders/1/0").await.unwrap().body_text().contains("Removed 0 from table 1"));
+        assert!(delete("/orders/1").await.unwrap().body_text().contains("Cleared 4 orders from table 1"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_if_table_empty() {
+        let restaurant = init_restaurant(10, 5);
+        restaurant.get_table(2).write().take_orders();
+
+        // A first-seat order lands on the empty table
+        let request = "POST /orders HTTP/1.1\r\nIf-Table-Empty: true\r\n\r\n{\"table_id\": 2, \"items\": [42]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        // ...and is turned away once the table has orders, leaving them untouched
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        let response = result.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 409 Conflict"));
+        assert!(response.contains("table 2 already has orders"));
+
+        let request = "POST /orders?if_empty=true HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [43]}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 409);
+        assert!(restaurant.get_table(1).read().get_order(43).is_none());
+        assert_eq!(restaurant.get_table(2).read().item_ids(), vec![42]);
+    }
+
+    #[tokio::test]
+    async fn test_reset_table_vs_clear() {
+        let restaurant = init_restaurant(10, 5).with_auto_occupancy(false);
+        for table_id in [1, 2] {
+            restaurant.get_table(table_id).write().set_occupied(true);
+        }
+        restaurant.get_table(2).write().add_order(7);
+
+        // Clearing only removes the orders
+        let result = handle_delete_order(&parse(DELETE), "/orders/1", restaurant.clone()).await;
+        assert!(result.unwrap().body_text().contains("Cleared 5 orders from table 1"));
+        let summary = restaurant.get_table(1).read().summary();
+        assert_eq!((summary.order_count, summary.occupied), (0, true));
+
+        // Resetting leaves nothing behind
+        let result = handle_reset_table("/tables/2/reset", restaurant.clone()).await;
+        assert!(result.unwrap().body_text().contains("Reset table 2, removing 1 orders"));
+        assert!(restaurant.get_table(2).read().is_idle());
+
+        let result = handle_reset_table("/tables/10/reset", restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_resets_restore_stock() {
+        let menu = Menu::new(vec![MenuItem::new(30, "Special", Some("main")).with_stock(3)]);
+        let restaurant = Restaurant::new(5).with_menu(menu);
+        for table_id in [1, 2, 3] {
+            let request = format!("POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": {}, \"items\": [30]}}", table_id);
+            handle_post_order(&parse(&request), restaurant.clone()).await.unwrap();
+        }
+        assert_eq!(restaurant.stock().remaining(30), Some(0));
+
+        handle_reset_table("/tables/1/reset", restaurant.clone()).await.unwrap();
+        assert_eq!(restaurant.stock().remaining(30), Some(1));
+
+        handle_dev_reset(restaurant.clone()).await.unwrap();
+        assert_eq!(restaurant.stock().remaining(30), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_total_cap() {
+        let restaurant = Restaurant::new(10).with_max_total_orders(3);
+        let post = |table_id: u32, items: &str| {
+            let request = format!("POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": {}, \"items\": {}}}", table_id, items);
+            let restaurant = restaurant.clone();
+            async move { handle_post_order(&parse(&request), restaurant).await.unwrap() }
+        };
+
+        assert_eq!(post(1, "[1, 2]").await.status, 200);
+        // Re-ordering an item already on the table adds no order
+        assert_eq!(post(1, "[2, 3]").await.status, 200);
+        assert_eq!(restaurant.total_orders(), 3);
+
+        let response = post(4, "[1]").await.to_string();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.ends_with("{\"message\":\"restaurant at capacity\",\"success\":false}"));
+        assert!(restaurant.get_table(4).read().is_empty());
+
+        // Removing an order makes room again
+        handle_delete_order(&parse(DELETE), "/orders/1/2", restaurant.clone()).await.unwrap();
+        assert_eq!(post(4, "[1]").await.status, 200);
+        assert_eq!(restaurant.total_orders(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_reasons() {
+        let restaurant = init_restaurant(10, 5);
+        let delete = |request: &'static str| {
+            let restaurant = restaurant.clone();
+            async move {
+                let request = parse(request);
+                let path = http::split_target(request.target).0;
+                handle_delete_order(&request, path, restaurant).await
+            }
+        };
+
+        delete("DELETE /orders/1/0?reason=out_of_stock HTTP/1.1\r\n\r\n").await.unwrap();
+        delete("DELETE /orders/1/1 HTTP/1.1\r\n\r\n{\"reason\": \"customer changed mind\"}").await.unwrap();
+        delete("DELETE /orders/1/2 HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let reasons: Vec<(u32, Option<String>)> = restaurant
+            .events()
+            .page(0, 0, 10)
+            .into_iter()
+            .map(|event| (event.item_id, event.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (0, Some("out_of_stock".to_string())),
+                (1, Some("customer changed mind".to_string())),
+                (2, None),
+            ]
+        );
+
+        let too_long = format!("DELETE /orders/1/3?reason={} HTTP/1.1\r\n\r\n", "x".repeat(201));
+        let result = handle_delete_order(&parse(&too_long), "/orders/1/3", restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"reason exceeds 200 characters\",\"success\":false}");
+        assert!(restaurant.get_table(1).read().get_order(3).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_json_fields() {
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [1], \"typo\": true}";
+
+        // Lenient by default
+        let restaurant = Restaurant::new(5);
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let restaurant = Restaurant::new(5).with_strict_json(true);
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"unknown field 'typo'\",\"success\":false}");
+        assert!(restaurant.get_table(1).read().is_empty());
+
+        // Declared fields, optional ones included, are fine
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [1], \"modifiers\": {}}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let request = "POST /tables/2/merge HTTP/1.1\r\n\r\n{\"from\": 1, \"into\": 2}";
+        let result = handle_merge_tables(&parse(request), "/tables/2/merge", restaurant).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"unknown field 'into'\",\"success\":false}");
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        assert_eq!(struct_fields::<AddOrderRequest>(), ["table_id", "items", "modifiers"]);
+        assert_eq!(struct_fields::<MergeRequest>(), ["from"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_clear_and_add() {
+        let restaurant = init_restaurant(10, 5);
+
+        let mut adds = Vec::new();
+        for item in 100..150u32 {
+            let restaurant = restaurant.clone();
+            adds.push(tokio::spawn(async move {
+                let request = format!(
+                    "POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": 1, \"items\": [{}]}}",
+                    item
+                );
+                (item, handle_post_order(&parse(&request), restaurant).await.unwrap())
+            }));
+        }
+        let clear = {
+            let restaurant = restaurant.clone();
+            tokio::spawn(async move { handle_delete_order(&parse(DELETE), "/orders/1", restaurant).await })
+        };
+
+        // Every add lands before or after the clear, so none is turned away
+        for add in adds {
+            let (_, response) = add.await.unwrap();
+            assert_eq!(response.status, 200);
+        }
+        let cleared = clear.await.unwrap().unwrap().body_text().into_owned();
+
+        // Whatever the clear did not take is still on the table: nothing was lost
+        let cleared: usize = cleared
+            .split("Cleared ")
+            .nth(1)
+            .and_then(|rest| rest.split(' ').next())
+            .and_then(|count| count.parse().ok())
+            .unwrap();
+        assert_eq!(cleared + restaurant.get_table(1).read().order_count(), 5 + 50);
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_all_orders_ok() {
+        // Create a sample path
+        let path = "/orders/1";
+
+        // Create a mock Restaurant
+        let restaurant = init_restaurant(10, 5);
+
+        // Call the function
+        let result = handle_get_order(path, &HashMap::new(), restaurant).await;
+
+        // Check if the result is as expected
+        assert!(result.is_ok());
+        let response = result.unwrap().to_string();
+
+        // Assert that we get the expected response
+        assert!(response.contains("HTTP/1.1 200 OK"));
+        assert!(response.contains("\\\"item_id\\\":0,\\\"table_id\\\":1"));
+        assert!(response.contains("\\\"item_id\\\":1,\\\"table_id\\\":1"));
+        assert!(response.contains("\\\"item_id\\\":2,\\\"table_id\\\":1"));
+        assert!(response.contains("\\\"item_id\\\":3,\\\"table_id\\\":1"));
+        assert!(response.contains("\\\"item_id\\\":4,\\\"table_id\\\":1"));
+        assert!(!response.contains("\\\"item_id\\\":5,\\\"table_id\\\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_order_count() {
+        let restaurant = init_restaurant(10, 0);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [1, 2, 3]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [4]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        handle_delete_order(&parse(DELETE), "/orders/3/2", restaurant.clone()).await.unwrap();
+
+        let result = handle_get_order("/orders/3/count", &HashMap::new(), restaurant.clone()).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"count\":3,\"table_id\":3}");
+
+        let result = handle_get_order("/orders/10/count", &HashMap::new(), restaurant).await;
+        assert_eq!(result.unwrap().status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_creates_table() {
+        let restaurant = Restaurant::new(5);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [7]}";
+
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"created\":true"));
+
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(!response.contains("created"));
+
+        // Reading a table does not create it, so the first order still does
+        let response = handle_get_order("/orders/4", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"empty\":true"));
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 4, \"items\": [7]}";
+        let response = handle_post_order(&parse(request), restaurant).await.unwrap().to_string();
+        assert!(response.contains("\"created\":true"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_sold_out() {
+        let menu = Menu::new(vec![
+            MenuItem::new(30, "Special", Some("main")).with_stock(2),
+            MenuItem::new(31, "Tea", Some("drink")),
+        ]);
+        let restaurant = Restaurant::new(5).with_menu(menu);
+        let order = |table_id: u32, items: &str| {
+            format!("POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": {}, \"items\": {}}}", table_id, items)
+        };
+
+        handle_post_order(&parse(&order(1, "[30]")), restaurant.clone()).await.unwrap();
+        handle_post_order(&parse(&order(2, "[30, 31]")), restaurant.clone()).await.unwrap();
+        assert_eq!(restaurant.stock().remaining(30), Some(0));
+
+        let response = handle_post_order(&parse(&order(3, "[31, 30]")), restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 409 Conflict\r\n\r\n{\"message\":\"item sold out\",\"success\":false}"
+        );
+        assert!(restaurant.get_table(3).read().is_empty());
+
+        // Cancelling an order puts its stock back
+        handle_delete_order(&parse(DELETE), "/orders/1/30", restaurant.clone()).await.unwrap();
+        assert_eq!(restaurant.stock().remaining(30), Some(1));
+        let response = handle_post_order(&parse(&order(3, "[30]")), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_substitutes_sold_out_items() {
+        use crate::menu::Substitutions;
+
+        let menu = Menu::new(vec![
+            MenuItem::new(30, "Special", Some("main")).with_stock(0),
+            MenuItem::new(32, "Other Special", Some("main")).with_stock(1),
+            MenuItem::new(31, "Tea", Some("drink")),
+        ]);
+        let restaurant = Restaurant::new(5)
+            .with_menu(menu)
+            .with_substitutions(Substitutions::parse("30=32").unwrap());
+        let order = |target: &str, table_id: u32| {
+            format!("POST {} HTTP/1.1\r\n\r\n{{\"table_id\": {}, \"items\": [31, 30]}}", target, table_id)
+        };
+
+        // Substitution is opt-in
+        let response = handle_post_order(&parse(&order("/orders", 1)), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 409 Conflict"));
+
+        let response = handle_post_order(&parse(&order("/orders?allow_substitution=true", 1)), restaurant.clone())
+            .await
+            .unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"substituted\":[{\"original\":30,\"replacement\":32}]"));
+        assert_eq!(restaurant.get_table(1).read().item_ids(), vec![31, 32]);
+        assert_eq!(restaurant.stock().remaining(32), Some(0));
+
+        // Once the replacement sells out too, the order is rejected as before
+        let response = handle_post_order(&parse(&order("/orders?allow_substitution=true", 2)), restaurant.clone())
+            .await
+            .unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 409 Conflict"));
+        assert!(restaurant.get_table(2).read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_code_resolves_to_orders() {
+        let restaurant = Restaurant::new(5);
+        let order = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [7, 8, 7]}";
+
+        let response = handle_post_order(&parse(order), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"confirmation\":\"0001\""));
+        let response = handle_post_order(&parse(order), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"confirmation\":\"0002\""));
+
+        let response = handle_get_confirmation("/orders/confirm/0001", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"table_id\":3"));
+        assert!(response.contains("\\\"item_id\\\":7"));
+        assert!(response.contains("\\\"item_id\\\":8"));
+
+        // Removed orders drop out of the confirmation
+        restaurant.get_table(3).write().remove_order(8);
+        let response = handle_get_confirmation("/orders/confirm/0001", restaurant.clone()).await.unwrap().to_string();
+        assert!(!response.contains("\\\"item_id\\\":8"));
+
+        let response = handle_get_confirmation("/orders/confirm/0009", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_cap_per_table() {
+        let restaurant = Restaurant::new(5).with_max_in_flight_per_table(2);
+        let order = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [7]}";
+
+        // Two changes to table 1 are still being handled
+        let first = restaurant.begin_mutation(1).unwrap();
+        let second = restaurant.begin_mutation(1).unwrap();
+        assert_eq!(restaurant.metrics().table_in_flight_max(), 2);
+
+        let response = handle_post_order(&parse(order), restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\n\r\n{\"message\":\"too many concurrent changes to table 1\",\"success\":false}"
+        );
+        let response = handle_delete_order(&parse(DELETE), "/orders/1", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests"));
+
+        // Other tables are not affected
+        let other = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7]}";
+        let response = handle_post_order(&parse(other), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        drop((first, second));
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let restaurant = restaurant.clone();
+            handles.push(tokio::spawn(async move {
+                handle_post_order(&parse(order), restaurant).await.unwrap()
+            }));
+        }
+        for handle in handles {
+            let response = handle.await.unwrap().to_string();
+            assert!(response.starts_with("HTTP/1.1 200 OK") || response.starts_with("HTTP/1.1 429"));
+        }
+        // Rejected changes never count towards the high-water mark
+        assert_eq!(restaurant.metrics().table_in_flight_max(), 2);
+        assert!(restaurant.metrics().render().contains("resto_table_in_flight_max 2\n"));
+        assert!(restaurant.begin_mutation(1).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_handle_swap_item() {
+        let menu = Menu::new(vec![
+            MenuItem::new(30, "Special", Some("main")).with_stock(1),
+            MenuItem::new(31, "Tea", Some("drink")).with_prep_seconds(5),
+        ]);
+        let restaurant = Restaurant::new(5).with_menu(menu);
+        restaurant.get_table(2).write().add_order(7);
+        let order = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [30]}";
+        handle_post_order(&parse(order), restaurant.clone()).await.unwrap();
+        let swap = |new_item_id: u32| {
+            format!("PUT /orders/2/items/7 HTTP/1.1\r\n\r\n{{\"new_item_id\": {}}}", new_item_id)
+        };
+
+        let response = handle_swap_item(&parse(&swap(31)), "/orders/2/items/7", restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Replaced 7 with 31 on table 2\",\"success\":true}"
+        );
+        let table = restaurant.get_table(2);
+        assert!(table.read().get_order(7).is_none());
+        assert_eq!(table.read().get_order(31).unwrap().waiting_time, 5);
+
+        // The old item is gone now
+        let response = handle_swap_item(&parse(&swap(31)), "/orders/2/items/7", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+
+        // A sold-out replacement leaves the table as it was
+        let request = "PUT /orders/2/items/31 HTTP/1.1\r\n\r\n{\"new_item_id\": 30}";
+        let response = handle_swap_item(&parse(request), "/orders/2/items/31", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 409 Conflict"));
+        assert!(table.read().get_order(31).is_some());
+
+        let request = "PUT /orders/2/items/31 HTTP/1.1\r\n\r\n{\"item\": 30}";
+        let result = handle_swap_item(&parse(request), "/orders/2/items/31", restaurant).await;
+        assert!(result.unwrap_err().contains("Failed to parse swap request"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_rejects_oversized_batch() {
+        let restaurant = Restaurant::new(5).with_max_items_per_request(2, OversizedBatch::Reject);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [7, 8, 9]}";
+
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap_err(),
+            "{\"message\":\"3 items exceeds the maximum of 2 per request\",\"success\":false}"
+        );
+        assert!(restaurant.get_table(1).read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_truncates_oversized_batch() {
+        let restaurant = Restaurant::new(5).with_max_items_per_request(2, OversizedBatch::Truncate);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [7, 8, 9, 10]}";
+
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"skipped\":[9,10]"));
+        assert_eq!(restaurant.get_table(1).read().item_ids(), vec![7, 8]);
+
+        // Batches within the limit report nothing skipped
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7, 8]}";
+        let response = handle_post_order(&parse(request), restaurant).await.unwrap().to_string();
+        assert!(!response.contains("skipped"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_merge_tables() {
+        let restaurant = Restaurant::new(5);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [7, 8]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [8, 9]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        let request = "POST /tables/1/merge HTTP/1.1\r\n\r\n{\"from\": 3}";
+        let response = handle_merge_tables(&parse(request), "/tables/1/merge", restaurant.clone()).await;
+        assert_eq!(
+            response.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Merged 2 orders from table 3 into table 1\",\"success\":true}"
+        );
+
+        let source = restaurant.get_table(3);
+        let source = source.read();
+        assert!(source.is_empty());
+        assert!(!source.summary().occupied);
+
+        let target = restaurant.get_table(1);
+        let target = target.read();
+        assert_eq!(target.item_ids(), vec![7, 8, 9]);
+        let quantities: Vec<(u32, u32, u32)> = target
+            .item_ids()
+            .into_iter()
+            .map(|item| {
+                let order = target.get_order(item).unwrap();
+                (order.item_id, order.table_id, order.quantity)
+            })
+            .collect();
+        assert_eq!(quantities, vec![(7, 1, 1), (8, 1, 2), (9, 1, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_merge_tables_rejects_bad_source() {
+        let restaurant = Restaurant::new(5);
+
+        let request = "POST /tables/2/merge HTTP/1.1\r\n\r\n{\"from\": 2}";
+        let result = handle_merge_tables(&parse(request), "/tables/2/merge", restaurant.clone()).await;
+        assert!(result.unwrap_err().contains("Cannot merge a table into itself"));
+
+        let request = "POST /tables/2/merge HTTP/1.1\r\n\r\n{\"from\": 9}";
+        let result = handle_merge_tables(&parse(request), "/tables/2/merge", restaurant).await;
+        assert_eq!(result.unwrap().status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_order_total_wait() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone());
+        {
+            let t = restaurant.get_table(2);
+            let mut table = t.write();
+            table.add_order_with_wait(7, 10);
+            table.add_order_with_wait(8, 3);
+            table.add_order_with_wait(9, 6);
+        }
+        clock.advance(4_000);
+
+        // 6 + 0 + 2 seconds left
+        let result = handle_get_order("/orders/2/total-wait", &HashMap::new(), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"table_id\":2,\"total_remaining_seconds\":8}"
+        );
+
+        let result = handle_get_order("/orders/3/total-wait", &HashMap::new(), restaurant).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"table_id\":3,\"total_remaining_seconds\":0}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_listing_is_413() {
+        let restaurant = init_restaurant(3, 500).with_max_response_bytes(16 * 1024);
+
+        let result = handle_get_order("/orders/1", &HashMap::new(), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 413 Payload Too Large\r\n\r\n{\"message\":\"response exceeds 16384 bytes\",\"success\":false}"
+        );
+        let result = handle_get_orders_ndjson("/orders/1", restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 413);
+
+        // Small responses and an unlimited restaurant are unaffected
+        let result = handle_get_order("/orders/1/oldest", &HashMap::new(), restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+        let restaurant = restaurant.with_max_response_bytes(0);
+        let result = handle_get_order("/orders/1", &HashMap::new(), restaurant).await;
+        assert_eq!(result.unwrap().status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_no_table_lock_held_while_awaiting() {
+        let restaurant = Restaurant::new(5);
+        let query = http::parse_query("wait=5");
+        let polling = tokio::spawn({
+            let restaurant = restaurant.clone();
+            async move { handle_get_order("/orders/2", &query, restaurant).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The poll is parked on an await; the table must be free for writers
+        assert!(!polling.is_finished());
+        assert!(restaurant.get_table(2).try_write().is_some());
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        let response = polling.await.unwrap().unwrap().to_string();
+        assert!(response.contains("\"changed\":true"));
+        assert!(restaurant.get_table(2).try_write().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_order_items() {
+        let restaurant = init_restaurant(10, 0);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 4, \"items\": [103, 7, 42]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        let result = handle_get_order("/orders/4/items", &HashMap::new(), restaurant.clone()).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"items\":[7,42,103],\"table_id\":4}");
+
+        let result = handle_get_order("/orders/5/items", &HashMap::new(), restaurant).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"items\":[],\"table_id\":5}");
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_coalesces_repeat_adds() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone()).with_coalesce_window_ms(1_000);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7]}";
+        let quantity = || restaurant.get_table(2).read().get_order(7).unwrap().quantity;
+
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        clock.advance(400);
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(quantity(), 2);
+
+        // Outside the window the add is a new order again
+        clock.advance(600);
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(quantity(), 1);
+        assert_eq!(restaurant.get_table(2).read().get_order(7).unwrap().created_at, 1_000);
+
+        // Without a window repeat adds never coalesce
+        let restaurant = Restaurant::with_clock(5, clock.clone());
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(restaurant.get_table(2).read().get_order(7).unwrap().quantity, 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_quantity_per_item() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone())
+            .with_coalesce_window_ms(1_000)
+            .with_max_quantity_per_item(3);
+        let post = |items: &str| {
+            let request = format!("POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": 2, \"items\": {}}}", items);
+            let restaurant = restaurant.clone();
+            async move { handle_post_order(&parse(&request), restaurant).await.unwrap() }
+        };
+        let quantity = || restaurant.get_table(2).read().get_order(7).unwrap().quantity;
+        let over_cap = "HTTP/1.1 400 Bad Request\r\n\r\n{\"message\":\"quantity exceeds maximum\",\"success\":false}";
+
+        // Increments up to the cap are fine, the one crossing it is rejected whole
+        assert_eq!(post("[7, 7]").await.status, 200);
+        assert_eq!(post("[7]").await.status, 200);
+        assert_eq!(quantity(), 3);
+        assert_eq!(post("[8, 7]").await.to_string(), over_cap);
+        assert_eq!(quantity(), 3);
+        assert!(restaurant.get_table(2).read().get_order(8).is_none());
+
+        // A single request asking for more than the cap at once
+        assert_eq!(post("[9, 9, 9, 9]").await.to_string(), over_cap);
+        assert!(restaurant.get_table(2).read().get_order(9).is_none());
+
+        // Outside the window the add replaces the order, so it is under the cap again
+        clock.advance(1_000);
+        assert_eq!(post("[7]").await.status, 200);
+        assert_eq!(quantity(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_returns_on_change() {
+        let restaurant = Restaurant::new(5);
+
+        let poster = restaurant.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7]}";
+            handle_post_order(&parse(request), poster).await.unwrap();
         });
 
-        Ok(format!(
-            "HTTP/1.1 200 OK\r\n\r\n{}",
-            serde_json::to_string(&response).unwrap()
-        ))
+        let query = http::parse_query("wait=30");
+        let started = std::time::Instant::now();
+        let response = handle_get_order("/orders/2", &query, restaurant.clone()).await.unwrap().to_string();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(response.contains("\"changed\":true"));
+        assert!(response.contains("\\\"item_id\\\":7,"));
+
+        // Nothing changes within the timeout
+        let query = http::parse_query("wait=0");
+        let response = handle_get_order("/orders/2", &query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"changed\":false"));
+
+        let query = http::parse_query("wait=soon");
+        assert!(handle_get_order("/orders/2", &query, restaurant).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_bill_with_modifiers() {
+        let restaurant = Restaurant::new(5);
+        // Gyoza (650) with an extra sauce, and plain Green Tea (300)
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [2, 18], \"modifiers\": {\"2\": [{\"name\": \"extra sauce\", \"price_delta_cents\": 75}]}}";
+        let response = handle_post_order(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("extra sauce"));
+
+        let response = handle_get_order("/orders/2/bill", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        let body_start = response.find("\r\n\r\n").unwrap() + 4;
+        let bill: Value = serde_json::from_str(&response[body_start..]).unwrap();
+        assert_eq!(bill["lines"][0]["item_id"], 2);
+        assert_eq!(bill["lines"][0]["modifiers"][0]["name"], "extra sauce");
+        assert_eq!(bill["lines"][0]["line_total_cents"], 725);
+        assert_eq!(bill["lines"][1]["line_total_cents"], 300);
+        assert_eq!(bill["total_cents"], 1025);
+
+        // Modifiers show up on the order itself
+        let response = handle_get_order("/orders/2/items/2", &HashMap::new(), restaurant).await.unwrap().to_string();
+        assert!(response.contains("\\\"price_delta_cents\\\":75"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_post_order_modifiers_for_unknown_item() {
+        let restaurant = Restaurant::new(5);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [2], \"modifiers\": {\"18\": [{\"name\": \"lemon\", \"price_delta_cents\": 50}]}}";
+        let result = handle_post_order(&parse(request), restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap_err(),
+            "{\"message\":\"modifiers given for item 18 which is not in items\",\"success\":false}"
+        );
+        assert!(restaurant.get_table(2).read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_order_responses_use_item_names() {
+        let names = ItemNames::from_json(r#"{"7": "Salmon Teriyaki"}"#).unwrap();
+        let restaurant = init_restaurant(5, 0).with_item_names(names);
+        restaurant.get_table(2).write().add_order(7);
+        restaurant.get_table(2).write().add_order(99);
+
+        let response = handle_get_order("/orders/2/items/7", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\\\"name\\\":\\\"Salmon Teriyaki\\\""));
+
+        let response = handle_get_order("/orders/2/items/99", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\\\"name\\\":null"));
+
+        let response = handle_get_orders_ndjson("/orders/2", restaurant).await.unwrap().to_string();
+        assert_eq!(response.matches("\"name\":").count(), 2);
+
+        // Without a name map responses stay as they were
+        let restaurant = init_restaurant(5, 0);
+        restaurant.get_table(2).write().add_order(7);
+        let response = handle_get_order("/orders/2", &HashMap::new(), restaurant).await.unwrap().to_string();
+        assert!(!response.contains("name"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_split_bill() {
+        let restaurant = Restaurant::new(5);
+        {
+            let t = restaurant.get_table(2);
+            let mut table = t.write();
+            // Gyoza 650, Chicken Teriyaki 1450, Green Tea 300
+            table.add_order(2);
+            table.add_order(6);
+            table.add_order(18);
+        }
+
+        let request = parse("POST /orders/2/split HTTP/1.1\r\n\r\n{\"groups\":[[2,18],[6]]}");
+        let response = handle_split_bill(&request, "/orders/2/split", restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"groups\":[{\"items\":[2,18],\"subtotal_cents\":950},{\"items\":[6],\"subtotal_cents\":1450}],\"table_id\":2}"
+        );
+
+        let request = parse("POST /orders/2/split HTTP/1.1\r\n\r\n{\"groups\":[[2],[7]]}");
+        let result = handle_split_bill(&request, "/orders/2/split", restaurant).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"item 7 is not on table 2\",\"success\":false}");
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_bill_minimum() {
+        // Gyoza is 650
+        let restaurant = Restaurant::new(5).with_min_order_cents(1000);
+        restaurant.get_table(2).write().add_order(2);
+
+        let bill = |response: Response| -> Value { serde_json::from_slice(&response.body).unwrap() };
+
+        let response = handle_get_order("/orders/2/bill", &HashMap::new(), restaurant.clone()).await.unwrap();
+        let below = bill(response);
+        assert_eq!(below["below_minimum"], true);
+        assert_eq!(below["shortfall_cents"], 350);
+
+        // Chicken Teriyaki takes it over
+        restaurant.get_table(2).write().add_order(6);
+        let response = handle_get_order("/orders/2/bill", &HashMap::new(), restaurant).await.unwrap();
+        let above = bill(response);
+        assert_eq!(above["below_minimum"], false);
+        assert_eq!(above["shortfall_cents"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_oldest_order() {
+        let restaurant = Restaurant::new(5);
+        {
+            let t = restaurant.get_table(2);
+            let mut table = t.write();
+            table.insert_order(Order::new(7, 2, 10, 3_000));
+            table.insert_order(Order::new(8, 2, 10, 1_000));
+            table.insert_order(Order::new(9, 2, 10, 2_000));
+        }
+
+        let response = handle_get_order("/orders/2/oldest", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\\\"item_id\\\":8,"));
+
+        let response = handle_get_order("/orders/3/oldest", &HashMap::new(), restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_one_order_missing_item() {
+        let restaurant = init_restaurant(10, 5);
+
+        let result = handle_get_order("/orders/1/items/9999", &HashMap::new(), restaurant).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 404 Not Found\r\n\r\n{\"message\":\"item 9999 not found on table 1\",\"success\":false}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_one_order_ok() {
+        // Create a sample path
+        let path = "/orders/1/items/3";
+
+        // Create a mock Restaurant
+        let restaurant = init_restaurant(10, 5);
+
+        // Call the function
+        let result = handle_get_order(path, &HashMap::new(), restaurant).await;
+
+        // Check if the result is as expected
+        assert!(result.is_ok());
+        let response = result.unwrap().to_string();
+
+        // Assert that we get the expected response
+        assert!(response.contains("HTTP/1.1 200 OK"));
+        assert!(!response.contains("\\\"item_id\\\":0,\\\"table_id\\\":1"));
+        assert!(!response.contains("\\\"item_id\\\":1,\\\"table_id\\\":1"));
+        assert!(!response.contains("\\\"item_id\\\":2,\\\"table_id\\\":1"));
+        assert!(response.contains("\\\"item_id\\\":3,\\\"table_id\\\":1"));
+        assert!(!response.contains("\\\"item_id\\\":4,\\\"table_id\\\":1"));
+        assert!(!response.contains("\\\"item_id\\\":5,\\\"table_id\\\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_put_occupancy() {
+        let restaurant = init_restaurant(10, 0);
+
+        let request = "PUT /tables/3/occupancy HTTP/1.1\r\n\r\n{\"occupied\": true}";
+        let result = handle_put_occupancy(&parse(request), "/tables/3/occupancy", restaurant.clone()).await;
+        assert!(result.unwrap().body_text().contains("Table 3 marked occupied"));
+        assert!(restaurant.get_table(3).read().summary().occupied);
+
+        let listing = handle_get_tables(&HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(listing.contains("{\\\"table_id\\\":3,\\\"occupied\\\":true,\\\"order_count\\\":0}"));
+
+        let request = "PUT /tables/3/occupancy HTTP/1.1\r\n\r\n{\"occupied\": false}";
+        let result = handle_put_occupancy(&parse(request), "/tables/3/occupancy", restaurant.clone()).await;
+        assert!(result.unwrap().body_text().contains("Table 3 marked free"));
+        assert!(!restaurant.get_table(3).read().summary().occupied);
+
+        // Unknown table
+        let request = "PUT /tables/30/occupancy HTTP/1.1\r\n\r\n{\"occupied\": true}";
+        let result = handle_put_occupancy(&parse(request), "/tables/30/occupancy", restaurant).await;
+        assert_eq!(result.unwrap().status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_auto_occupancy() {
+        let restaurant = init_restaurant(10, 0);
+
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [101, 102]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert!(restaurant.get_table(2).read().summary().occupied);
+
+        // Still occupied while an order remains
+        handle_delete_order(&parse(DELETE), "/orders/2/101", restaurant.clone()).await.unwrap();
+        assert!(restaurant.get_table(2).read().summary().occupied);
 
-    } else {
-        Err("Invalid path".to_string())
+        handle_delete_order(&parse(DELETE), "/orders/2/102", restaurant.clone()).await.unwrap();
+        assert!(!restaurant.get_table(2).read().summary().occupied);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_auto_occupancy_disabled() {
+        let restaurant = Restaurant::new(10).with_auto_occupancy(false);
 
-    fn init_restaurant(tables: usize, items: usize) -> Restaurant {
-        let restaurant = Restaurant::new(tables);
-        let table = restaurant.get_table(1);
-        for i in 0..items {
-            table.lock().unwrap().add_order(i as u32);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [101]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        assert!(!restaurant.get_table(2).read().summary().occupied);
+    }
+
+    /// A restaurant on a fake clock whose item 30 takes a minute to prepare,
+    /// with its webhook pointed at the returned listener.
+    async fn init_webhook_restaurant() -> (Restaurant, tokio::net::TcpListener) {
+        use crate::webhook::Webhook;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/ready", listener.local_addr().unwrap());
+        let menu = Menu::new(vec![MenuItem::new(30, "Special", Some("main")).with_prep_seconds(60)]);
+        let restaurant = Restaurant::with_clock(5, Arc::new(FakeClock::new(1_000)))
+            .with_menu(menu)
+            .with_admin_token(Some("secret".to_string()));
+        let webhook = Webhook::spawn(&url, restaurant.logger().clone()).unwrap();
+        (restaurant.with_webhook(webhook), listener)
+    }
+
+    /// Accepts one webhook delivery, answering `204`, and returns its request.
+    async fn next_ready(listener: &tokio::net::TcpListener) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let accepted = tokio::time::timeout(Duration::from_secs(2), listener.accept());
+        let (mut stream, _) = accepted.await.expect("no webhook delivery").unwrap();
+        let mut request = String::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with('}') {
+            let n = stream.read(&mut buffer).await.unwrap();
+            assert!(n > 0, "webhook request ended early");
+            request.push_str(&String::from_utf8_lossy(&buffer[..n]));
+        }
+        stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").await.unwrap();
+        request
+    }
+
+    fn armed(restaurant: &Restaurant) -> Vec<(u32, u32)> {
+        restaurant.webhook().unwrap().timers().armed()
+    }
+
+    async fn post(restaurant: &Restaurant, request: &str) -> Response {
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ready_notification_follows_bumps() {
+        let (restaurant, listener) = init_webhook_restaurant().await;
+
+        // Ordering the item again keeps a single timer
+        let order = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [30]}";
+        post(&restaurant, order).await;
+        post(&restaurant, order).await;
+        assert_eq!(armed(&restaurant), [(1, 30)]);
+
+        // A bump to ready announces the order on the fake clock's time
+        let bump = "POST /orders/1/items/30/bump HTTP/1.1\r\n\r\n{\"seconds\": 60}";
+        handle_bump_order(&parse(bump), "/orders/1/items/30/bump", restaurant.clone()).await.unwrap();
+        let request = next_ready(&listener).await;
+        assert!(request.starts_with("POST /ready HTTP/1.1"));
+        assert!(request.ends_with("{\"event\":\"order_ready\",\"table_id\":1,\"item_id\":30}"));
+        assert!(armed(&restaurant).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ready_notification_moves_with_merge() {
+        let (restaurant, listener) = init_webhook_restaurant().await;
+        post(&restaurant, "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [30]}").await;
+
+        let merge = "POST /tables/2/merge HTTP/1.1\r\n\r\n{\"from\": 1}";
+        handle_merge_tables(&parse(merge), "/tables/2/merge", restaurant.clone()).await.unwrap();
+        assert_eq!(armed(&restaurant), [(2, 30)]);
+
+        let bump = "POST /orders/2/items/30/bump HTTP/1.1\r\n\r\n{\"seconds\": 60}";
+        handle_bump_order(&parse(bump), "/orders/2/items/30/bump", restaurant.clone()).await.unwrap();
+        assert!(next_ready(&listener).await.ends_with("\"table_id\":2,\"item_id\":30}"));
+    }
+
+    #[tokio::test]
+    async fn test_ready_notification_cancelled() {
+        let (restaurant, listener) = init_webhook_restaurant().await;
+        for table_id in [1, 2, 3] {
+            let order = format!("POST /orders HTTP/1.1\r\n\r\n{{\"table_id\": {}, \"items\": [30]}}", table_id);
+            post(&restaurant, &order).await;
         }
+        assert_eq!(armed(&restaurant), [(1, 30), (2, 30), (3, 30)]);
+
+        // Serving announces the order at once and drops its timer
+        handle_fire_table("/orders/1/fire", restaurant.clone()).await.unwrap();
+        let serve = "POST /kitchen/serve-all HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n";
+        handle_serve_all(&parse(serve), restaurant.clone()).await.unwrap();
+        assert!(next_ready(&listener).await.ends_with("\"table_id\":1,\"item_id\":30}"));
+        assert_eq!(armed(&restaurant), [(2, 30), (3, 30)]);
+
+        handle_delete_order(&parse(DELETE), "/orders/2/30", restaurant.clone()).await.unwrap();
+        let void = "POST /orders/3/items/30/void?reason=spilled HTTP/1.1\r\n\r\n";
+        handle_void_order(&parse(void), "/orders/3/items/30/void", restaurant.clone()).await.unwrap();
+        assert!(armed(&restaurant).is_empty());
+
+        // Nothing else is announced, not even once the orders would have been ready
+        schedule_ready_notification(&restaurant, 1, 30);
+        assert!(armed(&restaurant).is_empty());
+        assert!(tokio::time::timeout(Duration::from_millis(200), listener.accept()).await.is_err());
+    }
+
+    /// Tables 1 and 2 have orders placed 30 minutes and 5 minutes before
+    /// `now`; all other tables are empty.
+    fn init_aged_restaurant() -> Restaurant {
+        let now = 10_000_000;
+        let restaurant = Restaurant::with_clock(4, Arc::new(FakeClock::new(now)));
+        restaurant.get_table(1).write().add_order_at(7, now - 30 * 60 * 1000);
+        restaurant.get_table(2).write().add_order_at(8, now - 5 * 60 * 1000);
         restaurant
     }
 
+    fn listed_tables(response: &Response) -> Vec<u64> {
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        let data: serde_json::Value = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
+        data.as_array()
+            .unwrap()
+            .iter()
+            .map(|table| table["table_id"].as_u64().unwrap())
+            .collect()
+    }
+
     #[tokio::test]
-    async fn test_handle_post_order_ok() {
-        // Create a sample request body
-        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [101, 102]}";
+    async fn test_handle_get_tables_filters() {
+        let restaurant = init_aged_restaurant();
 
-        // Create a mock Restaurant
-        let restaurant = init_restaurant(10, 5);
+        let query = http::parse_query("has_orders=true");
+        let response = handle_get_tables(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(listed_tables(&response), vec![1, 2]);
 
-        // Call the function
-        let result = handle_post_order(request, restaurant).await;
+        let query = http::parse_query("has_orders=false");
+        let response = handle_get_tables(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(listed_tables(&response), vec![0, 3]);
 
-        // Check if the result is as expected
-        assert!(result.is_ok());
-        let response = result.unwrap();
-        println!("test {:?}", response);
-        assert!(response.contains("HTTP/1.1 200 OK"));
-        assert!(response.contains("\\\"table_id\\\":2"));
-        assert!(response.contains("\\\"items\\\":[101,102]"));
+        let query = http::parse_query("older_than_seconds=1200");
+        let response = handle_get_tables(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(listed_tables(&response), vec![1]);
+
+        // Predicates are combined with AND
+        let query = http::parse_query("has_orders=false&older_than_seconds=1200");
+        let response = handle_get_tables(&query, restaurant.clone()).await.unwrap();
+        assert!(listed_tables(&response).is_empty());
+
+        let query = http::parse_query("has_orders=true&older_than_seconds=60");
+        let response = handle_get_tables(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(listed_tables(&response), vec![1, 2]);
+
+        let query = http::parse_query("older_than_seconds=soon");
+        assert!(handle_get_tables(&query, restaurant.clone()).await.is_err());
+
+        // Too many seconds to count in milliseconds is rejected, not wrapped
+        let query = http::parse_query(&format!("older_than_seconds={}", u64::MAX));
+        assert_eq!(handle_get_tables(&query, restaurant).await.unwrap_err(), "Invalid older_than_seconds");
     }
 
     #[tokio::test]
-    async fn test_handle_post_order_ng() {
-        // String table id
-        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": st, \"items\": [101, 102]}";
+    async fn test_empty_table_vs_missing_table() {
+        use crate::order::OrderStatus;
 
-        // Create a mock Restaurant
-        let restaurant = init_restaurant(10, 5);
-        let restaurant2 = restaurant.clone();
+        let restaurant = Restaurant::new(5);
+        let listing = |path: &'static str, query: &'static str| {
+            let restaurant = restaurant.clone();
+            async move { handle_get_order(path, &http::parse_query(query), restaurant).await.unwrap() }
+        };
 
-        // Call the function
-        let result = handle_post_order(request, restaurant).await;
+        let response = listing("/orders/2", "").await;
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"count\":0,\"data\":\"[]\",\"empty\":true,\"message\":\"Success!\",\"success\":true}"
+        );
+        assert_eq!(listing("/orders/5", "").await.status, 404);
 
-        // Check if the result is as expected
-        assert!(result.is_err());
-        let response = result.unwrap_err();
-        assert!(response.contains("Failed to parse order request"));
+        // A filter matching nothing on a table with orders is not an empty table
+        restaurant.get_table(2).write().add_order(7);
+        let response = listing("/orders/2", "status=Served").await.to_string();
+        assert!(response.contains("\"count\":0,\"data\":\"[]\",\"empty\":false"));
+        let response = listing("/orders/2", "status=Pending").await.to_string();
+        assert!(response.contains("\"count\":1,"));
+        assert_eq!(restaurant.get_table(2).read().get_order(7).unwrap().status, OrderStatus::Pending);
+    }
 
-        // String item id
-        let request2 = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [st, 102]}";
-        
-        // Call the function
-        let result2 = handle_post_order(request2, restaurant2).await;
-        // Check if the result is as expected
-        assert!(result2.is_err());
-        let response2 = result2.unwrap_err();
-        assert!(response2.contains("Failed to parse order request"));
+    #[tokio::test]
+    async fn test_handle_get_available_tables() {
+        let restaurant = Restaurant::new(6).with_auto_occupancy(false);
+        restaurant.get_table(1).write().add_order(7);
+        restaurant.get_table(4).write().add_order(8);
+        restaurant.get_table(3).write().set_occupied(true);
+
+        let response = handle_get_available_tables(restaurant).await.unwrap().to_string();
+        let body: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        let available: Vec<u32> = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
+        assert_eq!(available, vec![0, 2, 5]);
     }
 
     #[tokio::test]
-    async fn test_handle_delete_order_ok() {
-        // Create a sample path
-        let path = "/orders/1/2"; // Assuming table_id = 1, item_id = 2
+    async fn test_handle_get_wait_stats() {
+        use crate::order::Order;
 
-        // Create a mock Restaurant
-        let restaurant = init_restaurant(10, 5);
+        let restaurant = Restaurant::with_clock(4, Arc::new(FakeClock::new(20_000)));
+        {
+            let t = restaurant.get_table(2);
+            let mut table = t.write();
+            table.insert_order(Order::new(1, 2, 12, 10_000));
+            table.insert_order(Order::new(2, 2, 8, 18_000));
+        }
 
-        // Call the function
-        let result = handle_delete_order(path, restaurant).await;
+        let response = handle_get_wait_stats("/tables/2/wait-stats", restaurant.clone()).await;
+        assert_eq!(
+            response.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"avg_remaining_seconds\":4.0,\"max_remaining_seconds\":6,\"order_count\":2}"
+        );
 
-        // Check if the result is as expected
-        assert!(result.is_ok());
-        let response = result.unwrap();
-        assert!(response.contains("HTTP/1.1 200 OK"));
-        assert!(response.contains("Removed 2 from table 1"));
+        let response = handle_get_wait_stats("/tables/1/wait-stats", restaurant.clone()).await;
+        assert_eq!(
+            response.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"avg_remaining_seconds\":0.0,\"max_remaining_seconds\":0,\"order_count\":0}"
+        );
+
+        let response = handle_get_wait_stats("/tables/9/wait-stats", restaurant).await;
+        assert_eq!(response.unwrap().status, 404);
     }
 
     #[tokio::test]
-    async fn test_handle_get_all_orders_ok() {
-        // Create a sample path
-        let path = "/orders/1";
+    async fn test_handle_get_orders_grouped_by_category() {
+        let restaurant = Restaurant::new(4);
+        {
+            let t = restaurant.get_table(2);
+            let mut table = t.write();
+            table.insert_order(Order::new(1, 2, 5, 0));
+            table.insert_order(Order::new(6, 2, 9, 0));
+            table.insert_order(Order::new(7, 2, 12, 0));
+            table.insert_order(Order::new(101, 2, 7, 0));
+        }
 
-        // Create a mock Restaurant
-        let restaurant = init_restaurant(10, 5);
+        let query = http::parse_query("group_by=category");
+        let response = handle_get_order("/orders/2", &query, restaurant.clone()).await.unwrap().to_string();
+        let body_start = response.find("\r\n\r\n").unwrap() + 4;
+        let body: Value = serde_json::from_str(&response[body_start..]).unwrap();
+        let groups: Value = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
 
-        // Call the function
-        let result = handle_get_order(path, restaurant).await;
+        let item_ids = |category: &str| -> Vec<u64> {
+            let mut ids: Vec<u64> = groups[category]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|order| order["item_id"].as_u64().unwrap())
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(groups.as_object().unwrap().len(), 3);
+        assert_eq!(item_ids("appetizer"), vec![1]);
+        assert_eq!(item_ids("main"), vec![6, 7]);
+        assert_eq!(item_ids("uncategorized"), vec![101]);
 
-        // Check if the result is as expected
-        assert!(result.is_ok());
-        let response = result.unwrap();
+        let query = http::parse_query("group_by=colour");
+        assert!(handle_get_order("/orders/2", &query, restaurant).await.is_err());
+    }
 
-        // Assert that we get the expected response
-        assert!(response.contains("HTTP/1.1 200 OK"));
-        assert!(response.contains("\\\"item_id\\\":0,\\\"table_id\\\":1"));
-        assert!(response.contains("\\\"item_id\\\":1,\\\"table_id\\\":1"));
-        assert!(response.contains("\\\"item_id\\\":2,\\\"table_id\\\":1"));
-        assert!(response.contains("\\\"item_id\\\":3,\\\"table_id\\\":1"));
-        assert!(response.contains("\\\"item_id\\\":4,\\\"table_id\\\":1"));
-        assert!(!response.contains("\\\"item_id\\\":5,\\\"table_id\\\":1"));
+    #[tokio::test]
+    async fn test_handle_get_orders_ndjson() {
+        let restaurant = init_restaurant(10, 3);
+
+        let response = handle_get_orders_ndjson("/orders/1", restaurant.clone()).await.unwrap().to_string();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Content-Type: application/x-ndjson"));
+
+        let mut item_ids: Vec<u64> = body
+            .lines()
+            .map(|line| {
+                let order: Value = serde_json::from_str(line).unwrap();
+                assert!(order.is_object());
+                order["item_id"].as_u64().unwrap()
+            })
+            .collect();
+        item_ids.sort();
+        assert_eq!(item_ids, vec![0, 1, 2]);
+        assert!(body.ends_with('\n'));
+
+        let response = handle_get_orders_ndjson("/orders/2", restaurant).await.unwrap().to_string();
+        assert!(response.ends_with("\r\n\r\n"));
     }
 
     #[tokio::test]
-    async fn test_handle_get_one_order_ok() {
-        // Create a sample path
-        let path = "/orders/1/items/3";
+    async fn test_handle_get_order_status_filter() {
+        let restaurant = Restaurant::new(6);
+        for (item_id, status) in [(10, OrderStatus::Cooking), (11, OrderStatus::Pending), (12, OrderStatus::Cooking)] {
+            let mut order = Order::new(item_id, 5, 5, 0);
+            order.status = status;
+            restaurant.get_table(5).write().insert_order(order);
+        }
 
-        // Create a mock Restaurant
-        let restaurant = init_restaurant(10, 5);
+        let query = http::parse_query("status=Cooking");
+        let response = handle_get_order("/orders/5", &query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\\\"item_id\\\":10"));
+        assert!(!response.contains("\\\"item_id\\\":11"));
+        assert!(response.contains("\\\"item_id\\\":12"));
 
-        // Call the function
-        let result = handle_get_order(path, restaurant).await;
+        let query = http::parse_query("status=Served");
+        let response = handle_get_order("/orders/5", &query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"data\":\"[]\""));
 
-        // Check if the result is as expected
-        assert!(result.is_ok());
-        let response = result.unwrap();
+        let query = http::parse_query("status=Burnt");
+        let result = handle_get_order("/orders/5", &query, restaurant).await;
+        assert_eq!(result.unwrap_err(), "Invalid status");
+    }
 
-        // Assert that we get the expected response
-        assert!(response.contains("HTTP/1.1 200 OK"));
-        assert!(!response.contains("\\\"item_id\\\":0,\\\"table_id\\\":1"));
-        assert!(!response.contains("\\\"item_id\\\":1,\\\"table_id\\\":1"));
-        assert!(!response.contains("\\\"item_id\\\":2,\\\"table_id\\\":1"));
-        assert!(response.contains("\\\"item_id\\\":3,\\\"table_id\\\":1"));
-        assert!(!response.contains("\\\"item_id\\\":4,\\\"table_id\\\":1"));
-        assert!(!response.contains("\\\"item_id\\\":5,\\\"table_id\\\":1"));
+    #[tokio::test]
+    async fn test_handle_get_order_sort() {
+        let clock = Arc::new(FakeClock::new(2_000));
+        let restaurant = Restaurant::with_clock(6, clock);
+        for (item_id, waiting_time, created_at) in [(10, 30, 0), (11, 5, 0), (12, 20, 0), (13, 4, 1_000)] {
+            restaurant.get_table(5).write().insert_order(Order::new(item_id, 5, waiting_time, created_at));
+        }
+        let item_ids = |response: &Response| -> Vec<u32> {
+            response
+                .body_text()
+                .split("\\\"item_id\\\":")
+                .skip(1)
+                .map(|rest| rest.split(',').next().unwrap().parse().unwrap())
+                .collect()
+        };
+
+        // Items 11 and 13 both have 3 seconds left; 11 was placed first
+        let query = http::parse_query("sort=remaining_asc");
+        let response = handle_get_order("/orders/5", &query, restaurant.clone()).await.unwrap();
+        assert_eq!(item_ids(&response), vec![11, 13, 12, 10]);
+
+        let query = http::parse_query("sort=remaining_desc");
+        let response = handle_get_order("/orders/5", &query, restaurant.clone()).await.unwrap();
+        assert_eq!(item_ids(&response), vec![10, 12, 11, 13]);
+
+        let response = handle_get_order("/orders/5", &HashMap::new(), restaurant.clone()).await.unwrap();
+        assert_eq!(item_ids(&response), vec![10, 11, 12, 13]);
+
+        let query = http::parse_query("sort=name");
+        let result = handle_get_order("/orders/5", &query, restaurant).await;
+        assert_eq!(result.unwrap_err(), "Invalid sort");
+    }
+
+    #[tokio::test]
+    async fn test_order_ready_at() {
+        let restaurant = Restaurant::with_clock(6, Arc::new(FakeClock::new(50_000)));
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [7]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        let response = handle_get_order("/orders/3/items/7", &HashMap::new(), restaurant).await.unwrap().to_string();
+        let body: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        let order: Value = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
+        assert_eq!(order["created_at"], 50_000);
+        let waiting_time = order["waiting_time"].as_u64().unwrap();
+        assert_eq!(order["ready_at"], 50_000 + waiting_time * 1000);
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_diagnostics() {
+        let restaurant = Restaurant::new(4).with_admin_token(Some("secret".to_string()));
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [7, 8]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [9]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        let _connection = restaurant.metrics().track_connection();
+
+        let request = "GET /admin/diagnostics HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n";
+        let response = handle_get_diagnostics(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"active_connections\":1,\"table_count\":4,\"total_orders\":3,\"uptime_seconds\":0}"
+        );
+
+        // A table held by a writer does not hold up the response
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let t = restaurant.get_table(1);
+        let holder = std::thread::spawn(move || {
+            let _held = t.write();
+            locked_tx.send(()).unwrap();
+            let _ = release_rx.recv_timeout(Duration::from_secs(2));
+        });
+        locked_rx.recv().unwrap();
+
+        let started = std::time::Instant::now();
+        let response = handle_get_diagnostics(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        assert!(response.contains("\"total_orders\":3"));
+
+        let request = "GET /admin/diagnostics HTTP/1.1\r\n\r\n";
+        let response = handle_get_diagnostics(&parse(request), restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_events() {
+        let clock = Arc::new(FakeClock::new(1_000));
+        let restaurant = Restaurant::with_clock(4, clock.clone())
+            .with_admin_token(Some("secret".to_string()));
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        clock.advance(1_000);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 1, \"items\": [8]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+        clock.advance(1_000);
+        handle_delete_order(&parse("DELETE /orders/2/7 HTTP/1.1\r\n\r\n"), "/orders/2/7", restaurant.clone())
+            .await
+            .unwrap();
+
+        let request = "GET /events HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n";
+        let response = handle_get_events(&parse(request), &HashMap::new(), restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"events\":[\
+             {\"action\":\"added\",\"item_id\":7,\"seq\":1,\"table_id\":2,\"timestamp\":1000},\
+             {\"action\":\"added\",\"item_id\":8,\"seq\":2,\"table_id\":1,\"timestamp\":2000},\
+             {\"action\":\"removed\",\"item_id\":7,\"seq\":3,\"table_id\":2,\"timestamp\":3000}]}"
+        );
+
+        let query = http::parse_query("since=1000&limit=1");
+        let response = handle_get_events(&parse(request), &query, restaurant.clone()).await.unwrap();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"events\":[{\"action\":\"added\",\"item_id\":8,\"seq\":2,\"table_id\":1,\"timestamp\":2000}]}"
+        );
+
+        // Every item of one order shares a timestamp, but not a sequence number
+        let order = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [1, 2, 3]}";
+        handle_post_order(&parse(order), restaurant.clone()).await.unwrap();
+        let query = http::parse_query("since_seq=3&limit=1");
+        let response = handle_get_events(&parse(request), &query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"item_id\":1,\"seq\":4"));
+        let query = http::parse_query("since_seq=4");
+        let response = handle_get_events(&parse(request), &query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"item_id\":2,\"seq\":5"));
+        assert!(response.contains("\"item_id\":3,\"seq\":6"));
+        assert!(!response.contains("\"seq\":4"));
+
+        let query = http::parse_query("since=soon");
+        let response = handle_get_events(&parse(request), &query, restaurant.clone()).await;
+        assert_eq!(response.unwrap_err(), "Invalid since");
+
+        let request = "GET /events HTTP/1.1\r\n\r\n";
+        let response = handle_get_events(&parse(request), &HashMap::new(), restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_serve_all() {
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5).with_admin_token(Some("secret".to_string()));
+        for (table_id, item_id, status) in [
+            (1, 10, OrderStatus::Cooking),
+            (1, 11, OrderStatus::Pending),
+            (3, 12, OrderStatus::Cooking),
+            (4, 13, OrderStatus::Served),
+        ] {
+            let mut order = Order::new(item_id, table_id, 5, 0);
+            order.status = status;
+            restaurant.get_table(table_id).write().insert_order(order);
+        }
+
+        let request = "POST /kitchen/serve-all HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n";
+        let response = handle_serve_all(&parse(request), restaurant.clone()).await.unwrap();
+        assert_eq!(response.to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"served\":2}");
+
+        let status = |table_id: u32, item_id: u32| {
+            restaurant.get_table(table_id).read().get_order(item_id).unwrap().status
+        };
+        assert_eq!(status(1, 10), OrderStatus::Served);
+        assert_eq!(status(1, 11), OrderStatus::Pending);
+        assert_eq!(status(3, 12), OrderStatus::Served);
+        assert_eq!(status(4, 13), OrderStatus::Served);
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_kitchen_overview() {
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5);
+        for (table_id, item_id, status) in [
+            (1, 10, OrderStatus::Pending),
+            (1, 11, OrderStatus::Cooking),
+            (1, 12, OrderStatus::Cooking),
+            (3, 13, OrderStatus::Served),
+            (4, 14, OrderStatus::Pending),
+            (4, 15, OrderStatus::Served),
+        ] {
+            let mut order = Order::new(item_id, table_id, 5, 0);
+            order.status = status;
+            restaurant.get_table(table_id).write().insert_order(order);
+        }
+        // Tables without orders are left out
+        restaurant.get_table(2).write().set_occupied(true);
+
+        let response = handle_get_kitchen_overview(restaurant).await.unwrap().to_string();
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(body).unwrap(),
+            json!([
+                {"table_id": 1, "pending": 1, "cooking": 2, "served": 0},
+                {"table_id": 3, "pending": 0, "cooking": 0, "served": 1},
+                {"table_id": 4, "pending": 1, "cooking": 0, "served": 1},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_bump_order() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone());
+        restaurant.get_table(2).write().add_order_with_wait(7, 120);
+        clock.advance(30_000);
+        let remaining = || restaurant.get_table(2).read().get_order(7).unwrap().remaining_seconds(restaurant.now_ms());
+        assert_eq!(remaining(), 90);
+
+        let request = parse("POST /orders/2/items/7/bump HTTP/1.1\r\n\r\n{\"seconds\": 45}");
+        let response = handle_bump_order(&request, "/orders/2/items/7/bump", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(remaining(), 45);
+
+        let request = parse("POST /orders/2/items/7/bump HTTP/1.1\r\n\r\n{\"seconds\": 600}");
+        handle_bump_order(&request, "/orders/2/items/7/bump", restaurant.clone()).await.unwrap();
+        assert_eq!(remaining(), 0);
+
+        let response = handle_bump_order(&request, "/orders/2/items/8/bump", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_void_order() {
+        use crate::events::Action;
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [7, 8]}";
+        handle_post_order(&parse(request), restaurant.clone()).await.unwrap();
+
+        let request = parse("POST /orders/2/items/7/void HTTP/1.1\r\n\r\n");
+        let result = handle_void_order(&request, "/orders/2/items/7/void", restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"reason required\",\"success\":false}");
+
+        let request = parse("POST /orders/2/items/7/void HTTP/1.1\r\n\r\n{\"reason\": \"rung up twice\"}");
+        let response = handle_void_order(&request, "/orders/2/items/7/void", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Voided"));
+
+        // Gone from the listing and the bill, but kept in the void log
+        let table = restaurant.get_table(2);
+        assert_eq!(table.read().item_ids(), vec![8]);
+        let bill = handle_get_order("/orders/2/bill", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(!bill.contains("\"item_id\":7"));
+        let voids = restaurant.voids().for_table(2);
+        assert_eq!(voids.len(), 1);
+        assert_eq!((voids[0].order.item_id, voids[0].order.status), (7, OrderStatus::Voided));
+        assert_eq!((voids[0].reason.as_str(), voids[0].manager_override), ("rung up twice", false));
+        let event = restaurant.events().page(0, 0, 10).pop().unwrap();
+        assert_eq!((event.action, event.reason.as_deref()), (Action::Voided, Some("rung up twice")));
+        let listed = handle_get_order("/orders/2/voids", &HashMap::new(), restaurant.clone()).await.unwrap().to_string();
+        assert!(listed.contains("rung up twice"));
+
+        // Voiding it again finds nothing to void
+        let request = parse("POST /orders/2/items/7/void?reason=again HTTP/1.1\r\n\r\n");
+        let response = handle_void_order(&request, "/orders/2/items/7/void", restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_void_served_order_needs_override() {
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5).with_admin_token(Some("secret".to_string()));
+        let mut served = Order::new(7, 2, 5, 0);
+        served.status = OrderStatus::Served;
+        restaurant.get_table(2).write().insert_order(served);
+
+        let request = parse("POST /orders/2/items/7/void?reason=comped HTTP/1.1\r\n\r\n");
+        let response = handle_void_order(&request, "/orders/2/items/7/void", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+        assert!(restaurant.get_table(2).read().get_order(7).is_some());
+        assert!(restaurant.voids().for_table(2).is_empty());
+
+        let request = parse("POST /orders/2/items/7/void?reason=comped HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n");
+        let response = handle_void_order(&request, "/orders/2/items/7/void", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(restaurant.get_table(2).read().get_order(7).is_none());
+        assert!(restaurant.voids().for_table(2)[0].manager_override);
+    }
+
+    #[tokio::test]
+    async fn test_handle_resend_orders() {
+        use crate::events::Action;
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5);
+        for (item_id, status) in [(10, OrderStatus::Pending), (11, OrderStatus::Cooking), (12, OrderStatus::Served)] {
+            let mut order = Order::new(item_id, 2, 5, 0);
+            order.status = status;
+            restaurant.get_table(2).write().insert_order(order);
+        }
+        let resent = || -> Vec<u32> {
+            restaurant
+                .events()
+                .page(0, 0, 10)
+                .into_iter()
+                .filter(|event| event.action == Action::Resent)
+                .map(|event| event.item_id)
+                .collect()
+        };
+
+        // A watcher of the table is woken by the resend
+        let watcher = restaurant.clone();
+        let watching = tokio::spawn(async move {
+            handle_get_order("/orders/2", &http::parse_query("wait=5"), watcher).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let request = parse("POST /orders/2/resend HTTP/1.1\r\n\r\n");
+        let response = handle_resend_orders(&request, "/orders/2/resend", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.ends_with("{\"resent\":[10,11]}"));
+        assert!(watching.await.unwrap().unwrap().body_text().contains("\"changed\":true"));
+        assert_eq!(resent(), vec![10, 11]);
+        assert_eq!(restaurant.get_table(2).read().get_order(10).unwrap().status, OrderStatus::Pending);
+
+        let request = parse("POST /orders/2/resend HTTP/1.1\r\n\r\n{\"items\": [12]}");
+        let response = handle_resend_orders(&request, "/orders/2/resend", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.ends_with("{\"resent\":[12]}"));
+        assert_eq!(resent(), vec![10, 11, 12]);
+
+        let request = parse("POST /orders/2/resend HTTP/1.1\r\n\r\n{\"items\": [13]}");
+        let result = handle_resend_orders(&request, "/orders/2/resend", restaurant).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"item 13 is not on table 2\",\"success\":false}");
+    }
+
+    #[tokio::test]
+    async fn test_handle_fire_table() {
+        use crate::order::OrderStatus;
+
+        let restaurant = Restaurant::new(5);
+        for (item_id, status) in [
+            (10, OrderStatus::Pending),
+            (11, OrderStatus::Cooking),
+            (12, OrderStatus::Served),
+            (13, OrderStatus::Pending),
+        ] {
+            let mut order = Order::new(item_id, 2, 5, 0);
+            order.status = status;
+            restaurant.get_table(2).write().insert_order(order);
+        }
+
+        let response = handle_fire_table("/orders/2/fire", restaurant.clone()).await.unwrap();
+        assert_eq!(response.to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"fired\":2}");
+
+        let order = |item_id: u32| restaurant.get_table(2).read().get_order(item_id).unwrap().clone();
+        assert_eq!(order(10).status, OrderStatus::Cooking);
+        assert!(order(10).fired_at.is_some());
+        assert_eq!(order(13).status, OrderStatus::Cooking);
+        assert_eq!((order(11).status, order(11).fired_at), (OrderStatus::Cooking, None));
+        assert_eq!(order(12).status, OrderStatus::Served);
+
+        let response = handle_fire_table("/orders/2/fire", restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("{\"fired\":0}"));
+        let response = handle_fire_table("/orders/9/fire", restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_serve_all_requires_token() {
+        let restaurant = Restaurant::new(5).with_admin_token(Some("secret".to_string()));
+
+        let request = "POST /kitchen/serve-all HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n";
+        let response = handle_serve_all(&parse(request), restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+
+        let request = "POST /kitchen/serve-all HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n";
+        let response = handle_serve_all(&parse(request), restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        // Without a configured token the endpoint is disabled
+        let response = handle_serve_all(&parse(request), Restaurant::new(5)).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_kitchen_queue() {
+        // Items 6 and 8 go to the grill, 18 to the bar, 101 to the default station
+        let restaurant = Restaurant::new(5);
+        for (table_id, item_id, created_at) in [(1, 8, 300), (2, 6, 100), (2, 18, 200), (3, 101, 50)] {
+            restaurant
+                .get_table(table_id)
+                .write()
+                .insert_order(Order::new(item_id, table_id, 10, created_at));
+        }
+        let mut served = Order::new(7, 4, 10, 0);
+        served.status = OrderStatus::Served;
+        restaurant.get_table(4).write().insert_order(served);
+
+        let queued = |response: Response| -> Vec<(u64, String)> {
+            let body: Value = serde_json::from_slice(&response.body).unwrap();
+            let data: Value = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
+            data.as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    (entry["item_id"].as_u64().unwrap(), entry["station"].as_str().unwrap().to_string())
+                })
+                .collect()
+        };
+
+        let query = http::parse_query("station=grill");
+        let response = handle_get_kitchen_queue(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(queued(response), vec![(6, "grill".to_string()), (8, "grill".to_string())]);
+
+        let query = http::parse_query("station=bar");
+        let response = handle_get_kitchen_queue(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(queued(response), vec![(18, "bar".to_string())]);
+
+        let query = http::parse_query("station=kitchen");
+        let response = handle_get_kitchen_queue(&query, restaurant.clone()).await.unwrap();
+        assert_eq!(queued(response), vec![(101, "kitchen".to_string())]);
+
+        // Without a station every outstanding order is listed, oldest first
+        let response = handle_get_kitchen_queue(&HashMap::new(), restaurant).await.unwrap();
+        let items: Vec<u64> = queued(response).into_iter().map(|(item_id, _)| item_id).collect();
+        assert_eq!(items, vec![101, 6, 18, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_kitchen_queue_category_priorities() {
+        // Items 6 and 8 are mains, 18 and 19 drinks, 15 a dessert
+        let priorities = CategoryPriorities::parse("drink=0,main=1").unwrap();
+        let restaurant = Restaurant::new(5).with_category_priorities(priorities);
+        for (table_id, item_id, created_at) in [(1, 8, 100), (2, 6, 200), (2, 18, 300), (3, 15, 50), (3, 19, 400)] {
+            restaurant
+                .get_table(table_id)
+                .write()
+                .insert_order(Order::new(item_id, table_id, 10, created_at));
+        }
+
+        let response = handle_get_kitchen_queue(&HashMap::new(), restaurant).await.unwrap().to_string();
+        let body_start = response.find("\r\n\r\n").unwrap() + 4;
+        let body: Value = serde_json::from_str(&response[body_start..]).unwrap();
+        let data: Value = serde_json::from_str(body["data"].as_str().unwrap()).unwrap();
+        let items: Vec<u64> = data
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["item_id"].as_u64().unwrap())
+            .collect();
+
+        // Drinks fire first even though the mains were ordered earlier;
+        // the unprioritized dessert comes last despite being the oldest
+        assert_eq!(items, vec![18, 19, 8, 6, 15]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_delete_order_no_content() {
+        // Opted in per request
+        let restaurant = init_restaurant(5, 3);
+        let request = parse("DELETE /orders/1/0 HTTP/1.1\r\nPrefer: return=minimal\r\n\r\n");
+        let result = handle_delete_order(&request, "/orders/1/0", restaurant.clone()).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 204 No Content\r\n\r\n");
+
+        let result = handle_delete_order(&request, "/orders/1/0", restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 404);
+
+        // The JSON message stays the default
+        let result = handle_delete_order(&parse(DELETE), "/orders/1/1", restaurant).await;
+        assert!(result.unwrap().body_text().contains("Removed 1 from table 1"));
+
+        // Opted in by configuration
+        let restaurant = init_restaurant(5, 3).with_delete_no_content(true);
+        let result = handle_delete_order(&parse(DELETE), "/orders/1", restaurant.clone()).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 204 No Content\r\n\r\n");
+        assert!(restaurant.get_table(1).read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_get_health() {
+        let restaurant = Restaurant::new(5);
+
+        let response = handle_get_health(&HashMap::new(), restaurant.clone()).await.unwrap();
+        assert_eq!(response.to_string(), "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"status\":\"ok\"}");
+
+        // Without a running accept loop the server cannot take requests
+        let query = http::parse_query("verbose=true");
+        let response = handle_get_health(&query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("\"failing\":[\"accept_loop\"]"));
+
+        restaurant.metrics().record_accept_loop(restaurant.now_ms());
+        let response = handle_get_health(&query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"table_locks\":\"ok\""));
+        assert!(response.contains("\"runtime\":\"ok\""));
+
+        // A lock that stays held is flagged
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let t = restaurant.get_table(3);
+        let holder = std::thread::spawn(move || {
+            let _held = t.write();
+            locked_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        });
+        locked_rx.recv().unwrap();
+
+        let response = handle_get_health(&query, restaurant).await.unwrap().to_string();
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.contains("\"failing\":[\"table_locks\"]"));
+        assert!(response.contains("\"stuck_tables\":[3]"));
+    }
+
+    #[tokio::test]
+    async fn test_health_flags_wedged_accept_loop() {
+        let clock = Arc::new(FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(5, clock.clone());
+        let query = http::parse_query("verbose=true");
+        restaurant.metrics().record_accept_loop(restaurant.now_ms());
+
+        clock.advance(ACCEPT_LOOP_STALE_MS);
+        let response = handle_get_health(&query, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        // The loop stopped checking in, as if it were stuck
+        clock.advance(1);
+        let response = handle_get_health(&query, restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.contains("\"failing\":[\"accept_loop\"]"));
+        assert!(response.contains("\"last_seen_ms_ago\":5001"));
+    }
+
+    /// A response body whose serialization always fails.
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("order went missing"))
+        }
+    }
+
+    #[test]
+    fn test_serialization_failure_is_500() {
+        let (logger, lines) = crate::log::Logger::capturing(crate::log::Level::Error);
+        let restaurant = Restaurant::new(1).with_logger(logger);
+
+        assert_eq!(json_ok(&Unserializable, &restaurant), internal_server_error());
+        assert_eq!(success(&Unserializable, &restaurant), internal_server_error());
+        assert_eq!(
+            *lines.lock().unwrap(),
+            vec!["[ERROR] Failed to serialize response: order went missing"; 2]
+        );
     }
 }
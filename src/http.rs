@@ -0,0 +1,604 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest request body accepted, after any transfer decoding.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Largest request line and headers accepted.
+pub const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// A request method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl Method {
+    /// Parses a method name case-insensitively, returning `None` for unknown methods.
+    pub fn parse(name: &str) -> Option<Method> {
+        match name.to_ascii_uppercase().as_str() {
+            "GET" => Some(Method::Get),
+            "HEAD" => Some(Method::Head),
+            "POST" => Some(Method::Post),
+            "PUT" => Some(Method::Put),
+            "PATCH" => Some(Method::Patch),
+            "DELETE" => Some(Method::Delete),
+            "OPTIONS" => Some(Method::Options),
+            _ => None,
+        }
+    }
+}
+
+/// A request split into its request line, headers and body.
+#[derive(Debug, PartialEq)]
+pub struct RawRequest<'a> {
+    pub method: &'a str,
+    pub target: &'a str,
+    pub headers: Vec<(&'a str, &'a str)>,
+    pub body: &'a str,
+}
+
+impl<'a> RawRequest<'a> {
+    /// Parses a request, returning `None` if the request line is malformed.
+    ///
+    /// A request without a blank line after its headers has an empty body.
+    pub fn parse(request: &'a str) -> Option<RawRequest<'a>> {
+        let (head, body) = request.split_once("\r\n\r\n").unwrap_or((request, ""));
+        let mut lines = head.lines();
+
+        let request_line: Vec<&str> = lines.next()?.split_whitespace().collect();
+        if request_line.len() != 3 {
+            return None;
+        }
+
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+
+        Some(RawRequest {
+            method: request_line[0],
+            target: request_line[1],
+            headers,
+            body,
+        })
+    }
+
+    /// Returns the value of the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Why a chunked body could not be decoded.
+#[derive(Debug, PartialEq)]
+pub enum ChunkedError {
+    /// A chunk header or delimiter is not valid chunked encoding.
+    Malformed,
+    /// The decoded body is larger than allowed.
+    TooLarge,
+}
+
+/// Splits a request target such as `/tables?has_orders=true` into its path and query string.
+pub fn split_target(target: &str) -> (&str, &str) {
+    target.split_once('?').unwrap_or((target, ""))
+}
+
+/// Why a request path was refused by `decode_path`.
+#[derive(Debug, PartialEq)]
+pub enum PathDecodeError {
+    /// A `%` is not followed by two hex digits, or the bytes are not UTF-8.
+    Malformed,
+    /// `%2F` or `%5C`, which would turn into a separator only after routing.
+    EncodedSlash,
+    /// A `.` or `..` segment, written plainly or percent-encoded.
+    Traversal,
+}
+
+impl fmt::Display for PathDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathDecodeError::Malformed => write!(f, "Malformed percent-encoding in path"),
+            PathDecodeError::EncodedSlash => write!(f, "Encoded slash in path"),
+            PathDecodeError::Traversal => write!(f, "Path traversal not allowed"),
+        }
+    }
+}
+
+/// Percent-decodes a request path, refusing encoded slashes and `.`/`..`
+/// segments so that what gets routed is exactly what was asked for.
+pub fn decode_path(path: &str) -> Result<String, PathDecodeError> {
+    let raw = path.as_bytes();
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] != b'%' {
+            decoded.push(raw[i]);
+            i += 1;
+            continue;
+        }
+        let byte = path
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(PathDecodeError::Malformed)?;
+        if byte == b'/' || byte == b'\\' {
+            return Err(PathDecodeError::EncodedSlash);
+        }
+        decoded.push(byte);
+        i += 3;
+    }
+    let decoded = String::from_utf8(decoded).map_err(|_| PathDecodeError::Malformed)?;
+    if decoded.split('/').any(|segment| segment == "." || segment == "..") {
+        return Err(PathDecodeError::Traversal);
+    }
+    Ok(decoded)
+}
+
+/// Why a path parameter could not be read.
+#[derive(Debug, PartialEq)]
+pub enum PathError {
+    /// The path has no segment where the parameter belongs.
+    Missing(&'static str),
+    /// The segment is there but does not parse as the parameter's type.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::Missing(name) => write!(f, "Missing {}", name),
+            PathError::Invalid(name) => write!(f, "Invalid {}", name),
+        }
+    }
+}
+
+impl PathError {
+    /// The parameter's name as a JSON field, such as `table_id`.
+    pub fn field(&self) -> String {
+        match self {
+            PathError::Missing(name) | PathError::Invalid(name) => name.replace(' ', "_"),
+        }
+    }
+}
+
+impl From<PathError> for String {
+    fn from(err: PathError) -> String {
+        err.to_string()
+    }
+}
+
+/// The segments of a request path such as `/orders/3/items/7`, numbered from
+/// `0` after the leading slash, with typed access to the parameters among them.
+#[derive(Debug, PartialEq)]
+pub struct PathParams<'a> {
+    segments: Vec<&'a str>,
+}
+
+impl<'a> PathParams<'a> {
+    pub fn parse(path: &'a str) -> PathParams<'a> {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        PathParams {
+            segments: path.split('/').collect(),
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns true if the path has as many segments as `pattern` and each
+    /// segment equals the pattern's, except that `:name` segments match anything.
+    pub fn matches(&self, pattern: &[&str]) -> bool {
+        self.segments.len() == pattern.len()
+            && self
+                .segments
+                .iter()
+                .zip(pattern)
+                .all(|(segment, expected)| expected.starts_with(':') || segment == expected)
+    }
+
+    /// Parses segment `index` as the parameter called `name` in errors.
+    pub fn param<T: FromStr>(&self, index: usize, name: &'static str) -> Result<T, PathError> {
+        let segment = self.segments.get(index).ok_or(PathError::Missing(name))?;
+        segment.parse().map_err(|_| PathError::Invalid(name))
+    }
+}
+
+/// Parses a query string such as `a=1&b=2` into a map of parameters.
+///
+/// Parameters without a value map to an empty string; later duplicates win.
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Returns the value of the first header named `name` (case-insensitive).
+pub fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Returns the HTTP version named in the request line, such as `HTTP/1.0`.
+pub fn version(request: &str) -> Option<&str> {
+    let request_line = request.lines().next()?;
+    request_line.split_whitespace().nth(2)
+}
+
+/// Returns the body length declared by the `Content-Length` header, if any.
+pub fn content_length(head: &str) -> Option<usize> {
+    header(head, "Content-Length")?.parse().ok()
+}
+
+/// Returns true if the request body uses `Transfer-Encoding: chunked`.
+pub fn is_chunked(head: &str) -> bool {
+    header(head, "Transfer-Encoding").is_some_and(|value| {
+        value
+            .split(',')
+            .any(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    })
+}
+
+/// Returns the index of the first occurrence of `needle` in `data`.
+pub fn find_bytes(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Decodes a chunked body.
+///
+/// Returns `Ok(None)` while `data` does not yet hold the terminating chunk,
+/// and `Ok(Some(body))` once the whole body has been received.
+pub fn decode_chunked(data: &[u8], max_size: usize) -> Result<Option<Vec<u8>>, ChunkedError> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let line_end = match find_bytes(&data[pos..], b"\r\n") {
+            Some(i) => pos + i,
+            None => return Ok(None),
+        };
+        let size_line =
+            std::str::from_utf8(&data[pos..line_end]).map_err(|_| ChunkedError::Malformed)?;
+        // Chunk extensions after `;` are ignored
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size =
+            usize::from_str_radix(size_hex, 16).map_err(|_| ChunkedError::Malformed)?;
+        let chunk_start = line_end + 2;
+
+        if size == 0 {
+            // Skip any trailer fields up to the final empty line
+            let mut trailer = chunk_start;
+            loop {
+                match find_bytes(&data[trailer..], b"\r\n") {
+                    None => return Ok(None),
+                    Some(0) => return Ok(Some(body)),
+                    Some(i) => trailer += i + 2,
+                }
+            }
+        }
+        if body.len().saturating_add(size) > max_size {
+            return Err(ChunkedError::TooLarge);
+        }
+
+        let chunk_end = chunk_start + size;
+        if data.len() < chunk_end + 2 {
+            return Ok(None);
+        }
+        if &data[chunk_end..chunk_end + 2] != b"\r\n" {
+            return Err(ChunkedError::Malformed);
+        }
+        body.extend_from_slice(&data[chunk_start..chunk_end]);
+        pos = chunk_end + 2;
+    }
+}
+
+/// A response: status, headers and body.
+///
+/// Headers are kept in a map, one value per name, and written sorted by
+/// name. `Connection` and `Content-Length` are left to the connection
+/// handler, which knows whether the connection stays open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub version: &'static str,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates an HTTP/1.1 response with the given status and no headers or body.
+    pub fn new(status: u16) -> Response {
+        Response {
+            version: "HTTP/1.1",
+            status,
+            headers: BTreeMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Answers with `version` in the status line.
+    pub fn with_version(mut self, version: &'static str) -> Response {
+        self.version = version;
+        self
+    }
+
+    /// Adds a header, replacing any earlier header of the same name.
+    pub fn with_header(mut self, name: &str, value: impl fmt::Display) -> Response {
+        self.headers.retain(|key, _| !key.eq_ignore_ascii_case(name));
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the body.
+    pub fn with_body(self, body: impl fmt::Display) -> Response {
+        self.with_body_bytes(body.to_string().into_bytes())
+    }
+
+    /// Sets the body to raw bytes, such as a compressed body.
+    pub fn with_body_bytes(mut self, body: Vec<u8>) -> Response {
+        self.body = body;
+        self
+    }
+
+    /// Returns the value of a header, matching its name case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the body as text, replacing any invalid UTF-8.
+    pub fn body_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    /// Serializes the response as an HTTP message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("{} {} {}\r\n", self.version, self.status, reason_phrase(self.status)).into_bytes();
+        for (name, value) in &self.headers {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+/// Returns the reason phrase for a status code.
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!(Method::parse("GET"), Some(Method::Get));
+        assert_eq!(Method::parse("get"), Some(Method::Get));
+        assert_eq!(Method::parse("Delete"), Some(Method::Delete));
+        assert_eq!(Method::parse("BREW"), None);
+    }
+
+    #[test]
+    fn test_decode_path() {
+        assert_eq!(decode_path("/orders/1"), Ok("/orders/1".to_string()));
+        assert_eq!(decode_path("/orders/%31/items/%32"), Ok("/orders/1/items/2".to_string()));
+        assert_eq!(decode_path("/orders/1%2Fitems"), Err(PathDecodeError::EncodedSlash));
+        assert_eq!(decode_path("/orders/1%2fitems"), Err(PathDecodeError::EncodedSlash));
+        assert_eq!(decode_path("/orders/1%5Citems"), Err(PathDecodeError::EncodedSlash));
+        assert_eq!(decode_path("/orders/../admin"), Err(PathDecodeError::Traversal));
+        assert_eq!(decode_path("/orders/%2e%2e/admin"), Err(PathDecodeError::Traversal));
+        assert_eq!(decode_path("/orders/.%2E"), Err(PathDecodeError::Traversal));
+        assert_eq!(decode_path("/orders/%2e"), Err(PathDecodeError::Traversal));
+        assert_eq!(decode_path("/orders/..1"), Ok("/orders/..1".to_string()));
+        assert_eq!(decode_path("/orders/%zz"), Err(PathDecodeError::Malformed));
+        assert_eq!(decode_path("/orders/%2"), Err(PathDecodeError::Malformed));
+        assert_eq!(decode_path("/orders/%ff"), Err(PathDecodeError::Malformed));
+    }
+
+    #[test]
+    fn test_split_target() {
+        assert_eq!(split_target("/tables?has_orders=true"), ("/tables", "has_orders=true"));
+        assert_eq!(split_target("/orders/1"), ("/orders/1", ""));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("has_orders=true&older_than_seconds=1200&flag");
+
+        assert_eq!(query.len(), 3);
+        assert_eq!(query["has_orders"], "true");
+        assert_eq!(query["older_than_seconds"], "1200");
+        assert_eq!(query["flag"], "");
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn test_header() {
+        let request = "POST /orders HTTP/1.1\r\nHost: x\r\ntransfer-encoding:  chunked\r\n\r\nHost: body";
+
+        assert_eq!(header(request, "Transfer-Encoding"), Some("chunked"));
+        assert_eq!(header(request, "host"), Some("x"));
+        assert_eq!(header(request, "Accept"), None);
+        assert!(is_chunked(request));
+        assert!(!is_chunked("GET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_path_params() {
+        let path = PathParams::parse("/orders/3/items/7");
+
+        assert!(path.matches(&["orders", ":table_id", "items", ":item_id"]));
+        assert!(!path.matches(&["orders", ":table_id", "bill", ":item_id"]));
+        assert!(!path.matches(&["orders", ":table_id"]));
+        assert_eq!(path.param::<u32>(1, "table id"), Ok(3));
+        assert_eq!(path.param::<u32>(3, "item id"), Ok(7));
+        assert_eq!(path.param::<u32>(2, "item id"), Err(PathError::Invalid("item id")));
+
+        // Short paths report the missing parameter instead of panicking
+        let path = PathParams::parse("/orders");
+        assert_eq!(path.param::<u32>(1, "table id"), Err(PathError::Missing("table id")));
+        assert_eq!(String::from(PathError::Missing("table id")), "Missing table id");
+        assert_eq!(PathParams::parse("").param::<u32>(1, "table id"), Err(PathError::Missing("table id")));
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(version("GET /health HTTP/1.0\r\n\r\n"), Some("HTTP/1.0"));
+        assert_eq!(version("GET /health HTTP/1.1"), Some("HTTP/1.1"));
+        assert_eq!(version("GET /health\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let data = b"7\r\n{\"table\r\n8;ext=1\r\n_id\": 1}\r\n0\r\n\r\n";
+        assert_eq!(
+            decode_chunked(data, MAX_BODY_BYTES),
+            Ok(Some(b"{\"table_id\": 1}".to_vec()))
+        );
+
+        // Trailers are skipped
+        let data = b"2\r\nok\r\n0\r\nExpires: never\r\n\r\n";
+        assert_eq!(decode_chunked(data, MAX_BODY_BYTES), Ok(Some(b"ok".to_vec())));
+    }
+
+    #[test]
+    fn test_decode_chunked_incomplete() {
+        assert_eq!(decode_chunked(b"", MAX_BODY_BYTES), Ok(None));
+        assert_eq!(decode_chunked(b"5\r\nhel", MAX_BODY_BYTES), Ok(None));
+        assert_eq!(decode_chunked(b"5\r\nhello\r\n0\r\n", MAX_BODY_BYTES), Ok(None));
+    }
+
+    #[test]
+    fn test_decode_chunked_errors() {
+        assert_eq!(
+            decode_chunked(b"zz\r\nhello\r\n0\r\n\r\n", MAX_BODY_BYTES),
+            Err(ChunkedError::Malformed)
+        );
+        assert_eq!(
+            decode_chunked(b"2\r\nhello\r\n0\r\n\r\n", MAX_BODY_BYTES),
+            Err(ChunkedError::Malformed)
+        );
+        assert_eq!(
+            decode_chunked(b"4\r\nabcd\r\n4\r\nefgh\r\n0\r\n\r\n", 6),
+            Err(ChunkedError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_raw_request_parse() {
+        let request = "POST /orders HTTP/1.1\r\nX-Note: {\"table_id\": 9}\r\nHost: a:80\r\n\r\n{\"table_id\":1}";
+        let parsed = RawRequest::parse(request).unwrap();
+
+        assert_eq!(parsed.method, "POST");
+        assert_eq!(parsed.target, "/orders");
+        assert_eq!(parsed.header("x-note"), Some("{\"table_id\": 9}"));
+        assert_eq!(parsed.header("Host"), Some("a:80"));
+        assert_eq!(parsed.body, "{\"table_id\":1}");
+
+        // A body containing a blank line is kept whole
+        let parsed = RawRequest::parse("PUT /tables/1 HTTP/1.1\r\n\r\na\r\n\r\nb").unwrap();
+        assert!(parsed.headers.is_empty());
+        assert_eq!(parsed.body, "a\r\n\r\nb");
+
+        assert_eq!(RawRequest::parse("GET /tables HTTP/1.1").unwrap().body, "");
+        assert!(RawRequest::parse("GET\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_response_to_bytes() {
+        assert_eq!(Response::new(204).to_bytes(), b"HTTP/1.1 204 No Content\r\n\r\n");
+        assert_eq!(
+            Response::new(200).with_body("{}").to_bytes(),
+            b"HTTP/1.1 200 OK\r\n\r\n{}"
+        );
+        assert_eq!(
+            Response::new(429)
+                .with_header("Retry-After", 30)
+                .with_body("slow down")
+                .to_bytes(),
+            b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 30\r\n\r\nslow down"
+        );
+        assert_eq!(
+            Response::new(404)
+                .with_header("Content-Type", "text/plain")
+                .with_header("X-Trace", "a")
+                .with_header("content-type", "text/html")
+                .with_body("Not Found")
+                .to_bytes(),
+            b"HTTP/1.1 404 Not Found\r\nX-Trace: a\r\ncontent-type: text/html\r\n\r\nNot Found"
+        );
+        assert_eq!(
+            Response::new(200).with_version("HTTP/1.0").with_body_bytes(vec![0x1f, 0x8b]).to_bytes(),
+            b"HTTP/1.0 200 OK\r\n\r\n\x1f\x8b"
+        );
+        assert!(Response::new(599).to_string().starts_with("HTTP/1.1 599 Unknown\r\n"));
+    }
+
+    #[test]
+    fn test_response_parses_back() {
+        let response = Response::new(503)
+            .with_header("Content-Type", "application/json")
+            .with_body("{\"ok\":false}")
+            .to_string();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        assert_eq!(lines.next(), Some("HTTP/1.1 503 Service Unavailable"));
+        assert_eq!(lines.next(), Some("Content-Type: application/json"));
+        assert_eq!(lines.next(), None);
+        assert_eq!(header(&response, "content-type"), Some("application/json"));
+        assert_eq!(
+            Response::new(503).with_header("Content-Type", "text/plain").header("content-type"),
+            Some("text/plain")
+        );
+        assert_eq!(body, "{\"ok\":false}");
+    }
+}
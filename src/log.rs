@@ -0,0 +1,270 @@
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default number of body bytes logged before truncating.
+pub const DEFAULT_BODY_LOG_LIMIT: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    /// Parses a level name such as `debug` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Level> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+/// How each log line is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `[INFO] message`, for people reading the terminal.
+    Pretty,
+    /// One JSON object per line with `timestamp_ms`, `level` and `message`,
+    /// for log aggregators.
+    Json,
+}
+
+impl Format {
+    /// Parses a format name, `pretty` or `json` (case-insensitive).
+    pub fn parse(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "pretty" => Some(Format::Pretty),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Writes leveled log lines to stderr, or to an in-memory buffer in tests.
+#[derive(Clone)]
+pub struct Logger {
+    level: Level,
+    format: Format,
+    log_bodies: bool,
+    body_limit: usize,
+    /// Client address every line is tagged with, for loggers scoped to one connection.
+    peer: Option<SocketAddr>,
+    captured: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl Logger {
+    /// Creates a logger that drops events less severe than `level`.
+    pub fn new(level: Level) -> Logger {
+        Logger {
+            level,
+            format: Format::Pretty,
+            log_bodies: false,
+            body_limit: DEFAULT_BODY_LOG_LIMIT,
+            peer: None,
+            captured: None,
+        }
+    }
+
+    /// Creates a logger that keeps its lines in memory instead of printing them.
+    #[cfg(test)]
+    pub fn capturing(level: Level) -> (Logger, Arc<Mutex<Vec<String>>>) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut logger = Logger::new(level);
+        logger.captured = Some(Arc::clone(&lines));
+        (logger, lines)
+    }
+
+    pub fn with_format(mut self, format: Format) -> Logger {
+        self.format = format;
+        self
+    }
+
+    /// Tags every line with the client's address, if it has one.
+    pub fn with_peer(mut self, peer: Option<SocketAddr>) -> Logger {
+        self.peer = peer;
+        self
+    }
+
+    /// Enables logging request and response bodies at debug level,
+    /// truncated to `limit` bytes.
+    pub fn with_body_logging(mut self, enabled: bool, limit: usize) -> Logger {
+        self.log_bodies = enabled;
+        self.body_limit = limit;
+        self
+    }
+
+    pub fn log(&self, level: Level, message: &str) {
+        if level > self.level {
+            return;
+        }
+
+        let line = match self.format {
+            Format::Pretty => match self.peer {
+                Some(peer) => format!("[{}] [{}] {}", level.as_str(), peer, message),
+                None => format!("[{}] {}", level.as_str(), message),
+            },
+            Format::Json => {
+                let timestamp_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+                let mut line = json!({
+                    "timestamp_ms": timestamp_ms,
+                    "level": level.as_str(),
+                    "message": message
+                });
+                if let Some(peer) = self.peer {
+                    line["peer"] = json!(peer.to_string());
+                }
+                line.to_string()
+            }
+        };
+        match &self.captured {
+            Some(lines) => lines.lock().unwrap().push(line),
+            None => eprintln!("{}", line),
+        }
+    }
+
+    pub fn error(&self, message: &str) {
+        self.log(Level::Error, message);
+    }
+
+    pub fn debug(&self, message: &str) {
+        self.log(Level::Debug, message);
+    }
+
+    pub fn info(&self, message: &str) {
+        self.log(Level::Info, message);
+    }
+
+    pub fn warn(&self, message: &str) {
+        self.log(Level::Warn, message);
+    }
+
+    /// Logs a request or response body at debug level if body logging is enabled.
+    pub fn log_body(&self, label: &str, body: &str) {
+        if !self.log_bodies || self.level < Level::Debug {
+            return;
+        }
+
+        if body.len() <= self.body_limit {
+            self.debug(&format!("{} body: {}", label, body));
+        } else {
+            let mut end = self.body_limit;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.debug(&format!(
+                "{} body: {}... (truncated, {} bytes total)",
+                label,
+                &body[..end],
+                body.len()
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_filter() {
+        let (logger, lines) = Logger::capturing(Level::Info);
+
+        logger.debug("hidden");
+        logger.info("shown");
+        logger.warn("also shown");
+
+        assert_eq!(*lines.lock().unwrap(), vec!["[INFO] shown", "[WARN] also shown"]);
+    }
+
+    #[test]
+    fn test_peer_tag() {
+        let (logger, lines) = Logger::capturing(Level::Info);
+        let peer: SocketAddr = "10.0.0.7:5123".parse().unwrap();
+
+        logger.clone().with_peer(Some(peer)).warn("slow");
+        logger.clone().with_format(Format::Json).with_peer(Some(peer)).info("ok");
+        logger.info("untagged");
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines[0], "[WARN] [10.0.0.7:5123] slow");
+        let json: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(json["peer"], "10.0.0.7:5123");
+        assert_eq!(lines[2], "[INFO] untagged");
+    }
+
+    #[test]
+    fn test_json_format() {
+        let (logger, lines) = Logger::capturing(Level::Info);
+        let logger = logger.with_format(Format::Json);
+
+        logger.info("table 3 \"cleared\"");
+        logger.warn("slow");
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(first["level"], "INFO");
+        assert_eq!(first["message"], "table 3 \"cleared\"");
+        assert!(first["timestamp_ms"].as_u64().is_some_and(|ms| ms > 0));
+        let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(second["level"], "WARN");
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(Format::parse("JSON"), Some(Format::Json));
+        assert_eq!(Format::parse("pretty"), Some(Format::Pretty));
+        assert_eq!(Format::parse("xml"), None);
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(Level::parse("DEBUG"), Some(Level::Debug));
+        assert_eq!(Level::parse("warn"), Some(Level::Warn));
+        assert_eq!(Level::parse("loud"), None);
+    }
+
+    #[test]
+    fn test_log_body_truncates() {
+        let (logger, lines) = Logger::capturing(Level::Debug);
+        let logger = logger.with_body_logging(true, 8);
+
+        logger.log_body("request", "{\"a\":1}");
+        logger.log_body("response", "{\"message\":\"Success!\"}");
+
+        assert_eq!(
+            *lines.lock().unwrap(),
+            vec![
+                "[DEBUG] request body: {\"a\":1}",
+                "[DEBUG] response body: {\"messag... (truncated, 22 bytes total)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_body_disabled() {
+        let (logger, lines) = Logger::capturing(Level::Debug);
+
+        logger.log_body("request", "{\"a\":1}");
+        assert!(lines.lock().unwrap().is_empty());
+    }
+}
@@ -1,51 +1,429 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::AsyncReadExt;
-use tokio::io::AsyncWriteExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio::signal;
+use tokio::task::JoinSet;
 
+mod auth;
+mod bill;
+mod clock;
+mod compress;
+mod confirm;
+mod config;
+mod connections;
+mod events;
 mod handlers;
+mod http;
+mod listener;
+mod log;
+mod menu;
+mod metrics;
 mod order;
 mod restaurant;
 mod table;
+mod throttle;
+mod webhook;
 
+use auth::Principal;
+use config::Config;
+use connections::IpConnectionLimiter;
+use http::{Method, RawRequest, Response};
+use listener::Listener;
 use restaurant::Restaurant;
 use crate::handlers::{
     handle_post_order, 
     handle_get_order, 
-    handle_delete_order
+    handle_get_orders_ndjson,
+    handle_delete_order,
+    handle_dev_reset,
+    handle_get_diagnostics,
+    handle_get_events,
+    handle_get_health,
+    handle_get_kitchen_queue,
+    handle_get_tables,
+    handle_get_wait_stats,
+    handle_merge_tables,
+    handle_reset_table,
+    handle_put_occupancy,
+    handle_serve_all,
+    handle_split_bill,
+    handle_fire_table,
+    handle_resend_orders,
+    handle_void_order,
+    handle_bump_order,
+    handle_get_kitchen_overview,
+    handle_get_available_tables,
+    handle_get_confirmation,
+    handle_get_ready,
+    handle_swap_item,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AddOrderRequest {
     table_id: u32,
-    items: Vec<u32>
+    /// Item ids, accepted as JSON numbers or numeric strings such as `"101"`.
+    #[serde(deserialize_with = "deserialize_item_ids")]
+    items: Vec<u32>,
+    /// Add-ons for some of the items, keyed by item id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    modifiers: BTreeMap<u32, Vec<order::Modifier>>,
 }
 
+/// An item id sent either as a number or as a string of digits.
+struct ItemId(u32);
+
+impl<'de> Deserialize<'de> for ItemId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<ItemId, D::Error> {
+        struct ItemIdVisitor;
+
+        impl serde::de::Visitor<'_> for ItemIdVisitor {
+            type Value = ItemId;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an item id as a number or a numeric string")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<ItemId, E> {
+                u32::try_from(value)
+                    .map(ItemId)
+                    .map_err(|_| E::custom(format!("item id {} is out of range", value)))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<ItemId, E> {
+                value
+                    .parse()
+                    .map(ItemId)
+                    .map_err(|_| E::custom(format!("item id {:?} is not a number", value)))
+            }
+        }
+
+        deserializer.deserialize_any(ItemIdVisitor)
+    }
+}
+
+fn deserialize_item_ids<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<u32>, D::Error> {
+    let ids = Vec::<ItemId>::deserialize(deserializer)?;
+    Ok(ids.into_iter().map(|ItemId(id)| id).collect())
+}
+
+/// How long a kept-alive connection may sit idle before the next request.
+const KEEPALIVE_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Handles incoming connections.
 ///
 /// Reads data from the stream, processes the request, and sends a response back.
 /// If the request is invalid or an error occurs, it returns an appropriate error response.
-async fn handle_connection(mut stream: TcpStream, restaurant: Restaurant) {
-    let mut buffer = [0; 1024];
-    if let Ok(n) = stream.read(&mut buffer).await {
-        if n == 0 {
+///
+/// The connection is closed after the response unless the client sent
+/// `Connection: keep-alive`. Kept-alive responses carry a `Content-Length`,
+/// and after `keepalive_max` requests the server answers `Connection: close`
+/// and closes the connection.
+///
+/// Each request gets an access log line with its status. That line and every
+/// other line logged while handling the connection carry the `peer` address.
+async fn handle_connection<S>(mut stream: S, restaurant: Restaurant, peer: Option<SocketAddr>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let logger = restaurant.logger().clone().with_peer(peer);
+    let restaurant = restaurant.with_logger(logger);
+    let _connection = restaurant.metrics().track_connection();
+    // The first request's headers are timed from accept, so a client that
+    // connects and sends nothing cannot hold the connection open either
+    let first_deadline = restaurant.header_timeout().map(|timeout| tokio::time::Instant::now() + timeout);
+    let mut served = 0;
+    loop {
+        let mut buffer = [0; 1024];
+        let read = match (served, first_deadline) {
+            (0, Some(deadline)) => tokio::time::timeout_at(deadline, stream.read(&mut buffer)).await,
+            (0, None) => Ok(stream.read(&mut buffer).await),
+            _ => tokio::time::timeout(KEEPALIVE_IDLE_TIMEOUT, stream.read(&mut buffer)).await,
+        };
+        let request = match read {
+            Ok(Ok(n)) if n > 0 => {
+                let deadline = match served {
+                    0 => first_deadline,
+                    _ => restaurant.header_timeout().map(|timeout| tokio::time::Instant::now() + timeout),
+                };
+                read_request(&mut stream, buffer[..n].to_vec(), deadline).await
+            }
+            Err(_) if served == 0 => Err(error_response(408, "Request headers took too long")),
+            _ => return,
+        };
+
+        served += 1;
+        // HTTP/1.0 clients get HTTP/1.0 responses; anything else is answered as HTTP/1.1
+        let version = match request.as_deref().ok().and_then(http::version) {
+            Some("HTTP/1.0") => "HTTP/1.0",
+            _ => "HTTP/1.1",
+        };
+        let keep_alive = request.as_ref().is_ok_and(|request| {
+            http::header(request, "Connection").is_some_and(|value| value.eq_ignore_ascii_case("keep-alive"))
+        });
+        let connection = match keep_alive {
+            false => None,
+            true if served >= restaurant.keepalive_max() => Some("close"),
+            true => Some("keep-alive"),
+        };
+
+        restaurant.metrics().record_request();
+        let logger = restaurant.logger().clone();
+        let min_compress_bytes = restaurant.min_compress_bytes();
+        let server = restaurant.server_header().then_some(SERVER);
+        let mut encoding = None;
+        let request_line = match &request {
+            Ok(request) => request.lines().next().unwrap_or_default().to_string(),
+            Err(_) => "-".to_string(),
+        };
+        let response = match request {
+            Ok(request) => {
+                logger.log_body("request", body_of(&request));
+                encoding = http::header(&request, "Accept-Encoding")
+                    .and_then(|accept| compress::negotiate(accept, restaurant.compression()));
+                let restaurant = restaurant.clone();
+                let handling = async move {
+                    match handle_request(&request, restaurant).await {
+                        Ok(response) => response,
+                        Err(err) => bad_request(err),
+                    }
+                };
+                run_isolated(handling, &logger).await
+            }
+            Err(response) => response,
+        };
+        logger.log_body("response", &response.body_text());
+        let access = format!("\"{}\" {}", request_line, response.status);
+        if response.status >= 500 {
+            logger.warn(&access);
+        } else {
+            logger.info(&access);
+        }
+        let response = encode_response(response, version, server, connection, encoding, min_compress_bytes);
+        // Handling has finished by now, so the wait holds no table locks
+        if let Some(delay) = restaurant.artificial_delay() {
+            tokio::time::sleep(delay).await;
+        }
+
+        if let Err(e) = stream.write_all(&response.to_bytes()).await {
+            logger.warn(&format!("Error writing to stream: {}", e));
+            return;
+        }
+        if connection != Some("keep-alive") {
             return;
         }
+    }
+}
+
+/// Builds the `500` response sent when handling a request fails unexpectedly.
+pub(crate) fn internal_server_error() -> Response {
+    error_response(500, "Internal server error")
+}
+
+/// Runs request handling in its own task, so a panicking handler produces a
+/// `500` response instead of silently dropping the connection.
+async fn run_isolated<F>(handling: F, logger: &log::Logger) -> Response
+where
+    F: Future<Output = Response> + Send + 'static,
+{
+    match tokio::spawn(handling).await {
+        Ok(response) => response,
+        Err(e) => {
+            let reason = match e.try_into_panic() {
+                Ok(panic) => panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string()),
+                Err(e) => e.to_string(),
+            };
+            logger.error(&format!("Request handler failed: {}", reason));
+            internal_server_error()
+        }
+    }
+}
+
+/// Content type of responses whose handler does not declare one.
+const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
+/// Value of the `Server` header sent with every response unless suppressed.
+const SERVER: &str = concat!("resto_api/", env!("CARGO_PKG_VERSION"));
+
+/// Answers with `version` in the status line, labels a body without a
+/// declared `Content-Type` as `DEFAULT_CONTENT_TYPE`, adds the `Server` header
+/// when given and compresses the response body with `encoding` unless it is
+/// smaller than `min_bytes`. With a `connection`
+/// value the response also gets `Connection` and `Content-Length` headers, so
+/// a kept-alive client can find where the body ends.
+fn encode_response(
+    mut response: Response,
+    version: &'static str,
+    server: Option<&str>,
+    connection: Option<&str>,
+    encoding: Option<compress::Encoding>,
+    min_bytes: usize,
+) -> Response {
+    response = response.with_version(version);
+    if !response.body.is_empty() && response.header("Content-Type").is_none() {
+        response = response.with_header("Content-Type", DEFAULT_CONTENT_TYPE);
+    }
+    if let Some(server) = server {
+        response = response.with_header("Server", server);
+    }
+    if let Some(encoding) = encoding.filter(|_| response.body.len() >= min_bytes) {
+        let body = encoding.encode(&response.body);
+        response = response
+            .with_header("Content-Encoding", encoding.as_str())
+            .with_header("Vary", "Accept-Encoding")
+            .with_body_bytes(body);
+    }
+    if let Some(connection) = connection {
+        let length = response.body.len();
+        response = response.with_header("Connection", connection).with_header("Content-Length", length);
+    }
+    response
+}
+
+/// Builds an error response with the usual JSON body.
+fn error_response(status: u16, message: &str) -> Response {
+    Response::new(status).with_body(serde_json::json!({"success": false, "message": message}))
+}
+
+/// Builds the `400` response for a handler error. Handlers report some
+/// errors as plain messages such as `Invalid table id`; those are wrapped in
+/// the usual `{"success":false,"message":..}` so every body is JSON.
+fn bad_request(err: String) -> Response {
+    let is_json = serde_json::from_str::<serde_json::Value>(&err).is_ok_and(|value| value.is_object());
+    let body = if is_json {
+        err
+    } else {
+        serde_json::json!({"success": false, "message": err}).to_string()
+    };
 
-        let request = String::from_utf8_lossy(&buffer[..n]);
-        let response = match handle_request(request.as_ref(), restaurant).await {
-            Ok(response) => response,
-            Err(err) => format!("HTTP/1.1 400 Bad Request\r\n\r\n{}", err),
+    Response::new(400).with_body(body)
+}
+
+/// Returns the body of an HTTP message, or an empty string if it has none.
+fn body_of(message: &str) -> &str {
+    message.split_once("\r\n\r\n").map_or("", |(_, body)| body)
+}
+
+/// Reads the rest of a request whose first bytes are `data`.
+///
+/// Keeps reading until the headers and the body they declare, by
+/// `Content-Length` or chunked encoding, have arrived, so a request split
+/// across several TCP segments is handled whole. If the peer stops sending
+/// early, what was received is handled as the request.
+///
+/// Headers still incomplete at `deadline` get `408 Request Timeout`.
+async fn read_request<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    mut data: Vec<u8>,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<String, Response> {
+    let mut buffer = [0; 1024];
+    let head_end = loop {
+        if let Some(head_end) = http::find_bytes(&data, b"\r\n\r\n") {
+            break head_end;
+        }
+        if data.len() > http::MAX_HEAD_BYTES {
+            return Err(error_response(431, "Request headers too large"));
+        }
+        let read = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, stream.read(&mut buffer)).await {
+                Ok(read) => read,
+                Err(_) => return Err(error_response(408, "Request headers took too long")),
+            },
+            None => stream.read(&mut buffer).await,
         };
+        match read {
+            Ok(n) if n > 0 => data.extend_from_slice(&buffer[..n]),
+            _ => return Ok(String::from_utf8_lossy(&data).into_owned()),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    if http::is_chunked(&head) {
+        return read_chunked_request(stream, data, head_end + 4).await;
+    }
+    let body_len = http::content_length(&head).unwrap_or(0);
+    if body_len > http::MAX_BODY_BYTES {
+        return Err(error_response(413, "Request body too large"));
+    }
+    while data.len() < head_end + 4 + body_len {
+        match stream.read(&mut buffer).await {
+            Ok(n) if n > 0 => data.extend_from_slice(&buffer[..n]),
+            _ => break,
+        }
+    }
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Reads the remaining chunks of a `Transfer-Encoding: chunked` request.
+///
+/// Parameters:
+/// - `data`: The bytes received so far.
+/// - `body_start`: The offset in `data` where the chunked body begins.
+///
+/// Returns:
+/// - `Ok(request)`: The request head followed by the decoded body.
+/// - `Err(response)`: An error response if the body is malformed or too large.
+async fn read_chunked_request<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    mut data: Vec<u8>,
+    body_start: usize,
+) -> Result<String, Response> {
+    let mut buffer = [0; 1024];
+    loop {
+        match http::decode_chunked(&data[body_start..], http::MAX_BODY_BYTES) {
+            Ok(Some(body)) => {
+                let head = String::from_utf8_lossy(&data[..body_start]);
+                return Ok(format!("{}{}", head, String::from_utf8_lossy(&body)));
+            }
+            Ok(None) => {}
+            Err(http::ChunkedError::Malformed) => {
+                return Err(error_response(400, "Malformed chunked body"));
+            }
+            Err(http::ChunkedError::TooLarge) => {
+                return Err(error_response(413, "Request body too large"));
+            }
+        }
+
+        // Chunk headers add some overhead on top of the decoded size
+        if data.len() - body_start > 2 * http::MAX_BODY_BYTES {
+            return Err(error_response(413, "Request body too large"));
+        }
 
-        if let Err(e) = stream.write_all(response.as_bytes()).await {
-            eprintln!("Error writing to stream: {}", e);
+        match stream.read(&mut buffer).await {
+            Ok(n) if n > 0 => data.extend_from_slice(&buffer[..n]),
+            _ => return Err(error_response(400, "Incomplete chunked body")),
         }
     }
 }
 
+/// Returns the query parameters the endpoint for `method` and `path` reads.
+fn known_query_params(method: Method, path: &str) -> &'static [&'static str] {
+    match (method, path) {
+        (Method::Post, "/orders") => &["if_empty", "allow_substitution"],
+        (Method::Get, "/health") => &["verbose"],
+        (Method::Get, "/kitchen/queue") => &["station"],
+        (Method::Get, "/events") => &["since", "since_seq", "limit"],
+        (Method::Get, "/tables") => &["has_orders", "older_than_seconds"],
+        (Method::Get, path) if path.starts_with("/orders/confirm/") => &[],
+        (Method::Get, path) if path.starts_with("/orders/") => &["wait", "status", "sort", "group_by"],
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/void") => &["reason"],
+        (Method::Delete, path) if path == "/orders" || path.starts_with("/orders/") => &["reason"],
+        _ => &[],
+    }
+}
+
 /// Parses the HTTP request, extracts the method and path, and handles the request.
 ///
 /// Parameters:
@@ -55,74 +433,468 @@ async fn handle_connection(mut stream: TcpStream, restaurant: Restaurant) {
 /// Returns:
 /// - `Ok(response)`: The HTTP response if successful.
 /// - `Err(err)`: An error response if the request is invalid or an error occurs.
-async fn handle_request(request: &str, restaurant: Restaurant) -> Result<String, String> {
-    let lines: Vec<&str> = request.lines().collect();
-    let method_path: Vec<&str> = lines[0].split_whitespace().collect();
+async fn handle_request(request: &str, restaurant: Restaurant) -> Result<Response, String> {
+    let request = RawRequest::parse(request).ok_or("Invalid request")?;
 
-    if method_path.len() != 3 {
-        return Err("Invalid request".to_string());
+    let authenticator = restaurant.authenticator();
+    match authenticator.authenticate(&request.headers) {
+        Ok(Principal::Anonymous) => {}
+        Ok(principal) => restaurant.logger().debug(&format!("Authenticated as {}", principal)),
+        Err(err) => {
+            return Ok(Response::new(401)
+                .with_header("WWW-Authenticate", authenticator.challenge())
+                .with_body(serde_json::json!({"success": false, "message": err.to_string()})));
+        }
     }
 
-    let method = method_path[0];
-    let path = method_path[1];
+    let method = match Method::parse(request.method) {
+        Some(method) => method,
+        None => {
+            return Ok(Response::new(501)
+                .with_body("{\"message\":\"Method not implemented\",\"success\":false}"));
+        }
+    };
+    let target = request.target;
+
+    // Reject pathological paths before any handler splits them
+    if target.len() > restaurant.max_path_length() {
+        return Ok(Response::new(414)
+            .with_body("{\"message\":\"URI too long\",\"success\":false}"));
+    }
+    let (path, query) = http::split_target(target);
+    // Route on the decoded path, so an encoded segment cannot slip past a route
+    let path = match http::decode_path(path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(serde_json::json!({"success": false, "message": err.to_string()}).to_string());
+        }
+    };
+    if path.len() > restaurant.max_decoded_path_length() {
+        return Ok(Response::new(414)
+            .with_body("{\"message\":\"URI too long\",\"success\":false}"));
+    }
+    let path = path.as_str();
+    if path.split('/').skip(1).count() > restaurant.max_path_segments() {
+        return Err("{\"message\":\"Too many path segments\",\"success\":false}".to_string());
+    }
+    let query = http::parse_query(query);
+    if restaurant.strict_query() {
+        let known = known_query_params(method, path);
+        let unknown = query.keys().filter(|name| !known.contains(&name.as_str())).min();
+        if let Some(name) = unknown {
+            return Err(serde_json::json!({
+                "success": false,
+                "message": format!("unknown query param '{}'", name)
+            }).to_string());
+        }
+    }
 
     match (method, path) {
-        ("POST", "/orders") => {
-            let response = match handle_post_order(request, restaurant).await {
+        (Method::Post, "/orders") => {
+            let response = match handle_post_order(&request, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, "/kitchen/serve-all") => {
+            let response = match handle_serve_all(&request, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, "/dev/reset") if restaurant.dev_mode() => {
+            let response = match handle_dev_reset(restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/tables/") && path.ends_with("/reset") => {
+            let response = match handle_reset_table(path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/tables/") && path.ends_with("/merge") => {
+            let response = match handle_merge_tables(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/fire") => {
+            let response = match handle_fire_table(path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/bump") => {
+            let response = match handle_bump_order(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/void") => {
+            let response = match handle_void_order(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/resend") => {
+            let response = match handle_resend_orders(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Post, path) if path.starts_with("/orders/") && path.ends_with("/split") => {
+            let response = match handle_split_bill(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Delete, path) if path == "/orders" || path.starts_with("/orders/") => {
+            let response = match handle_delete_order(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/orders") => {
+            // The prefix is valid, so point at the missing table id rather than 404
+            Err("{\"message\":\"table_id required\",\"success\":false}".to_string())
+        }
+        (Method::Get, path) if path.starts_with("/orders/confirm/") => {
+            let response = match handle_get_confirmation(path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, path) if path.starts_with("/orders/") => {
+            let ndjson = request.header("Accept")
+                .is_some_and(|accept| accept.contains("application/x-ndjson"));
+            let result = if ndjson && path.split('/').count() == 3 {
+                handle_get_orders_ndjson(path, restaurant).await
+            } else {
+                handle_get_order(path, &query, restaurant).await
+            };
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/health") => {
+            let response = match handle_get_health(&query, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/ready") => {
+            let response = match handle_get_ready(restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/kitchen/overview") => {
+            let response = match handle_get_kitchen_overview(restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/kitchen/queue") => {
+            let response = match handle_get_kitchen_queue(&query, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/admin/diagnostics") => {
+            let response = match handle_get_diagnostics(&request, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/events") => {
+            let response = match handle_get_events(&request, &query, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/metrics") => {
+            Ok(Response::new(200)
+                .with_header("Content-Type", metrics::CONTENT_TYPE)
+                .with_body(restaurant.metrics().render()))
+        }
+        (Method::Get, "/tables/available") => {
+            let response = match handle_get_available_tables(restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Get, "/tables") => {
+            let response = match handle_get_tables(&query, restaurant).await {
                 Ok(response) => response,
-                Err(err) => format!("HTTP/1.1 400 Bad Request\r\n\r\n{}", err)
+                Err(err) => bad_request(err)
             };
             Ok(response)
         }
-        ("DELETE", path) if path.starts_with("/orders/") => {
-            let response = match handle_delete_order(path, restaurant).await {
+        (Method::Get, path) if path.starts_with("/tables/") => {
+            let response = match handle_get_wait_stats(path, restaurant).await {
                 Ok(response) => response,
-                Err(err) => format!("HTTP/1.1 400 Bad Request\r\n\r\n{}", err)
+                Err(err) => bad_request(err)
             };
             Ok(response)
         }
-        ("GET", path) if path.starts_with("/orders/") => {
-            let response = match handle_get_order(path, restaurant).await {
+        (Method::Put, path) if path.starts_with("/orders/") => {
+            let response = match handle_swap_item(&request, path, restaurant).await {
                 Ok(response) => response,
-                Err(err) => format!("HTTP/1.1 400 Bad Request\r\n\r\n{}", err)
+                Err(err) => bad_request(err)
+            };
+            Ok(response)
+        }
+        (Method::Put, path) if path.starts_with("/tables/") => {
+            let response = match handle_put_occupancy(&request, path, restaurant).await {
+                Ok(response) => response,
+                Err(err) => bad_request(err)
             };
             Ok(response)
         }
         _ => {
-            let response = "HTTP/1.1 404 Not Found\r\n\r\nNot Found".to_string();
+            let response = Response::new(404)
+                .with_header("Content-Type", "text/plain")
+                .with_body("Not Found");
             Ok(response)
         }
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    let listener = TcpListener::bind(&addr).await.unwrap();
+/// Builds the response sent to a peer that is over its connection cap.
+fn too_many_connections() -> Response {
+    Response::new(503).with_body("{\"message\":\"Too many connections\",\"success\":false}")
+}
+
+/// How often the accept loop checks in for the health check.
+const ACCEPT_LOOP_HEARTBEAT: Duration = Duration::from_secs(1);
+
+/// How often drain progress is logged during shutdown.
+const DRAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tunables for the accept loop.
+struct ServeOptions {
+    /// How long to wait for open connections on shutdown.
+    drain_timeout: Duration,
+    /// Concurrent connections allowed per peer IP; `0` disables the cap.
+    max_connections_per_ip: usize,
+    /// File the shutdown summary is also written to, as JSON.
+    summary_path: Option<PathBuf>,
+}
+
+impl Default for ServeOptions {
+    fn default() -> ServeOptions {
+        ServeOptions {
+            drain_timeout: Duration::from_secs(config::DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            max_connections_per_ip: config::DEFAULT_MAX_CONNECTIONS_PER_IP,
+            summary_path: None,
+        }
+    }
+}
+
+impl ServeOptions {
+    fn from_config(config: &Config) -> ServeOptions {
+        ServeOptions {
+            drain_timeout: config.shutdown_timeout,
+            max_connections_per_ip: config.max_connections_per_ip,
+            summary_path: config.summary_path.clone(),
+        }
+    }
+}
+
+/// Takes a connection slot for the peer.
+///
+/// Returns `None` if the peer is over its cap, and `Some(None)` for peers
+/// without an IP, which are never capped.
+fn admit(
+    limiter: &IpConnectionLimiter,
+    peer: Option<std::net::IpAddr>,
+) -> Option<Option<connections::IpConnectionGuard>> {
+    match peer {
+        Some(ip) => limiter.try_acquire(ip).map(Some),
+        None => Some(None),
+    }
+}
+
+/// Accepts connections until `shutdown` resolves, then drains the open ones.
+///
+/// Peers over their per-IP connection cap get a `503` and are closed right away;
+/// transports without peer IPs, such as Unix sockets, are not capped.
+/// While draining, requests on open connections are still served, except
+/// that orders may be rejected; see `Restaurant::with_reject_orders_while_draining`.
+/// Drain progress is logged every `DRAIN_PROGRESS_INTERVAL`.
+/// Connections still running after the drain timeout are force-closed, and
+/// a summary of the process's totals is logged once draining is done.
+///
+/// Returns:
+/// - The number of connections that were abandoned when the timeout elapsed.
+async fn serve<L, F>(
+    listener: L,
+    restaurant: Restaurant,
+    shutdown: F,
+    options: ServeOptions,
+) -> usize
+where
+    L: Listener,
+    F: Future<Output = ()>,
+{
+    let drain_timeout = options.drain_timeout;
+    let limiter = IpConnectionLimiter::new(options.max_connections_per_ip);
+    let mut connections = JoinSet::new();
+    let mut heartbeat = tokio::time::interval(ACCEPT_LOOP_HEARTBEAT);
+    restaurant.metrics().record_accept_loop(restaurant.now_ms());
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((mut stream, peer)) => match admit(&limiter, peer.map(|peer| peer.ip())) {
+                    Some(guard) => {
+                        // Spawning a new asynchronous task for each incoming connection
+                        let restaurant = restaurant.clone();
+                        connections.spawn(async move {
+                            handle_connection(stream, restaurant, peer).await;
+                            drop(guard);
+                        });
+                    }
+                    None => {
+                        // Only peers with an address are ever capped
+                        let from = peer.map_or_else(|| "unknown peer".to_string(), |peer| peer.to_string());
+                        restaurant.logger().warn(&format!(
+                            "Rejecting connection from {}: too many connections",
+                            from
+                        ));
+                        tokio::spawn(async move {
+                            let _ = stream.write_all(&too_many_connections().to_bytes()).await;
+                        });
+                    }
+                },
+                Err(e) => restaurant.logger().warn(&format!("Error accepting connection: {}", e)),
+            },
+            // Lets the verbose health check tell a running loop from a wedged one
+            _ = heartbeat.tick() => restaurant.metrics().record_accept_loop(restaurant.now_ms()),
+            // Reap finished connections so the set only tracks live ones
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = &mut shutdown => break,
+        }
+    }
+
+    // Stop accepting new connections while draining
+    drop(listener);
+    restaurant.start_draining();
+    let logger = restaurant.logger();
+    logger.info(&format!("Draining {} open connection(s).", connections.len()));
+
+    let drain = async {
+        let mut progress = tokio::time::interval(DRAIN_PROGRESS_INTERVAL);
+        progress.tick().await;
+        loop {
+            tokio::select! {
+                joined = connections.join_next() => if joined.is_none() {
+                    break;
+                },
+                _ = progress.tick() => logger.info(&format!(
+                    "Draining: {} connection(s) left after {:.1}s.",
+                    restaurant.metrics().active_connections(),
+                    restaurant.metrics().drain_elapsed().unwrap_or_default().as_secs_f64()
+                )),
+            }
+        }
+    };
+    let abandoned = match tokio::time::timeout(drain_timeout, drain).await {
+        Ok(()) => 0,
+        Err(_) => {
+            let abandoned = connections.len();
+            logger.warn(&format!(
+                "Drain timeout of {:?} elapsed. Force-closing {} connection(s).",
+                drain_timeout, abandoned
+            ));
+            connections.shutdown().await;
+            abandoned
+        }
+    };
+
+    write_summary(&restaurant, options.summary_path.as_deref());
+    abandoned
+}
+
+/// Logs the shutdown summary and, if `path` is set, writes it there as JSON.
+fn write_summary(restaurant: &Restaurant, path: Option<&Path>) {
+    let summary = serde_json::to_string(&restaurant.metrics().summary()).unwrap();
+    let logger = restaurant.logger();
+    logger.info(&format!("Shutdown summary: {}", summary));
 
-    let restaurant = Restaurant::new(150);
+    if let Some(path) = path {
+        if let Err(e) = std::fs::write(path, &summary) {
+            logger.warn(&format!("Failed to write summary to {}: {}", path.display(), e));
+        }
+    }
+}
 
-    println!("Server listening on: {}", addr);
+#[tokio::main]
+async fn main() {
+    let config = Config::from_env();
+    let restaurant = config.restaurant().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let options = ServeOptions::from_config(&config);
 
-    // Spawn a task to handle signals
-    tokio::spawn(async move {
+    let shutdown = async {
         signal::ctrl_c().await.expect("Failed to listen for Ctrl+C");
         println!("Ctrl+C received. Shutting down gracefully.");
+    };
 
-        // Gracefully shutdown the server
-        std::process::exit(0);
-    });
+    #[cfg(unix)]
+    if let Some(path) = &config.uds_path {
+        // A socket file left over from an unclean exit would make bind fail
+        let _ = std::fs::remove_file(path);
+        let listener = tokio::net::UnixListener::bind(path).unwrap_or_else(|e| {
+            eprintln!("Failed to bind {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        println!("Server listening on: {}", path.display());
 
-    while let Ok((stream, _)) = listener.accept().await {
-        let restaurant = restaurant.clone();
-        // Spawning a new asynchronous task for each incoming connection
-        tokio::spawn(handle_connection(stream, restaurant));
+        serve(listener, restaurant, shutdown, options).await;
+        let _ = std::fs::remove_file(path);
+        return;
     }
+
+    let listener = TcpListener::bind(&config.bind_addr).await.unwrap_or_else(|e| {
+        eprintln!("Failed to bind {}: {}", config.bind_addr, e);
+        std::process::exit(1);
+    });
+    println!("Server listening on: {}", config.bind_addr);
+
+    serve(listener, restaurant, shutdown, options).await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::net::TcpStream;
     
     #[tokio::test]
     async fn test_valid_post_request() {
@@ -131,8 +903,8 @@ mod tests {
         let result = handle_request(request, restaurant).await;
         assert!(result.is_ok());
         assert_eq!(
-            result.unwrap(), 
-            "HTTP/1.1 200 OK\r\n\r\n{\"data\":\"{\\\"table_id\\\":6,\\\"items\\\":[101,102]}\",\"message\":\"Success!\",\"success\":true}"
+            result.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"confirmation\":\"0001\",\"created\":true,\"data\":\"{\\\"table_id\\\":6,\\\"items\\\":[101,102]}\",\"message\":\"Success!\",\"success\":true}"
         );
     }
 
@@ -157,12 +929,12 @@ mod tests {
         let request2 = "DELETE /orders/15/16 HTTP/1.1\r\n\r\n";
         let result2 = handle_request(request2, restaurant2).await;
         assert!(result2.is_ok());
-        assert_eq!(result2.unwrap(), "HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Removed 16 from table 15\",\"success\":true}");
+        assert_eq!(result2.unwrap().to_string(), "HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Removed 16 from table 15\",\"success\":true}");
 
         let request3 = "DELETE /orders/10/16 HTTP/1.1\r\n\r\n";
         let result3 = handle_request(request3, restaurant3).await;
         assert!(result3.is_ok());
-        assert_eq!(result3.unwrap(), "HTTP/1.1 400 Bad Request\r\n\r\n{\"message\":\"Order not found\",\"success\":false}");
+        assert_eq!(result3.unwrap().to_string(), "HTTP/1.1 404 Not Found\r\n\r\n{\"message\":\"Order not found\",\"success\":false}");
     }
 
     #[tokio::test]
@@ -178,7 +950,7 @@ mod tests {
         let request2 = "GET /orders/15 HTTP/1.1\r\n\r\n";
         let result2 = handle_request(request2, restaurant2).await;
         assert!(result2.is_ok());
-        let response = result2.unwrap();
+        let response = result2.unwrap().to_string();
         assert!(response.contains("\\\"item_id\\\":16,\\\"table_id\\\":15"));
         assert!(response.contains("\\\"item_id\\\":102,\\\"table_id\\\":15"));
 
@@ -186,17 +958,890 @@ mod tests {
         let request3 = "GET /orders/15/items/16 HTTP/1.1\r\n\r\n";
         let result3 = handle_request(request3, restaurant3).await;
         assert!(result3.is_ok());
-        let response2 = result3.unwrap();
+        let response2 = result3.unwrap().to_string();
         assert!(response2.contains("\\\"item_id\\\":16,\\\"table_id\\\":15"));
         assert!(!response2.contains("\\\"item_id\\\":102,\\\"table_id\\\":15"));
     }
 
+    #[tokio::test]
+    async fn test_unknown_table_is_404_for_every_verb() {
+        let restaurant = Restaurant::new(12);
+        let expected = "HTTP/1.1 404 Not Found\r\n\r\n{\"message\":\"table 12 does not exist\",\"success\":false}";
+
+        for request in [
+            "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 12, \"items\": [1]}",
+            "GET /orders/12 HTTP/1.1\r\n\r\n",
+            "GET /orders/12/items/1 HTTP/1.1\r\n\r\n",
+            "GET /orders/12 HTTP/1.1\r\nAccept: application/x-ndjson\r\n\r\n",
+            "DELETE /orders/12 HTTP/1.1\r\n\r\n",
+            "DELETE /orders/12/1 HTTP/1.1\r\n\r\n",
+        ] {
+            let result = handle_request(request, restaurant.clone()).await;
+            assert_eq!(result.unwrap().to_string(), expected, "{}", request);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_compression() {
+        let restaurant = Restaurant::new(12).with_compression(vec![compress::Encoding::Deflate, compress::Encoding::Gzip], 64);
+        for item in 0..20 {
+            restaurant.get_table(4).write().add_order(item);
+        }
+
+        let request = b"GET /orders/4 HTTP/1.1\r\nAccept-Encoding: gzip, deflate\r\n\r\n";
+        let response = roundtrip(restaurant.clone(), request).await;
+        assert!(response.contains("\r\nContent-Encoding: deflate\r\n"));
+        assert!(response.contains("\r\nVary: Accept-Encoding\r\n"));
+
+        let request = b"GET /orders/4 HTTP/1.1\r\nAccept-Encoding: gzip, deflate;q=0.5\r\n\r\n";
+        let response = roundtrip(restaurant.clone(), request).await;
+        assert!(response.contains("\r\nContent-Encoding: gzip\r\n"));
+
+        // Small responses are sent as they are
+        let request = b"GET /orders/4/count HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n";
+        let response = roundtrip(restaurant.clone(), request).await;
+        assert_eq!(
+            response,
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nServer: {}\r\n\r\n{{\"count\":20,\"table_id\":4}}",
+                SERVER
+            )
+        );
+
+        let request = b"GET /orders/4 HTTP/1.1\r\n\r\n";
+        let response = roundtrip(restaurant, request).await;
+        assert!(!response.contains("Content-Encoding"));
+    }
+
+    #[tokio::test]
+    async fn test_server_header() {
+        let request = b"GET /health HTTP/1.1\r\n\r\n";
+
+        let response = roundtrip(Restaurant::new(2), request).await;
+        assert_eq!(
+            response,
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nServer: resto_api/{}\r\n\r\n{{\"status\":\"ok\"}}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        let response = roundtrip(Restaurant::new(2).with_server_header(false), request).await;
+        assert_eq!(response, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"status\":\"ok\"}");
+    }
+
+    #[tokio::test]
+    async fn test_http10_status_line() {
+        let restaurant = Restaurant::new(4).with_server_header(false);
+
+        let response = roundtrip(restaurant.clone(), b"GET /health HTTP/1.0\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+
+        // A kept-alive HTTP/1.0 connection keeps answering in HTTP/1.0
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        for _ in 0..2 {
+            client.write_all(b"GET /health HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+            assert!(response.contains("\r\nConnection: keep-alive\r\n"));
+        }
+
+        let response = roundtrip(Restaurant::new(4), b"GET /health HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_content_type() {
+        let restaurant = Restaurant::new(4).with_server_header(false);
+        restaurant.get_table(2).write().add_order(7);
+
+        let response = roundtrip(restaurant.clone(), b"GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\r\n"));
+
+        // Handlers that do not declare a type are labeled as JSON
+        let response = roundtrip(restaurant.clone(), b"GET /orders/2 HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n"));
+
+        // Plain handler errors are wrapped so a 400 labeled as JSON is JSON
+        let response = roundtrip(restaurant.clone(), b"GET /orders/2?status=Lost HTTP/1.1\r\n\r\n").await;
+        assert_eq!(
+            response,
+            "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\n\r\n{\"message\":\"Invalid status\",\"success\":false}"
+        );
+
+        // A response without a body gets no type
+        let response = roundtrip(restaurant, b"DELETE /orders/2/7 HTTP/1.1\r\nPrefer: return=minimal\r\n\r\n").await;
+        assert!(!response.contains("Content-Type"));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_handler_returns_500() {
+        let (logger, lines) = log::Logger::capturing(log::Level::Error);
+
+        let response = run_isolated(async { panic!("table exploded") }, &logger).await;
+        assert_eq!(response, internal_server_error());
+        assert_eq!(*lines.lock().unwrap(), vec!["[ERROR] Request handler failed: table exploded"]);
+
+        let response = run_isolated(async { Response::new(200) }, &logger).await;
+        assert_eq!(response.to_string(), "HTTP/1.1 200 OK\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_path_too_long() {
+        let restaurant = Restaurant::new(12).with_path_limits(32, 8);
+
+        let request = format!("GET /orders/1{} HTTP/1.1\r\n\r\n", "0".repeat(40));
+        let result = handle_request(&request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 414);
+
+        // Query strings count towards the limit too
+        let request = format!("GET /orders/1?x={} HTTP/1.1\r\n\r\n", "a".repeat(40));
+        let result = handle_request(&request, restaurant).await;
+        assert_eq!(result.unwrap().status, 414);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_authentication() {
+        let config = Config::from_vars(|name| (name == "RESTO_API_KEYS").then(|| "k1, k2".to_string()));
+        let restaurant = config.restaurant().unwrap();
+
+        let request = "GET /health HTTP/1.1\r\nX-Api-Key: k2\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let request = "GET /health HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: ApiKey header=\"X-Api-Key\"\r\n\r\n{\"message\":\"missing credentials\",\"success\":false}"
+        );
+
+        // Checked before routing, so unknown paths are not revealed either
+        let request = "GET /nowhere HTTP/1.1\r\nX-Api-Key: k3\r\n\r\n";
+        let result = handle_request(request, restaurant).await;
+        assert!(result.unwrap().body_text().ends_with("{\"message\":\"invalid credentials\",\"success\":false}"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_query_params() {
+        let restaurant = Restaurant::new(12);
+        restaurant.get_table(1).write().add_order(2);
+
+        // Lenient by default: typos are ignored
+        let request = "GET /orders/1?stauts=Pending HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let restaurant = restaurant.with_strict_query(true);
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"unknown query param 'stauts'\",\"success\":false}");
+
+        // Parameters are known per endpoint
+        let request = "GET /orders/1?status=Pending HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+        let request = "GET /kitchen/queue?status=Pending HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant).await;
+        assert!(result.unwrap_err().contains("unknown query param 'status'"));
+    }
+
+    #[tokio::test]
+    async fn test_encoded_paths() {
+        let restaurant = Restaurant::new(12);
+        restaurant.get_table(1).write().add_order(2);
+
+        // Encoded characters are decoded before routing
+        let request = "GET /orders/%31/items/%32 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let request = "GET /orders/1%2Fitems%2F2 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"Encoded slash in path\",\"success\":false}");
+
+        for path in ["/orders/../health", "/orders/%2e%2e/health", "/orders/1/%2E%2e"] {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            let result = handle_request(&request, restaurant.clone()).await;
+            assert_eq!(result.unwrap_err(), "{\"message\":\"Path traversal not allowed\",\"success\":false}");
+        }
+
+        let restaurant = restaurant.with_max_decoded_path_length(9);
+        let request = "GET /orders/%31 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+        let request = "GET /orders/10 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant).await;
+        assert_eq!(result.unwrap().status, 414);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_path_segments() {
+        let restaurant = Restaurant::new(12).with_path_limits(1024, 4);
+
+        let request = "GET /orders/1/items/2/a/b HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"Too many path segments\",\"success\":false}");
+
+        restaurant.get_table(1).write().add_order(2);
+        let request = "GET /orders/1/items/2 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant).await;
+        assert_eq!(result.unwrap().status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_get_orders_ndjson_request() {
+        let restaurant = Restaurant::new(12);
+        let request = "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 4, \"items\": [1, 2]}";
+        handle_request(request, restaurant.clone()).await.unwrap();
+
+        let request = "GET /orders/4 HTTP/1.1\r\nAccept: application/x-ndjson\r\n\r\n";
+        let response = handle_request(request, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("Content-Type: application/x-ndjson"));
+        assert_eq!(response.split_once("\r\n\r\n").unwrap().1.lines().count(), 2);
+
+        // JSON array stays the default
+        let request = "GET /orders/4 HTTP/1.1\r\n\r\n";
+        let response = handle_request(request, restaurant).await.unwrap().to_string();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n\r\n{\"count\":2,\"data\":\"["));
+    }
+
+    #[tokio::test]
+    async fn test_get_orders_without_table_id() {
+        let request = "GET /orders HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, Restaurant::new(12)).await;
+        assert_eq!(result.unwrap_err(), "{\"message\":\"table_id required\",\"success\":false}");
+    }
+
+    #[tokio::test]
+    async fn test_lowercase_method() {
+        let restaurant = Restaurant::new(12);
+
+        let request = "post /orders HTTP/1.1\r\n\r\n{\"table_id\": 6, \"items\": [101]}";
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().status, 200);
+
+        let request = "Get /orders/6/count HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, restaurant).await;
+        assert!(result.unwrap().body_text().contains("\"count\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method() {
+        let request = "BREW /orders/6 HTTP/1.1\r\n\r\n";
+        let result = handle_request(request, Restaurant::new(12)).await;
+        assert_eq!(result.unwrap().status, 501);
+    }
+
     #[tokio::test]
     async fn test_invalid_request_path() {
         let request = "GET /invalid-path HTTP/1.1\r\n\r\n";
         let restaurant = Restaurant::new(100);
         let result = handle_request(request, restaurant).await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "HTTP/1.1 404 Not Found\r\n\r\nNot Found");
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\r\nNot Found");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_order_post_is_404() {
+        let restaurant = Restaurant::new(12);
+        for request in ["POST /orders/6 HTTP/1.1\r\n\r\n", "POST /orders/6/splits HTTP/1.1\r\n\r\n"] {
+            let result = handle_request(request, restaurant.clone()).await;
+            assert_eq!(result.unwrap().to_string(), "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\r\nNot Found");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_table_post_is_404() {
+        let restaurant = Restaurant::new(12);
+        for request in ["POST /tables/6 HTTP/1.1\r\n\r\n", "POST /tables/6/merges HTTP/1.1\r\n\r\n"] {
+            let result = handle_request(request, restaurant.clone()).await;
+            assert_eq!(result.unwrap().to_string(), "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\r\nNot Found");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dev_reset_only_in_dev_mode() {
+        let request = "POST /dev/reset HTTP/1.1\r\n\r\n";
+        let restaurant = Restaurant::new(3);
+        restaurant.get_table(1).write().add_order(7);
+
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(result.unwrap().to_string(), "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\r\nNot Found");
+        assert!(!restaurant.get_table(1).read().is_empty());
+
+        let restaurant = restaurant.with_dev_mode(true);
+        let result = handle_request(request, restaurant.clone()).await;
+        assert_eq!(
+            result.unwrap().to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Reset 3 tables\",\"success\":true}"
+        );
+        assert!(restaurant.get_table(1).read().is_empty());
+    }
+
+    /// Sends raw bytes to `handle_connection` over a local socket and returns the response.
+    async fn roundtrip(restaurant: Restaurant, request: &[u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(request).await.unwrap();
+        // Compressed bodies are not UTF-8
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    /// Reads one response delimited by its `Content-Length` header.
+    async fn read_response(client: &mut TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut byte = [0; 1];
+        while !data.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).await.unwrap();
+            data.push(byte[0]);
+        }
+        let head = String::from_utf8(data).unwrap();
+        let length: usize = http::header(&head, "Content-Length").unwrap().parse().unwrap();
+        let mut body = vec![0; length];
+        client.read_exact(&mut body).await.unwrap();
+        head + &String::from_utf8(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_max_requests() {
+        let restaurant = Restaurant::new(5).with_keepalive_max(3);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let request = b"GET /orders/2/count HTTP/1.1\r\nConnection: keep-alive\r\n\r\n";
+        for expected in ["keep-alive", "keep-alive", "close"] {
+            client.write_all(request).await.unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert!(response.contains(&format!("\r\nConnection: {}\r\n", expected)));
+            assert!(response.ends_with("{\"count\":0,\"table_id\":2}"));
+        }
+
+        // The fourth request finds the connection closed
+        let _ = client.write_all(request).await;
+        let mut rest = Vec::new();
+        let _ = client.read_to_end(&mut rest).await;
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_artificial_delay_only_in_dev_mode() {
+        for dev_mode in [true, false] {
+            let restaurant = Restaurant::new(5).with_dev_mode(dev_mode).with_artificial_delay_ms(300);
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = restaurant.clone();
+            tokio::spawn(async move {
+                let (stream, peer) = listener.accept().await.unwrap();
+                handle_connection(stream, server, Some(peer)).await;
+            });
+
+            let started = std::time::Instant::now();
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(b"GET /orders/2/count HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+            let reading = tokio::spawn(async move { read_response(&mut client).await });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // The table is free while the response is held back
+            assert!(restaurant.get_table(2).try_write().is_some());
+
+            let response = reading.await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert_eq!(started.elapsed() >= Duration::from_millis(300), dev_mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_post_request() {
+        let restaurant = Restaurant::new(10);
+        let request = b"POST /orders HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            e\r\n{\"table_id\": 3\r\n\
+            12\r\n, \"items\": [7, 8]}\r\n\
+            0\r\n\r\n";
+
+        let response = roundtrip(restaurant.clone(), request).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(restaurant.get_table(3).read().get_orders().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_split_across_segments() {
+        let restaurant = Restaurant::new(10);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = restaurant.clone();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, server, Some(peer)).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.set_nodelay(true).unwrap();
+        let body = "{\"table_id\": 3, \"items\": [7, 8]}";
+        let head = format!("POST /orders HTTP/1.1\r\nContent-Length: {}\r\n", body.len());
+        for segment in [head.as_str(), "\r\n", &body[..10], &body[10..]] {
+            client.write_all(segment.as_bytes()).await.unwrap();
+            client.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(restaurant.get_table(3).read().item_ids(), vec![7, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_slow_header_drip_is_cut_off() {
+        let restaurant = Restaurant::new(10).with_header_timeout_ms(300);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        client.set_nodelay(true).unwrap();
+        let (mut reader, mut writer) = client.into_split();
+        // Keep sending a header byte every 50ms, never finishing the headers
+        tokio::spawn(async move {
+            writer.write_all(b"GET /orders/1/count HTTP/1.1\r\n").await.unwrap();
+            loop {
+                if writer.write_all(b"X").await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+
+        let started = std::time::Instant::now();
+        let mut response = String::new();
+        let _ = tokio::time::timeout(Duration::from_secs(2), reader.read_to_string(&mut response))
+            .await
+            .expect("connection was not closed");
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_is_cut_off() {
+        let restaurant = Restaurant::new(10).with_header_timeout_ms(300);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+
+        // Connect and never send a byte
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let started = std::time::Instant::now();
+        let mut response = String::new();
+        let _ = tokio::time::timeout(Duration::from_secs(2), client.read_to_string(&mut response))
+            .await
+            .expect("connection was not closed");
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_body_logging() {
+        let request = b"POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [7]}";
+
+        let (logger, lines) = log::Logger::capturing(log::Level::Debug);
+        let restaurant = Restaurant::new(10).with_logger(logger.with_body_logging(true, 1024));
+        roundtrip(restaurant, request).await;
+        {
+            let lines = lines.lock().unwrap();
+            let bodies: Vec<&String> = lines.iter().filter(|line| line.starts_with("[DEBUG]")).collect();
+            assert_eq!(bodies.len(), 2);
+            assert!(bodies[0].ends_with("] request body: {\"table_id\": 3, \"items\": [7]}"));
+            assert!(bodies[1].contains("] response body: {\"confirmation\":\"0001\",\"created\":true"));
+        }
+
+        // Without the flag no bodies are logged, even at debug level
+        let (logger, lines) = log::Logger::capturing(log::Level::Debug);
+        let restaurant = Restaurant::new(10).with_logger(logger);
+        roundtrip(restaurant, request).await;
+        assert!(!lines.lock().unwrap().iter().any(|line| line.starts_with("[DEBUG]")));
+    }
+
+    #[tokio::test]
+    async fn test_logs_carry_peer_address() {
+        let (logger, lines) = log::Logger::capturing(log::Level::Info);
+        let restaurant = Restaurant::new(10).with_logger(logger);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, restaurant, Some(peer)).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let peer = client.local_addr().unwrap();
+        client.write_all(b"GET /missing HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(
+            *lines.lock().unwrap(),
+            vec![format!("[INFO] [{}] \"GET /missing HTTP/1.1\" 404", peer)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_active_connection_gauge() {
+        let restaurant = Restaurant::new(10);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_restaurant = restaurant.clone();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            handle_connection(stream, server_restaurant, Some(peer)).await;
+        });
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(restaurant.metrics().active_connections(), 1);
+
+        let request = "GET /metrics HTTP/1.1\r\n\r\n";
+        let response = handle_request(request, restaurant.clone()).await.unwrap().to_string();
+        assert!(response.contains("resto_active_connections 1\n"));
+
+        // Closing without sending a request is an early return
+        drop(client);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(restaurant.metrics().active_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_chunk_header() {
+        let request = b"POST /orders HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            xyz\r\n{\"table_id\": 3}\r\n0\r\n\r\n";
+
+        let response = roundtrip(Restaurant::new(10), request).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+
+    #[tokio::test]
+    async fn test_serve_exits_after_drain_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let server = tokio::spawn(serve(
+            listener,
+            Restaurant::new(10),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions {
+                drain_timeout: Duration::from_millis(200),
+                ..ServeOptions::default()
+            },
+        ));
+
+        // A client that connects but never sends a request keeps its connection open
+        let _stuck = TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        shutdown_tx.send(()).unwrap();
+        let abandoned = tokio::time::timeout(Duration::from_secs(2), server)
+            .await
+            .expect("server did not exit after the drain timeout")
+            .unwrap();
+        assert_eq!(abandoned, 1);
+    }
+
+    #[tokio::test]
+    async fn test_orders_during_drain() {
+        for reject in [false, true] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+            let restaurant = Restaurant::new(10).with_reject_orders_while_draining(reject);
+            let server = tokio::spawn(serve(
+                listener,
+                restaurant.clone(),
+                async {
+                    let _ = shutdown_rx.await;
+                },
+                ServeOptions {
+                    drain_timeout: Duration::from_secs(2),
+                    ..ServeOptions::default()
+                },
+            ));
+
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(b"GET /orders/1/count HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+            assert!(read_response(&mut client).await.starts_with("HTTP/1.1 200 OK"));
+
+            shutdown_tx.send(()).unwrap();
+            while !restaurant.is_draining() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+
+            // The open connection can still order, unless orders are rejected
+            let body = "{\"table_id\": 1, \"items\": [4]}";
+            let request = format!(
+                "POST /orders HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            client.write_all(request.as_bytes()).await.unwrap();
+            let response = read_response(&mut client).await;
+            match reject {
+                false => assert!(response.starts_with("HTTP/1.1 200 OK")),
+                true => {
+                    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+                    assert!(response.ends_with("{\"message\":\"server is shutting down\",\"success\":false}"));
+                }
+            }
+
+            // Other requests are still served either way
+            client.write_all(b"GET /orders/1/count HTTP/1.1\r\n\r\n").await.unwrap();
+            let mut rest = String::new();
+            client.read_to_string(&mut rest).await.unwrap();
+            let expected = if reject { "{\"count\":0,\"table_id\":1}" } else { "{\"count\":1,\"table_id\":1}" };
+            assert!(rest.starts_with("HTTP/1.1 200 OK") && rest.ends_with(expected));
+            assert_eq!(server.await.unwrap(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serving_server_is_healthy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            Restaurant::new(5),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions::default(),
+        ));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET /health?verbose=true HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("\"accept_loop\":\"ok\""));
+        assert!(response.contains("\"runtime\":\"ok\""));
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain_progress() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let restaurant = Restaurant::new(10);
+        let server = tokio::spawn(serve(
+            listener,
+            restaurant.clone(),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions {
+                drain_timeout: Duration::from_secs(5),
+                ..ServeOptions::default()
+            },
+        ));
+        let ready = b"GET /ready HTTP/1.1\r\nConnection: keep-alive\r\n\r\n";
+        let drain_status = |response: &str| -> serde_json::Value {
+            serde_json::from_str(body_of(response)).unwrap()
+        };
+
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        first.write_all(ready).await.unwrap();
+        assert!(read_response(&mut first).await.ends_with("{\"status\":\"ready\"}"));
+        second.write_all(ready).await.unwrap();
+        read_response(&mut second).await;
+
+        shutdown_tx.send(()).unwrap();
+        while !restaurant.is_draining() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        first.write_all(ready).await.unwrap();
+        let response = read_response(&mut first).await;
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        let status = drain_status(&response);
+        assert_eq!((status["status"].as_str(), status["active_connections"].as_u64()), (Some("draining"), Some(2)));
+        let elapsed = status["drain_elapsed_seconds"].as_f64().unwrap();
+
+        // Each connection that finishes brings the gauge down
+        drop(second);
+        while restaurant.metrics().active_connections() > 1 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        first.write_all(ready).await.unwrap();
+        let status = drain_status(&read_response(&mut first).await);
+        assert_eq!(status["active_connections"], 1);
+        assert!(status["drain_elapsed_seconds"].as_f64().unwrap() > elapsed);
+        assert!(restaurant.metrics().render().contains("resto_draining 1\n"));
+
+        drop(first);
+        assert_eq!(server.await.unwrap(), 0);
+        assert_eq!(restaurant.metrics().active_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_serve_writes_shutdown_summary() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let (logger, lines) = log::Logger::capturing(log::Level::Info);
+        let restaurant = Restaurant::new(10)
+            .with_logger(logger)
+            .with_admin_token(Some("secret".to_string()));
+        let mut cooking = order::Order::new(4, 3, 10, 0);
+        cooking.status = order::OrderStatus::Cooking;
+        restaurant.get_table(3).write().insert_order(cooking);
+        let path = std::env::temp_dir().join(format!("resto_summary_{}.json", std::process::id()));
+
+        let server = tokio::spawn(serve(
+            listener,
+            restaurant,
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions {
+                drain_timeout: Duration::from_millis(200),
+                summary_path: Some(path.clone()),
+                ..ServeOptions::default()
+            },
+        ));
+
+        for request in [
+            "POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 2, \"items\": [1, 2]}",
+            "POST /kitchen/serve-all HTTP/1.1\r\nX-Admin-Token: secret\r\n\r\n",
+            "GET /tables HTTP/1.1\r\n\r\n",
+        ] {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+        }
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+
+        let summary: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary["requests_total"], 3);
+        assert_eq!(summary["orders_created"], 2);
+        assert_eq!(summary["orders_served"], 1);
+        assert!(summary["uptime_seconds"].is_u64());
+        assert!(lines
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|line| line.starts_with("[INFO] Shutdown summary: {\"requests_total\":3,")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("resto_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let server = tokio::spawn(serve(
+            listener,
+            Restaurant::new(10),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions {
+                drain_timeout: Duration::from_millis(200),
+                max_connections_per_ip: 1,
+                ..ServeOptions::default()
+            },
+        ));
+
+        // Unix peers have no IP, so the per-IP cap does not apply to them
+        let _idle = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"POST /orders HTTP/1.1\r\n\r\n{\"table_id\": 3, \"items\": [5]}")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_limits_connections_per_ip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+        tokio::spawn(serve(
+            listener,
+            Restaurant::new(10),
+            async {
+                let _ = shutdown_rx.await;
+            },
+            ServeOptions {
+                drain_timeout: Duration::from_millis(100),
+                max_connections_per_ip: 2,
+                ..ServeOptions::default()
+            },
+        ));
+
+        // Two idle connections use up the cap for 127.0.0.1
+        let _first = TcpStream::connect(addr).await.unwrap();
+        let _second = TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut excess = TcpStream::connect(addr).await.unwrap();
+        let mut response = String::new();
+        excess.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+
+        // Another peer IP is unaffected
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.2:0".parse().unwrap()).unwrap();
+        let mut other = socket.connect(addr).await.unwrap();
+        other.write_all(b"GET /tables HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        other.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        shutdown_tx.send(()).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Category reported for items without one.
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// Kitchen station for items that are not mapped to one.
+pub const DEFAULT_STATION: &str = "kitchen";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MenuItem {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// The item's price in cents.
+    #[serde(default)]
+    pub price_cents: Option<u32>,
+    /// How long the kitchen takes to prepare the item.
+    #[serde(default)]
+    pub prep_seconds: Option<u32>,
+    /// The kitchen station that prepares the item.
+    #[serde(default)]
+    pub station: Option<String>,
+    /// How many can be ordered in total, across all tables; unlimited if unset.
+    #[serde(default)]
+    pub stock: Option<u32>,
+}
+
+impl MenuItem {
+    pub fn new(id: u32, name: &str, category: Option<&str>) -> MenuItem {
+        MenuItem {
+            id,
+            name: name.to_string(),
+            category: category.map(str::to_string),
+            price_cents: None,
+            prep_seconds: None,
+            station: None,
+            stock: None,
+        }
+    }
+
+    pub fn with_price_cents(mut self, price_cents: u32) -> MenuItem {
+        self.price_cents = Some(price_cents);
+        self
+    }
+
+    pub fn with_prep_seconds(mut self, prep_seconds: u32) -> MenuItem {
+        self.prep_seconds = Some(prep_seconds);
+        self
+    }
+
+    pub fn with_station(mut self, station: &str) -> MenuItem {
+        self.station = Some(station.to_string());
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_stock(mut self, stock: u32) -> MenuItem {
+        self.stock = Some(stock);
+        self
+    }
+}
+
+/// The items a restaurant serves, keyed by item id.
+#[derive(Debug, Clone, Default)]
+pub struct Menu {
+    items: HashMap<u32, MenuItem>,
+}
+
+impl Menu {
+    pub fn new(items: Vec<MenuItem>) -> Menu {
+        Menu {
+            items: items.into_iter().map(|item| (item.id, item)).collect(),
+        }
+    }
+
+    /// Parses a menu from a JSON array of items, rejecting duplicate ids.
+    pub fn from_json(json: &str) -> Result<Menu, String> {
+        let items: Vec<MenuItem> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid menu: {}", e))?;
+
+        let mut menu = Menu::default();
+        for item in items {
+            if menu.items.contains_key(&item.id) {
+                return Err(format!("Invalid menu: duplicate item id {}", item.id));
+            }
+            menu.items.insert(item.id, item);
+        }
+        Ok(menu)
+    }
+
+    /// Loads a menu from a JSON file; see `from_json` for the format.
+    pub fn load(path: &Path) -> Result<Menu, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read menu file {}: {}", path.display(), e))?;
+        Menu::from_json(&json)
+    }
+
+    /// The menu served when none is configured.
+    pub fn default_menu() -> Menu {
+        Menu::new(vec![
+            MenuItem::new(1, "Edamame", Some("appetizer")).with_price_cents(450).with_prep_seconds(5),
+            MenuItem::new(2, "Gyoza", Some("appetizer")).with_price_cents(650).with_prep_seconds(8).with_station("fryer"),
+            MenuItem::new(3, "Miso Soup", Some("appetizer")).with_price_cents(350).with_prep_seconds(5),
+            MenuItem::new(4, "Agedashi Tofu", Some("appetizer")).with_price_cents(600).with_prep_seconds(7),
+            MenuItem::new(5, "Karaage", Some("appetizer")).with_price_cents(750).with_prep_seconds(9).with_station("fryer"),
+            MenuItem::new(6, "Chicken Teriyaki", Some("main")).with_price_cents(1450).with_prep_seconds(12).with_station("grill"),
+            MenuItem::new(7, "Salmon Teriyaki", Some("main")).with_price_cents(1650).with_prep_seconds(13).with_station("grill"),
+            MenuItem::new(8, "Beef Sukiyaki", Some("main")).with_price_cents(1850).with_prep_seconds(15).with_station("grill"),
+            MenuItem::new(9, "Tonkotsu Ramen", Some("main")).with_price_cents(1550).with_prep_seconds(14),
+            MenuItem::new(10, "Shoyu Ramen", Some("main")).with_price_cents(1450).with_prep_seconds(14),
+            MenuItem::new(11, "Katsu Curry", Some("main")).with_price_cents(1500).with_prep_seconds(12).with_station("fryer"),
+            MenuItem::new(12, "Unagi Don", Some("main")).with_price_cents(1950).with_prep_seconds(11).with_station("grill"),
+            MenuItem::new(13, "Tempura Udon", Some("main")).with_price_cents(1400).with_prep_seconds(13).with_station("fryer"),
+            MenuItem::new(14, "Sushi Platter", Some("main")).with_price_cents(2400).with_prep_seconds(15),
+            MenuItem::new(15, "Matcha Ice Cream", Some("dessert")).with_price_cents(550).with_prep_seconds(5),
+            MenuItem::new(16, "Mochi", Some("dessert")).with_price_cents(500).with_prep_seconds(5),
+            MenuItem::new(17, "Dorayaki", Some("dessert")).with_price_cents(450).with_prep_seconds(6),
+            MenuItem::new(18, "Green Tea", Some("drink")).with_price_cents(300).with_prep_seconds(5).with_station("bar"),
+            MenuItem::new(19, "Ramune", Some("drink")).with_price_cents(400).with_prep_seconds(5).with_station("bar"),
+            MenuItem::new(20, "Sake", Some("drink")).with_price_cents(900).with_prep_seconds(5).with_station("bar"),
+            MenuItem::new(21, "Asahi", Some("drink")).with_price_cents(700).with_prep_seconds(5).with_station("bar"),
+        ])
+    }
+
+    pub fn get(&self, item_id: u32) -> Option<&MenuItem> {
+        self.items.get(&item_id)
+    }
+
+    /// Returns the item's preparation time, if the menu defines one.
+    pub fn prep_seconds(&self, item_id: u32) -> Option<u32> {
+        self.get(item_id).and_then(|item| item.prep_seconds)
+    }
+
+    /// Returns the station preparing the item, or `DEFAULT_STATION` if it is not mapped.
+    pub fn station_of(&self, item_id: u32) -> &str {
+        self.get(item_id)
+            .and_then(|item| item.station.as_deref())
+            .unwrap_or(DEFAULT_STATION)
+    }
+
+    /// Returns the item's category, or `UNCATEGORIZED` if it has none or is not on the menu.
+    pub fn category_of(&self, item_id: u32) -> &str {
+        self.get(item_id)
+            .and_then(|item| item.category.as_deref())
+            .unwrap_or(UNCATEGORIZED)
+    }
+}
+
+/// How soon the kitchen fires each menu category; lower priorities fire first.
+///
+/// Categories without a priority fire after every listed one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CategoryPriorities {
+    priorities: HashMap<String, u32>,
+}
+
+impl CategoryPriorities {
+    /// Parses priorities such as `drink=0,main=2`, returning `None` if any entry is malformed.
+    pub fn parse(value: &str) -> Option<CategoryPriorities> {
+        let mut priorities = HashMap::new();
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (category, priority) = entry.split_once('=')?;
+            priorities.insert(category.trim().to_string(), priority.trim().parse().ok()?);
+        }
+        Some(CategoryPriorities { priorities })
+    }
+
+    /// Returns the category's priority, or `u32::MAX` if it has none.
+    pub fn priority_of(&self, category: &str) -> u32 {
+        self.priorities.get(category).copied().unwrap_or(u32::MAX)
+    }
+}
+
+/// Which item the kitchen serves instead of each item that has sold out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Substitutions {
+    replacements: HashMap<u32, u32>,
+}
+
+impl Substitutions {
+    /// Parses substitutions such as `16=17,9=10`, returning `None` if any entry is malformed.
+    pub fn parse(value: &str) -> Option<Substitutions> {
+        let mut replacements = HashMap::new();
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (item_id, replacement) = entry.split_once('=')?;
+            replacements.insert(item_id.trim().parse().ok()?, replacement.trim().parse().ok()?);
+        }
+        Some(Substitutions { replacements })
+    }
+
+    /// Returns the item served instead of `item_id`, if it has one.
+    pub fn replacement_for(&self, item_id: u32) -> Option<u32> {
+        self.replacements.get(&item_id).copied()
+    }
+}
+
+/// What is left of the menu items with limited stock, shared by all tables.
+#[derive(Debug, Default)]
+pub struct Stock {
+    remaining: HashMap<u32, AtomicU32>,
+}
+
+impl Stock {
+    /// Starts counting down from the stock of each menu item that has one.
+    pub fn new(menu: &Menu) -> Stock {
+        Stock {
+            remaining: menu
+                .items
+                .values()
+                .filter_map(|item| Some((item.id, AtomicU32::new(item.stock?))))
+                .collect(),
+        }
+    }
+
+    /// Takes `quantity` of an item, returning false and taking nothing if
+    /// fewer are left. Items without a stock limit can always be taken.
+    pub fn take(&self, item_id: u32, quantity: u32) -> bool {
+        match self.remaining.get(&item_id) {
+            Some(left) => left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(quantity))
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    /// Puts `quantity` of an item back, such as when its order is removed.
+    pub fn restore(&self, item_id: u32, quantity: u32) {
+        if let Some(left) = self.remaining.get(&item_id) {
+            left.fetch_add(quantity, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns how many of the item are left, or `None` if it is unlimited.
+    #[cfg(test)]
+    pub fn remaining(&self, item_id: u32) -> Option<u32> {
+        self.remaining.get(&item_id).map(|left| left.load(Ordering::SeqCst))
+    }
+}
+
+/// Item names by id, for naming orders without configuring a full menu.
+#[derive(Debug, Clone, Default)]
+pub struct ItemNames {
+    names: HashMap<u32, String>,
+}
+
+impl ItemNames {
+    /// Parses names from a JSON object such as `{"16": "Shoyu Ramen"}`.
+    pub fn from_json(json: &str) -> Result<ItemNames, String> {
+        let names = serde_json::from_str(json).map_err(|e| format!("Invalid item names: {}", e))?;
+        Ok(ItemNames { names })
+    }
+
+    /// Loads names from a JSON file; see `from_json` for the format.
+    pub fn load(path: &Path) -> Result<ItemNames, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read item names file {}: {}", path.display(), e))?;
+        ItemNames::from_json(&json)
+    }
+
+    pub fn get(&self, item_id: u32) -> Option<&str> {
+        self.names.get(&item_id).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_of() {
+        let menu = Menu::new(vec![
+            MenuItem::new(1, "Edamame", Some("appetizer")),
+            MenuItem::new(2, "Bread", None),
+        ]);
+
+        assert_eq!(menu.category_of(1), "appetizer");
+        assert_eq!(menu.category_of(2), UNCATEGORIZED);
+        assert_eq!(menu.category_of(3), UNCATEGORIZED);
+        assert_eq!(menu.get(1).unwrap().name, "Edamame");
+    }
+
+    #[test]
+    fn test_prep_seconds() {
+        let menu = Menu::new(vec![
+            MenuItem::new(1, "Steak", Some("main")).with_price_cents(450).with_prep_seconds(20),
+            MenuItem::new(2, "Bread", None),
+        ]);
+
+        assert_eq!(menu.prep_seconds(1), Some(20));
+        assert_eq!(menu.prep_seconds(2), None);
+        assert_eq!(menu.prep_seconds(3), None);
+    }
+
+    #[test]
+    fn test_station_of() {
+        let menu = Menu::new(vec![
+            MenuItem::new(1, "Steak", Some("main")).with_station("grill"),
+            MenuItem::new(2, "Bread", None),
+        ]);
+
+        assert_eq!(menu.station_of(1), "grill");
+        assert_eq!(menu.station_of(2), DEFAULT_STATION);
+        assert_eq!(menu.station_of(3), DEFAULT_STATION);
+    }
+
+    #[test]
+    fn test_load_menu_file() {
+        let path = std::env::temp_dir().join(format!("resto_menu_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"[{"id":1,"name":"Ramen","price_cents":1200,"prep_seconds":14},{"id":2,"name":"Tea"}]"#,
+        )
+        .unwrap();
+
+        let menu = Menu::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(menu.get(1).unwrap().name, "Ramen");
+        assert_eq!(menu.get(1).unwrap().price_cents, Some(1200));
+        assert_eq!(menu.prep_seconds(1), Some(14));
+        assert_eq!(menu.get(2).unwrap().price_cents, None);
+        assert!(menu.get(3).is_none());
+    }
+
+    #[test]
+    fn test_menu_rejects_duplicate_ids() {
+        let result = Menu::from_json(r#"[{"id":4,"name":"Gyoza"},{"id":4,"name":"Karaage"}]"#);
+        assert_eq!(result.unwrap_err(), "Invalid menu: duplicate item id 4");
+
+        assert!(Menu::load(Path::new("/nonexistent/menu.json")).is_err());
+    }
+
+    #[test]
+    fn test_category_priorities() {
+        let priorities = CategoryPriorities::parse("drink=0, main=2").unwrap();
+
+        assert_eq!(priorities.priority_of("drink"), 0);
+        assert_eq!(priorities.priority_of("main"), 2);
+        assert_eq!(priorities.priority_of("dessert"), u32::MAX);
+        assert_eq!(CategoryPriorities::parse("drink=first"), None);
+        assert_eq!(CategoryPriorities::parse("drink"), None);
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let substitutions = Substitutions::parse("16=17, 9=10").unwrap();
+
+        assert_eq!(substitutions.replacement_for(16), Some(17));
+        assert_eq!(substitutions.replacement_for(17), None);
+        assert_eq!(Substitutions::parse("16=mochi"), None);
+        assert_eq!(Substitutions::parse("16"), None);
+    }
+
+    #[test]
+    fn test_stock() {
+        let menu = Menu::from_json(r#"[{"id":1,"name":"Special","stock":3},{"id":2,"name":"Tea"}]"#).unwrap();
+        let stock = Stock::new(&menu);
+
+        assert!(stock.take(1, 2));
+        assert!(!stock.take(1, 2));
+        assert_eq!(stock.remaining(1), Some(1));
+        stock.restore(1, 2);
+        assert_eq!(stock.remaining(1), Some(3));
+
+        assert!(stock.take(2, 1_000));
+        assert_eq!(stock.remaining(2), None);
+    }
+
+    #[test]
+    fn test_item_names() {
+        let names = ItemNames::from_json(r#"{"16": "Mochi", "3": "Miso Soup"}"#).unwrap();
+
+        assert_eq!(names.get(16), Some("Mochi"));
+        assert_eq!(names.get(3), Some("Miso Soup"));
+        assert_eq!(names.get(4), None);
+        assert!(ItemNames::from_json(r#"{"x": "Mochi"}"#).is_err());
+    }
+}
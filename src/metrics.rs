@@ -0,0 +1,208 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Content type of the Prometheus text format served at `/metrics`.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Process-wide counters exposed at `/metrics`.
+pub struct Metrics {
+    active_connections: AtomicUsize,
+    requests_total: AtomicU64,
+    orders_created: AtomicU64,
+    orders_served: AtomicU64,
+    /// Most mutations seen in flight on one table at the same time.
+    table_in_flight_max: AtomicUsize,
+    started: Instant,
+    /// When the server stopped accepting connections to shut down.
+    drain_started: OnceLock<Instant>,
+    /// When the accept loop last checked in, in milliseconds since the Unix epoch.
+    accept_loop_seen_ms: Mutex<Option<u64>>,
+}
+
+/// Totals logged when the server shuts down.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub requests_total: u64,
+    pub orders_created: u64,
+    pub orders_served: u64,
+    pub uptime_seconds: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics {
+            active_connections: AtomicUsize::new(0),
+            requests_total: AtomicU64::new(0),
+            orders_created: AtomicU64::new(0),
+            orders_served: AtomicU64::new(0),
+            table_in_flight_max: AtomicUsize::new(0),
+            started: Instant::now(),
+            drain_started: OnceLock::new(),
+            accept_loop_seen_ms: Mutex::new(None),
+        }
+    }
+}
+
+/// Counts one open connection; the gauge is decremented when it is dropped.
+pub struct ConnectionGuard {
+    metrics: Arc<Metrics>,
+}
+
+impl Metrics {
+    /// Records a new connection until the returned guard is dropped,
+    /// which also covers early returns and panics in the connection task.
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.active_connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            metrics: Arc::clone(self),
+        }
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::SeqCst)
+    }
+
+    pub fn record_request(&self) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_orders_created(&self, count: u64) {
+        self.orders_created.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_orders_served(&self, count: u64) {
+        self.orders_served.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Raises the in-flight high-water mark to `in_flight` if it is higher.
+    pub fn record_table_in_flight(&self, in_flight: usize) {
+        self.table_in_flight_max.fetch_max(in_flight, Ordering::Relaxed);
+    }
+
+    pub fn table_in_flight_max(&self) -> usize {
+        self.table_in_flight_max.load(Ordering::Relaxed)
+    }
+
+    /// Notes that the accept loop is running at `now_ms`.
+    pub fn record_accept_loop(&self, now_ms: u64) {
+        *self.accept_loop_seen_ms.lock() = Some(now_ms);
+    }
+
+    /// Returns when the accept loop last checked in, or `None` if it never has.
+    pub fn accept_loop_seen_ms(&self) -> Option<u64> {
+        *self.accept_loop_seen_ms.lock()
+    }
+
+    /// Notes that draining has begun; later calls keep the first start.
+    pub fn start_drain(&self) {
+        let _ = self.drain_started.set(Instant::now());
+    }
+
+    /// Returns how long the server has been draining, or `None` if it is not.
+    pub fn drain_elapsed(&self) -> Option<Duration> {
+        self.drain_started.get().map(Instant::elapsed)
+    }
+
+    /// Returns the totals since the metrics were created.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            requests_total: self.requests_total.load(Ordering::Relaxed),
+            orders_created: self.orders_created.load(Ordering::Relaxed),
+            orders_served: self.orders_served.load(Ordering::Relaxed),
+            uptime_seconds: self.started.elapsed().as_secs(),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let summary = self.summary();
+        format!(
+            "# HELP resto_active_connections Connections currently being served.\n\
+             # TYPE resto_active_connections gauge\n\
+             resto_active_connections {}\n\
+             # HELP resto_requests_total Requests received.\n\
+             # TYPE resto_requests_total counter\n\
+             resto_requests_total {}\n\
+             # HELP resto_orders_created_total Orders placed.\n\
+             # TYPE resto_orders_created_total counter\n\
+             resto_orders_created_total {}\n\
+             # HELP resto_orders_served_total Orders served.\n\
+             # TYPE resto_orders_served_total counter\n\
+             resto_orders_served_total {}\n\
+             # HELP resto_table_in_flight_max Most changes seen in flight on one table at once.\n\
+             # TYPE resto_table_in_flight_max gauge\n\
+             resto_table_in_flight_max {}\n\
+             # HELP resto_draining Whether the server is draining connections to shut down.\n\
+             # TYPE resto_draining gauge\n\
+             resto_draining {}\n\
+             # HELP resto_drain_elapsed_seconds Time spent draining so far.\n\
+             # TYPE resto_drain_elapsed_seconds gauge\n\
+             resto_drain_elapsed_seconds {:.3}\n",
+            self.active_connections(),
+            summary.requests_total,
+            summary.orders_created,
+            summary.orders_served,
+            self.table_in_flight_max(),
+            self.drain_elapsed().is_some() as u8,
+            self.drain_elapsed().unwrap_or_default().as_secs_f64()
+        )
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_gauge() {
+        let metrics = Arc::new(Metrics::default());
+
+        let first = metrics.track_connection();
+        let second = metrics.track_connection();
+        assert_eq!(metrics.active_connections(), 2);
+        assert!(metrics.render().contains("resto_active_connections 2\n"));
+
+        drop(first);
+        drop(second);
+        assert_eq!(metrics.active_connections(), 0);
+    }
+
+    #[test]
+    fn test_drain_gauges() {
+        let metrics = Metrics::default();
+        assert!(metrics.drain_elapsed().is_none());
+        assert!(metrics.render().contains("resto_draining 0\n"));
+        assert!(metrics.render().contains("resto_drain_elapsed_seconds 0.000\n"));
+
+        metrics.start_drain();
+        let first = metrics.drain_elapsed().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        metrics.start_drain();
+        assert!(metrics.drain_elapsed().unwrap() > first);
+        assert!(metrics.render().contains("resto_draining 1\n"));
+    }
+
+    #[test]
+    fn test_summary_counters() {
+        let metrics = Metrics::default();
+        metrics.record_request();
+        metrics.record_request();
+        metrics.record_orders_created(3);
+        metrics.record_orders_served(1);
+
+        assert_eq!(
+            metrics.summary(),
+            Summary { requests_total: 2, orders_created: 3, orders_served: 1, uptime_seconds: 0 }
+        );
+        assert!(metrics.render().contains("resto_orders_created_total 3\n"));
+    }
+}
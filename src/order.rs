@@ -1,20 +1,80 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Where an order is in the kitchen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OrderStatus {
+    Pending,
+    Cooking,
+    Served,
+    /// Taken off the bill by staff; only kept in the void log.
+    Voided,
+}
+
+impl OrderStatus {
+    /// Parses a status by its name as it appears in responses, such as `Cooking`.
+    pub fn parse(name: &str) -> Option<OrderStatus> {
+        match name {
+            "Pending" => Some(OrderStatus::Pending),
+            "Cooking" => Some(OrderStatus::Cooking),
+            "Served" => Some(OrderStatus::Served),
+            "Voided" => Some(OrderStatus::Voided),
+            _ => None,
+        }
+    }
+}
+
+/// An add-on to an order, such as extra cheese, and what it adds to the price.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifier {
+    pub name: String,
+    pub price_delta_cents: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Order {
     pub item_id: u32,
     pub table_id: u32,
     pub waiting_time: u32,
+    /// Milliseconds since the Unix epoch when the order was placed.
+    pub created_at: u64,
+    pub status: OrderStatus,
+    pub modifiers: Vec<Modifier>,
+    /// How many of the item were ordered.
+    pub quantity: u32,
+    /// Milliseconds since the Unix epoch when the order was fired to the kitchen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fired_at: Option<u64>,
 }
 
 impl Order {
-    pub fn new(item_id: u32, table_id: u32, waiting_time: u32) -> Order {
+    pub fn new(item_id: u32, table_id: u32, waiting_time: u32, created_at: u64) -> Order {
         Order {
-            item_id: item_id,
-            table_id: table_id,
-            waiting_time: waiting_time,
+            item_id,
+            table_id,
+            waiting_time,
+            created_at,
+            status: OrderStatus::Pending,
+            modifiers: Vec::new(),
+            quantity: 1,
+            fired_at: None,
         }
     }
+
+    /// Returns how long the order has existed at `now` (milliseconds since the Unix epoch).
+    pub fn age_ms(&self, now: u64) -> u64 {
+        now.saturating_sub(self.created_at)
+    }
+
+    /// Returns when the order is expected to be ready, in milliseconds since the Unix epoch.
+    pub fn ready_at(&self) -> u64 {
+        self.created_at + self.waiting_time as u64 * 1000
+    }
+
+    /// Returns the seconds left until the order is ready at `now`, never below zero.
+    pub fn remaining_seconds(&self, now: u64) -> u32 {
+        let elapsed_secs = self.age_ms(now) / 1000;
+        (self.waiting_time as u64).saturating_sub(elapsed_secs) as u32
+    }
 }
 
 #[cfg(test)]
@@ -23,7 +83,7 @@ mod tests {
 
     #[test]
     fn test_order() -> Result<(), String> {
-        let order = Order::new(10, 2, 5);
+        let order = Order::new(10, 2, 5, 1_000);
 
         assert_eq!(
             order,
@@ -31,8 +91,46 @@ mod tests {
                 item_id: 10,
                 table_id: 2,
                 waiting_time: 5,
+                created_at: 1_000,
+                status: OrderStatus::Pending,
+                modifiers: Vec::new(),
+                quantity: 1,
+                fired_at: None,
             }
         );
         Ok(())
     }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(OrderStatus::parse("Cooking"), Some(OrderStatus::Cooking));
+        assert_eq!(OrderStatus::parse("Served"), Some(OrderStatus::Served));
+        assert_eq!(OrderStatus::parse("cooking"), None);
+    }
+
+    #[test]
+    fn test_age_ms() {
+        let order = Order::new(10, 2, 5, 1_000);
+
+        assert_eq!(order.age_ms(4_500), 3_500);
+        assert_eq!(order.age_ms(500), 0);
+    }
+
+    #[test]
+    fn test_ready_at() {
+        let order = Order::new(10, 2, 5, 1_000);
+
+        assert_eq!(order.ready_at(), 6_000);
+        assert_eq!(order.remaining_seconds(order.ready_at()), 0);
+        assert_eq!(order.remaining_seconds(order.ready_at() - 1), 1);
+    }
+
+    #[test]
+    fn test_remaining_seconds() {
+        let order = Order::new(10, 2, 5, 1_000);
+
+        assert_eq!(order.remaining_seconds(1_000), 5);
+        assert_eq!(order.remaining_seconds(3_999), 3);
+        assert_eq!(order.remaining_seconds(60_000), 0);
+    }
 }
\ No newline at end of file
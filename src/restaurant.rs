@@ -1,29 +1,848 @@
-use std::sync::{Arc, Mutex};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
 
+use super::auth::{Authenticator, NoAuth};
+use super::clock::{Clock, SystemClock};
+use super::compress::{self, Encoding};
+use super::confirm::{self, Confirmation, Confirmations};
+use super::events::{Action, EventLog, VoidLog, VoidedOrder};
+use super::log::{Level, Logger};
+use super::menu::{CategoryPriorities, ItemNames, Menu, Stock, Substitutions};
+use super::metrics::Metrics;
 use super::table::Table;
+use super::throttle::OrderRateLimiter;
+use super::webhook::Webhook;
 
-type TablePtr = Arc<Mutex<Table>>;
+/// A shared, lockable table.
+///
+/// Uses `parking_lot`'s task-fair `RwLock` rather than the std lock: once a
+/// writer is waiting, new readers queue behind it, so a busy table cannot
+/// starve order writes (or vice versa). The cost is that readers lose some
+/// concurrency while a writer waits, which is fine given how briefly
+/// handlers hold table locks.
+pub type TablePtr = Arc<RwLock<Table>>;
+
+/// Default limit on the length of a request target.
+pub const DEFAULT_MAX_PATH_LENGTH: usize = 1024;
+
+/// Default limit on the length of a request path once percent-decoded.
+pub const DEFAULT_MAX_DECODED_PATH_LENGTH: usize = 1024;
+
+/// Default limit on the number of segments in a request path.
+pub const DEFAULT_MAX_PATH_SEGMENTS: usize = 8;
+
+/// Default number of requests served on one kept-alive connection.
+pub const DEFAULT_KEEPALIVE_MAX: usize = 100;
+
+/// Default time a request's headers may take to arrive, in milliseconds.
+pub const DEFAULT_HEADER_TIMEOUT_MS: u64 = 10_000;
+
+/// Default limit on the size of a serialized response body.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Default limit on the number of items in one order request.
+pub const DEFAULT_MAX_ITEMS_PER_REQUEST: usize = 100;
+
+/// What happens to an order request with more items than allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedBatch {
+    /// The whole request is rejected with `400`.
+    Reject,
+    /// Items up to the limit are added and the rest reported as skipped.
+    Truncate,
+}
+
+impl OversizedBatch {
+    /// Parses a mode name, `reject` or `truncate` (case-insensitive).
+    pub fn parse(name: &str) -> Option<OversizedBatch> {
+        match name.to_ascii_lowercase().as_str() {
+            "reject" => Some(OversizedBatch::Reject),
+            "truncate" => Some(OversizedBatch::Truncate),
+            _ => None,
+        }
+    }
+}
+
+/// A created table, with what requests use to coordinate on it.
+struct TableEntry {
+    table: TablePtr,
+    /// Woken whenever the table changes.
+    changes: Notify,
+    /// Mutations of the table currently being handled.
+    in_flight: AtomicUsize,
+}
+
+/// The tables a restaurant has created, keyed by id.
+///
+/// Nothing is held for a table until it is first used, so memory grows with
+/// the tables in use rather than with the table count.
+struct Tables {
+    count: usize,
+    /// Once this many tables are held, creating another first evicts the idle
+    /// ones nobody is using; `0` keeps every table once created.
+    max_resident: usize,
+    entries: RwLock<HashMap<u32, Arc<TableEntry>>>,
+}
+
+impl Tables {
+    fn new(count: usize, max_resident: usize) -> Tables {
+        Tables { count, max_resident, entries: RwLock::new(HashMap::new()) }
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the table if it has been created and not evicted since.
+    fn get(&self, table_id: u32) -> Option<Arc<TableEntry>> {
+        self.entries.read().get(&table_id).map(Arc::clone)
+    }
+
+    /// Returns the table, creating it with `create` if needed, and whether it
+    /// was created; `None` if the id is outside the restaurant.
+    fn get_or_create(&self, table_id: u32, create: impl FnOnce() -> Table) -> Option<(Arc<TableEntry>, bool)> {
+        if table_id as usize >= self.count {
+            return None;
+        }
+        if let Some(entry) = self.get(table_id) {
+            return Some((entry, false));
+        }
+        let mut entries = self.entries.write();
+        if let Some(entry) = entries.get(&table_id) {
+            return Some((Arc::clone(entry), false));
+        }
+        if self.max_resident > 0 && entries.len() >= self.max_resident {
+            // Tables and their entries are only given out under the map's
+            // lock, so a table held by nobody else stays unused while it is
+            // checked and dropped.
+            entries.retain(|_, entry| {
+                Arc::strong_count(entry) > 1 || Arc::strong_count(&entry.table) > 1 || !entry.table.read().is_idle()
+            });
+        }
+        let entry = Arc::new(TableEntry {
+            table: Arc::new(RwLock::new(create())),
+            changes: Notify::new(),
+            in_flight: AtomicUsize::new(0),
+        });
+        entries.insert(table_id, Arc::clone(&entry));
+        Some((entry, true))
+    }
+
+    /// Returns every table held, in id order.
+    fn created(&self) -> Vec<(u32, Arc<TableEntry>)> {
+        let mut created: Vec<_> = self
+            .entries
+            .read()
+            .iter()
+            .map(|(table_id, entry)| (*table_id, Arc::clone(entry)))
+            .collect();
+        created.sort_by_key(|(table_id, _)| *table_id);
+        created
+    }
+}
 
 #[derive(Clone)]
 pub struct Restaurant {
-    tables: Vec<TablePtr>,
+    tables: Arc<Tables>,
+    max_in_flight_per_table: usize,
+    /// Orders across every table.
+    order_count: Arc<AtomicUsize>,
+    max_total_orders: usize,
+    max_quantity_per_item: u32,
+    auto_occupancy: bool,
+    webhook: Option<Webhook>,
+    clock: Arc<dyn Clock>,
+    menu: Arc<Menu>,
+    stock: Arc<Stock>,
+    category_priorities: Arc<CategoryPriorities>,
+    substitutions: Arc<Substitutions>,
+    item_names: Option<Arc<ItemNames>>,
+    max_path_length: usize,
+    max_path_segments: usize,
+    max_decoded_path_length: usize,
+    logger: Logger,
+    admin_token: Option<String>,
+    authenticator: Arc<dyn Authenticator>,
+    delete_no_content: bool,
+    coalesce_window_ms: u64,
+    order_rate: Option<Arc<OrderRateLimiter>>,
+    max_items_per_request: usize,
+    oversized_batch: OversizedBatch,
+    max_response_bytes: usize,
+    keepalive_max: usize,
+    header_timeout_ms: u64,
+    min_order_cents: i64,
+    compression: Vec<Encoding>,
+    min_compress_bytes: usize,
+    dev_mode: bool,
+    artificial_delay_ms: u64,
+    server_header: bool,
+    strict_query: bool,
+    strict_json: bool,
+    metrics: Arc<Metrics>,
+    events: Arc<EventLog>,
+    voids: Arc<VoidLog>,
+    confirmations: Arc<Confirmations>,
+    /// Set once the server stops accepting connections and drains open ones.
+    draining: Arc<AtomicBool>,
+    reject_orders_while_draining: bool,
+}
+
+/// Marks one mutation of a table as in flight; see `Restaurant::begin_mutation`.
+pub struct MutationGuard {
+    entry: Option<Arc<TableEntry>>,
+}
+
+impl Drop for MutationGuard {
+    fn drop(&mut self) {
+        if let Some(entry) = &self.entry {
+            entry.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
 }
 
 impl Restaurant {
     pub fn new(number_of_tables: usize) -> Restaurant {
-        let mut tables = Vec::new();
-
-        tables.reserve(number_of_tables);
+        Restaurant::with_clock(number_of_tables, Arc::new(SystemClock))
+    }
 
-        for tid in 0..number_of_tables as u32 {
-            tables.push(Arc::new(Mutex::new(Table::new(tid))));
+    /// Creates a restaurant whose tables and handlers read the time from `clock`.
+    pub fn with_clock(number_of_tables: usize, clock: Arc<dyn Clock>) -> Restaurant {
+        Restaurant {
+            tables: Arc::new(Tables::new(number_of_tables, 0)),
+            max_in_flight_per_table: 0,
+            order_count: Arc::new(AtomicUsize::new(0)),
+            max_total_orders: 0,
+            max_quantity_per_item: 0,
+            auto_occupancy: true,
+            webhook: None,
+            clock,
+            menu: Arc::new(Menu::default_menu()),
+            stock: Arc::new(Stock::default()),
+            category_priorities: Arc::new(CategoryPriorities::default()),
+            substitutions: Arc::new(Substitutions::default()),
+            item_names: None,
+            max_path_length: DEFAULT_MAX_PATH_LENGTH,
+            max_path_segments: DEFAULT_MAX_PATH_SEGMENTS,
+            max_decoded_path_length: DEFAULT_MAX_DECODED_PATH_LENGTH,
+            logger: Logger::new(Level::Info),
+            admin_token: None,
+            authenticator: Arc::new(NoAuth),
+            delete_no_content: false,
+            coalesce_window_ms: 0,
+            order_rate: None,
+            max_items_per_request: DEFAULT_MAX_ITEMS_PER_REQUEST,
+            oversized_batch: OversizedBatch::Reject,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            keepalive_max: DEFAULT_KEEPALIVE_MAX,
+            header_timeout_ms: DEFAULT_HEADER_TIMEOUT_MS,
+            min_order_cents: 0,
+            compression: Vec::new(),
+            min_compress_bytes: compress::DEFAULT_MIN_COMPRESS_BYTES,
+            dev_mode: false,
+            artificial_delay_ms: 0,
+            server_header: true,
+            strict_query: false,
+            strict_json: false,
+            metrics: Arc::new(Metrics::default()),
+            events: Arc::new(EventLog::default()),
+            voids: Arc::new(VoidLog::default()),
+            confirmations: Arc::new(Confirmations::default()),
+            draining: Arc::new(AtomicBool::new(false)),
+            reject_orders_while_draining: false,
         }
+    }
+
+    /// Sets whether tables are marked occupied on their first order and
+    /// freed once their last order is removed.
+    pub fn with_auto_occupancy(mut self, auto_occupancy: bool) -> Restaurant {
+        self.auto_occupancy = auto_occupancy;
+        self
+    }
+
+    pub fn auto_occupancy(&self) -> bool {
+        self.auto_occupancy
+    }
+
+    /// Sets the webhook notified when orders become ready.
+    pub fn with_webhook(mut self, webhook: Webhook) -> Restaurant {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
 
-        Restaurant { tables: tables }
+    /// Returns the current time in milliseconds since the Unix epoch.
+    pub fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
     }
 
+    /// Returns the table with the given id, creating it on first use.
+    ///
+    /// Panics if the id is outside the restaurant.
+    #[cfg(test)]
     pub fn get_table(&self, table_id: u32) -> TablePtr {
-        Arc::clone(&self.tables[table_id as usize])
+        self.try_get_table(table_id).expect("table id out of range")
+    }
+
+    /// Sets the longest request target and the most path segments accepted.
+    pub fn with_path_limits(mut self, max_length: usize, max_segments: usize) -> Restaurant {
+        self.max_path_length = max_length;
+        self.max_path_segments = max_segments;
+        self
+    }
+
+    pub fn max_path_length(&self) -> usize {
+        self.max_path_length
+    }
+
+    pub fn max_path_segments(&self) -> usize {
+        self.max_path_segments
+    }
+
+    /// Sets the longest path accepted after percent-decoding.
+    pub fn with_max_decoded_path_length(mut self, max_length: usize) -> Restaurant {
+        self.max_decoded_path_length = max_length;
+        self
+    }
+
+    pub fn max_decoded_path_length(&self) -> usize {
+        self.max_decoded_path_length
+    }
+
+    pub fn with_logger(mut self, logger: Logger) -> Restaurant {
+        self.logger = logger;
+        self
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    /// Sets the token required by admin endpoints; without one they are disabled.
+    pub fn with_admin_token(mut self, admin_token: Option<String>) -> Restaurant {
+        self.admin_token = admin_token;
+        self
+    }
+
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
+    }
+
+    /// Sets how every request is authenticated before it is routed. The
+    /// default, `NoAuth`, lets everything through.
+    pub fn with_authenticator(mut self, authenticator: Arc<dyn Authenticator>) -> Restaurant {
+        self.authenticator = authenticator;
+        self
+    }
+
+    pub fn authenticator(&self) -> &dyn Authenticator {
+        self.authenticator.as_ref()
+    }
+
+    /// Sets whether successful deletes answer `204 No Content` instead of a JSON message.
+    pub fn with_delete_no_content(mut self, delete_no_content: bool) -> Restaurant {
+        self.delete_no_content = delete_no_content;
+        self
+    }
+
+    pub fn delete_no_content(&self) -> bool {
+        self.delete_no_content
+    }
+
+    /// Sets how long after an order repeat adds of the same item count towards
+    /// its quantity instead of replacing it; `0` disables coalescing.
+    pub fn with_coalesce_window_ms(mut self, coalesce_window_ms: u64) -> Restaurant {
+        self.coalesce_window_ms = coalesce_window_ms;
+        self
+    }
+
+    pub fn coalesce_window_ms(&self) -> u64 {
+        self.coalesce_window_ms
+    }
+
+    /// Sets the most items one order request may carry, `0` for no limit,
+    /// and how requests over the limit are handled.
+    pub fn with_max_items_per_request(mut self, max_items: usize, oversized: OversizedBatch) -> Restaurant {
+        self.max_items_per_request = max_items;
+        self.oversized_batch = oversized;
+        self
+    }
+
+    pub fn max_items_per_request(&self) -> usize {
+        self.max_items_per_request
+    }
+
+    pub fn oversized_batch(&self) -> OversizedBatch {
+        self.oversized_batch
+    }
+
+    /// Sets the largest response body serialized before giving up with `413`;
+    /// `0` disables the limit.
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Restaurant {
+        self.max_response_bytes = max_bytes;
+        self
+    }
+
+    pub fn max_response_bytes(&self) -> usize {
+        self.max_response_bytes
+    }
+
+    /// Caps how many order requests the whole restaurant accepts per minute.
+    /// A limit of `0` disables the cap.
+    pub fn with_order_rate_limit(mut self, per_minute: u32) -> Restaurant {
+        self.order_rate = (per_minute > 0)
+            .then(|| Arc::new(OrderRateLimiter::new(per_minute, self.clock.now_ms())));
+        self
+    }
+
+    /// Takes one order from the restaurant-wide rate limit, or returns the
+    /// seconds until the next order is accepted.
+    pub fn try_accept_order(&self) -> Result<(), u64> {
+        match &self.order_rate {
+            Some(limiter) => limiter.try_acquire(self.clock.now_ms()),
+            None => Ok(()),
+        }
+    }
+
+    /// Gives back the order taken by `try_accept_order` for a request that
+    /// was then rejected, so rejections do not use up the rate limit.
+    pub fn refund_order(&self) {
+        if let Some(limiter) = &self.order_rate {
+            limiter.refund();
+        }
+    }
+
+    /// Sets how many requests one kept-alive connection serves before it is closed.
+    pub fn with_keepalive_max(mut self, keepalive_max: usize) -> Restaurant {
+        self.keepalive_max = keepalive_max;
+        self
+    }
+
+    pub fn keepalive_max(&self) -> usize {
+        self.keepalive_max
+    }
+
+    /// Sets how long a request's headers may take to arrive, counted from
+    /// accept for a connection's first request and from the first byte for
+    /// later ones, so silent or dripping clients cannot hold a connection
+    /// open. A timeout of `0` disables it.
+    pub fn with_header_timeout_ms(mut self, header_timeout_ms: u64) -> Restaurant {
+        self.header_timeout_ms = header_timeout_ms;
+        self
+    }
+
+    pub fn header_timeout(&self) -> Option<Duration> {
+        (self.header_timeout_ms > 0).then(|| Duration::from_millis(self.header_timeout_ms))
+    }
+
+    /// Sets the minimum spend per table flagged on bills.
+    pub fn with_min_order_cents(mut self, min_order_cents: i64) -> Restaurant {
+        self.min_order_cents = min_order_cents;
+        self
+    }
+
+    pub fn min_order_cents(&self) -> i64 {
+        self.min_order_cents
+    }
+
+    /// Sets the response encodings offered, most preferred first, and the
+    /// smallest body worth compressing. An empty list disables compression.
+    pub fn with_compression(mut self, preference: Vec<Encoding>, min_bytes: usize) -> Restaurant {
+        self.compression = preference;
+        self.min_compress_bytes = min_bytes;
+        self
+    }
+
+    pub fn compression(&self) -> &[Encoding] {
+        &self.compression
+    }
+
+    pub fn min_compress_bytes(&self) -> usize {
+        self.min_compress_bytes
+    }
+
+    /// Sets whether responses name the server and its version in a `Server` header.
+    pub fn with_server_header(mut self, server_header: bool) -> Restaurant {
+        self.server_header = server_header;
+        self
+    }
+
+    pub fn server_header(&self) -> bool {
+        self.server_header
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Sets how many events the restaurant-wide event log keeps.
+    pub fn with_event_log_capacity(mut self, capacity: usize) -> Restaurant {
+        self.events = Arc::new(EventLog::new(capacity));
+        self
+    }
+
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    /// Records a change to one of a table's orders in the event log.
+    pub fn record_event(&self, table_id: u32, item_id: u32, action: Action) {
+        self.record_event_with_reason(table_id, item_id, action, None);
+    }
+
+    /// Like `record_event`, noting why the change was made.
+    pub fn record_event_with_reason(&self, table_id: u32, item_id: u32, action: Action, reason: Option<String>) {
+        self.events.record(table_id, item_id, action, reason, self.clock.now_ms());
+    }
+
+    /// Keeps a voided order in the void log.
+    pub fn record_void(&self, voided: VoidedOrder) {
+        self.voids.record(voided);
+    }
+
+    pub fn voids(&self) -> &VoidLog {
+        &self.voids
+    }
+
+    /// Sets how many digits confirmation codes are padded to.
+    pub fn with_confirmation_code_length(mut self, length: usize) -> Restaurant {
+        self.confirmations = Arc::new(Confirmations::new(length, confirm::DEFAULT_CONFIRMATION_CAPACITY));
+        self
+    }
+
+    /// Issues the confirmation code for orders placed on a table by one request.
+    pub fn confirm_orders(&self, table_id: u32, item_ids: Vec<u32>) -> String {
+        self.confirmations.issue(Confirmation { table_id, item_ids })
+    }
+
+    /// Finds where the orders confirmed by `code` were placed.
+    pub fn resolve_confirmation(&self, code: &str) -> Option<Confirmation> {
+        self.confirmations.resolve(code)
+    }
+
+    /// Replaces the default menu, starting its items' stock from the menu's limits.
+    pub fn with_menu(mut self, menu: Menu) -> Restaurant {
+        self.stock = Arc::new(Stock::new(&menu));
+        self.menu = Arc::new(menu);
+        self
+    }
+
+    pub fn menu(&self) -> &Menu {
+        &self.menu
+    }
+
+    pub fn stock(&self) -> &Stock {
+        &self.stock
+    }
+
+    /// Sets how soon the kitchen queue fires each menu category.
+    pub fn with_category_priorities(mut self, priorities: CategoryPriorities) -> Restaurant {
+        self.category_priorities = Arc::new(priorities);
+        self
+    }
+
+    pub fn category_priorities(&self) -> &CategoryPriorities {
+        &self.category_priorities
+    }
+
+    /// Sets which item replaces each sold-out item in orders that allow it.
+    pub fn with_substitutions(mut self, substitutions: Substitutions) -> Restaurant {
+        self.substitutions = Arc::new(substitutions);
+        self
+    }
+
+    pub fn substitutions(&self) -> &Substitutions {
+        &self.substitutions
+    }
+
+    /// Keeps at most about `max_resident` tables in memory, for restaurants
+    /// with far more tables than are in use at once. A cap of `0` keeps every
+    /// table once created.
+    ///
+    /// When the cap is reached, creating a table first evicts every idle table
+    /// (no orders, not occupied) that no request is using;
+    /// an evicted table is created afresh on its next use. Busy tables are
+    /// never evicted, so the cap can be exceeded. Tables already created are
+    /// dropped, so call this before the restaurant is used.
+    pub fn with_max_resident_tables(mut self, max_resident: usize) -> Restaurant {
+        self.tables = Arc::new(Tables::new(self.tables.len(), max_resident));
+        self
+    }
+
+    /// Caps how many mutations of one table may be handled at the same time.
+    /// A cap of `0` disables it.
+    pub fn with_max_in_flight_per_table(mut self, max_in_flight: usize) -> Restaurant {
+        self.max_in_flight_per_table = max_in_flight;
+        self
+    }
+
+    /// Counts a mutation of the table as in flight until the returned guard is
+    /// dropped, recording the high-water mark in the metrics. Returns `None`,
+    /// counting nothing, if the table is already at its cap.
+    ///
+    /// Ids outside the restaurant are not counted; the handler reports them.
+    /// The guard keeps the table from being evicted.
+    pub fn begin_mutation(&self, table_id: u32) -> Option<MutationGuard> {
+        let entry = match self.entry(table_id) {
+            Some(entry) => entry,
+            None => return Some(MutationGuard { entry: None }),
+        };
+        let in_flight = entry.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.max_in_flight_per_table > 0 && in_flight > self.max_in_flight_per_table {
+            entry.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        self.metrics.record_table_in_flight(in_flight);
+        Some(MutationGuard { entry: Some(entry) })
+    }
+
+    /// Waits up to `timeout` for the table's orders to change, returning
+    /// whether they did; `false` at once if the id is outside the restaurant.
+    ///
+    /// Waiting creates the table, so the order that ends the wait finds the
+    /// same notifier, and keeps it from being evicted until the wait is over.
+    pub async fn wait_for_table_change(&self, table_id: u32, timeout: Duration) -> bool {
+        let entry = match self.entry(table_id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let changed = entry.changes.notified();
+        tokio::time::timeout(timeout, changed).await.is_ok()
+    }
+
+    /// Wakes every request waiting for a change to the table.
+    pub fn notify_table_changed(&self, table_id: u32) {
+        if let Some(entry) = self.tables.get(table_id) {
+            entry.changes.notify_waiters();
+        }
+    }
+
+    /// Sets the names added to orders in responses.
+    pub fn with_item_names(mut self, item_names: ItemNames) -> Restaurant {
+        self.item_names = Some(Arc::new(item_names));
+        self
+    }
+
+    pub fn item_names(&self) -> Option<&ItemNames> {
+        self.item_names.as_deref()
+    }
+
+    /// Returns the number of tables, created or not.
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Iterates over the tables created so far with their ids, in id order.
+    ///
+    /// Callers lock each table themselves, one at a time. Tables not created
+    /// yet are empty and unoccupied; iterating skips them and never creates them.
+    pub fn iter_tables(&self) -> impl Iterator<Item = (u32, TablePtr)> {
+        self.tables
+            .created()
+            .into_iter()
+            .map(|(table_id, entry)| (table_id, Arc::clone(&entry.table)))
+    }
+
+    /// Returns the ids of the tables not created yet, in order.
+    pub fn uncreated_table_ids(&self) -> Vec<u32> {
+        let created: HashSet<u32> = self.tables.created().into_iter().map(|(table_id, _)| table_id).collect();
+        (0..self.tables.len() as u32).filter(|table_id| !created.contains(table_id)).collect()
+    }
+
+    /// Returns the table with the given id, creating it on first use, or
+    /// `None` if the id is outside the restaurant.
+    pub fn try_get_table(&self, table_id: u32) -> Option<TablePtr> {
+        self.get_or_create_table(table_id).map(|(table, _)| table)
+    }
+
+    /// Returns the table if it has been created, without creating it.
+    pub fn find_table(&self, table_id: u32) -> Option<TablePtr> {
+        self.tables.get(table_id).map(|entry| Arc::clone(&entry.table))
+    }
+
+    /// Returns true if the id is one of the restaurant's tables, created or not.
+    pub fn has_table(&self, table_id: u32) -> bool {
+        (table_id as usize) < self.tables.len()
+    }
+
+    /// Runs `f` on the table with its read lock held, or returns `None` if the
+    /// id is outside the restaurant.
+    ///
+    /// Reading never creates a table: one not created yet is read as the empty
+    /// table it would start as, which `f` cannot change and which is dropped
+    /// when `f` returns. The lock is released when `f` returns. `f` cannot
+    /// await, so handlers using this can never hold a table lock across an
+    /// `.await`.
+    pub fn with_table_read<R>(&self, table_id: u32, f: impl FnOnce(&Table) -> R) -> Option<R> {
+        if !self.has_table(table_id) {
+            return None;
+        }
+        match self.find_table(table_id) {
+            Some(table) => {
+                let guard = table.read();
+                Some(f(&guard))
+            }
+            None => Some(f(&self.new_table(table_id))),
+        }
+    }
+
+    /// Runs `f` on the table with its write lock held, or returns `None` if
+    /// the table does not exist. Like `with_table_read`, the lock never
+    /// outlives `f`.
+    pub fn with_table_write<R>(&self, table_id: u32, f: impl FnOnce(&mut Table) -> R) -> Option<R> {
+        let table = self.try_get_table(table_id)?;
+        let mut guard = table.write();
+        Some(f(&mut guard))
+    }
+
+    /// Like `try_get_table`, but also reports whether this call created the table.
+    pub fn get_or_create_table(&self, table_id: u32) -> Option<(TablePtr, bool)> {
+        self.tables
+            .get_or_create(table_id, || self.new_table(table_id))
+            .map(|(entry, created)| (Arc::clone(&entry.table), created))
+    }
+
+    /// Returns the table's entry, creating the table on first use.
+    fn entry(&self, table_id: u32) -> Option<Arc<TableEntry>> {
+        self.tables
+            .get_or_create(table_id, || self.new_table(table_id))
+            .map(|(entry, _)| entry)
+    }
+
+    /// Rejects requests with query parameters their endpoint does not know
+    /// with `400`, instead of ignoring them.
+    pub fn with_strict_query(mut self, strict_query: bool) -> Restaurant {
+        self.strict_query = strict_query;
+        self
+    }
+
+    pub fn strict_query(&self) -> bool {
+        self.strict_query
+    }
+
+    /// Rejects request bodies with fields their endpoint does not know with
+    /// `400`, instead of ignoring those fields.
+    pub fn with_strict_json(mut self, strict_json: bool) -> Restaurant {
+        self.strict_json = strict_json;
+        self
+    }
+
+    pub fn strict_json(&self) -> bool {
+        self.strict_json
+    }
+
+    /// Sets whether new orders arriving on open connections while the server
+    /// drains are rejected. By default they are still accepted, so parties
+    /// already being served can finish ordering.
+    pub fn with_reject_orders_while_draining(mut self, reject: bool) -> Restaurant {
+        self.reject_orders_while_draining = reject;
+        self
+    }
+
+    /// Marks the server as draining; shared by every clone.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+        self.metrics.start_drain();
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Returns false if new orders are being turned away during shutdown.
+    pub fn accepts_orders(&self) -> bool {
+        !(self.reject_orders_while_draining && self.is_draining())
+    }
+
+    /// Builds an empty table that reads this restaurant's clock and counts
+    /// its orders towards the restaurant's total.
+    fn new_table(&self, table_id: u32) -> Table {
+        Table::with_clock(table_id, Arc::clone(&self.clock)).with_order_count(Arc::clone(&self.order_count))
+    }
+
+    /// Caps the number of orders across all tables. A cap of `0` disables it.
+    pub fn with_max_total_orders(mut self, max_total_orders: usize) -> Restaurant {
+        self.max_total_orders = max_total_orders;
+        self
+    }
+
+    /// Returns the number of orders across all tables.
+    pub fn total_orders(&self) -> usize {
+        self.order_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if `new_orders` more orders fit under the restaurant-wide cap.
+    ///
+    /// The check does not reserve anything, so orders placed on different
+    /// tables at the same moment can overshoot the cap slightly.
+    pub fn has_room_for_orders(&self, new_orders: usize) -> bool {
+        self.max_total_orders == 0 || self.total_orders() + new_orders <= self.max_total_orders
+    }
+
+    /// Caps the quantity one order may reach through repeat adds. A cap of `0`
+    /// disables it.
+    pub fn with_max_quantity_per_item(mut self, max_quantity: u32) -> Restaurant {
+        self.max_quantity_per_item = max_quantity;
+        self
+    }
+
+    /// Returns the most one order's quantity may reach, or `0` for no cap.
+    pub fn max_quantity_per_item(&self) -> u32 {
+        self.max_quantity_per_item
+    }
+
+    /// Enables development-only endpoints such as `/dev/reset`.
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Restaurant {
+        self.dev_mode = dev_mode;
+        self
+    }
+
+    pub fn dev_mode(&self) -> bool {
+        self.dev_mode
+    }
+
+    /// Delays every response by `delay_ms`, so clients can test their
+    /// timeouts. Only applies in dev mode.
+    pub fn with_artificial_delay_ms(mut self, delay_ms: u64) -> Restaurant {
+        self.artificial_delay_ms = delay_ms;
+        self
+    }
+
+    /// Returns how long to wait before sending each response, if at all.
+    pub fn artificial_delay(&self) -> Option<Duration> {
+        (self.dev_mode && self.artificial_delay_ms > 0).then(|| Duration::from_millis(self.artificial_delay_ms))
+    }
+
+    /// Replaces every table with an empty one, returning how many tables the
+    /// restaurant has. The stock of every discarded order is put back, so the
+    /// menu's stock limits start over.
+    ///
+    /// Every created table's lock is taken, in id order, before any table is
+    /// touched and held until all are reset, so a request sees each table
+    /// either wholly before or wholly after the reset. Blocks while waiting
+    /// for the locks.
+    pub fn reset_tables(&self) -> usize {
+        let created = self.tables.created();
+        let mut tables: Vec<_> = created.iter().map(|(table_id, entry)| (*table_id, entry.table.write())).collect();
+        for (table_id, table) in tables.iter_mut() {
+            for order in table.reset() {
+                self.stock.restore(order.item_id, order.quantity);
+            }
+            **table = self.new_table(*table_id);
+        }
+        drop(tables);
+
+        for (_, entry) in &created {
+            entry.changes.notify_waiters();
+        }
+        self.tables.len()
     }
 }
 
@@ -48,6 +867,216 @@ mod tests {
         let table_id = 1;
         let table_ptr = restaurant.get_table(table_id);
 
-        assert!(table_ptr.lock().is_ok()); // Check if the mutex can be locked
+        assert!(table_ptr.try_write().is_some()); // Check if the lock can be acquired
+    }
+
+    #[test]
+    fn test_try_get_table() {
+        let restaurant = Restaurant::new(3);
+
+        assert!(restaurant.try_get_table(2).is_some());
+        assert!(restaurant.try_get_table(3).is_none());
+    }
+
+    #[test]
+    fn test_tables_created_on_first_use() {
+        let restaurant = Restaurant::new(3);
+        assert!(restaurant.iter_tables().next().is_none());
+
+        assert!(matches!(restaurant.get_or_create_table(1), Some((_, true))));
+        assert!(matches!(restaurant.get_or_create_table(1), Some((_, false))));
+        assert!(matches!(restaurant.get_or_create_table(0), Some((_, true))));
+        assert!(matches!(restaurant.get_or_create_table(0), Some((_, false))));
+        assert!(restaurant.get_or_create_table(3).is_none());
+
+        // Clones share the created tables
+        restaurant.clone().get_table(0).write().add_order(4);
+        assert!(restaurant.iter_tables().any(|(_, t)| !t.read().is_empty()));
+    }
+
+    #[test]
+    fn test_resident_tables_created_lazily() {
+        let restaurant = Restaurant::new(1_000_000).with_max_resident_tables(2);
+        assert_eq!(restaurant.tables.created().len(), 0);
+
+        assert!(matches!(restaurant.get_or_create_table(999_999), Some((_, true))));
+        assert!(matches!(restaurant.get_or_create_table(999_999), Some((_, false))));
+        assert!(restaurant.get_or_create_table(1_000_000).is_none());
+        assert_eq!(restaurant.tables.created().len(), 1);
+
+        // Notifying and scanning hold nothing for tables nobody has used
+        restaurant.notify_table_changed(5);
+        assert_eq!(restaurant.iter_tables().count(), 1);
+        assert_eq!(restaurant.tables.created().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_keeps_tables_in_use() {
+        let restaurant = Restaurant::new(10).with_max_resident_tables(1);
+        let guard = restaurant.begin_mutation(1).unwrap();
+        let waiting = tokio::spawn({
+            let restaurant = restaurant.clone();
+            async move { restaurant.wait_for_table_change(2, Duration::from_secs(5)).await }
+        });
+        while restaurant.tables.get(2).is_none() {
+            tokio::task::yield_now().await;
+        }
+
+        // Neither the table being changed nor the one being waited on is evicted
+        restaurant.get_table(3);
+        let resident: Vec<u32> = restaurant.tables.created().iter().map(|(table_id, _)| *table_id).collect();
+        assert_eq!(resident, vec![1, 2, 3]);
+
+        restaurant.notify_table_changed(2);
+        assert!(waiting.await.unwrap());
+        drop(guard);
+        restaurant.get_table(4);
+        let resident: Vec<u32> = restaurant.tables.created().iter().map(|(table_id, _)| *table_id).collect();
+        assert_eq!(resident, vec![4]);
+    }
+
+    #[test]
+    fn test_eviction_keeps_busy_tables() {
+        let restaurant = Restaurant::new(10).with_max_resident_tables(2);
+        restaurant.get_table(1).write().add_order(4);
+        restaurant.get_table(2).write().set_occupied(true);
+        restaurant.get_table(3);
+        let held = restaurant.get_table(4);
+
+        // Only the idle table nobody holds was dropped to make room
+        restaurant.get_table(5);
+        let resident: Vec<u32> = restaurant.tables.created().iter().map(|(table_id, _)| *table_id).collect();
+        assert_eq!(resident, vec![1, 2, 4, 5]);
+        assert_eq!(restaurant.get_table(1).read().item_ids(), vec![4]);
+        assert!(restaurant.get_table(2).read().summary().occupied);
+        assert!(Arc::ptr_eq(&held, &restaurant.get_table(4)));
+
+        // An evicted table comes back empty on its next use
+        assert!(matches!(restaurant.get_or_create_table(3), Some((_, true))));
+    }
+
+    #[test]
+    fn test_with_table_lock_released() {
+        let restaurant = Restaurant::new(3);
+
+        let count = restaurant.with_table_write(1, |table| {
+            table.add_order(4);
+            table.order_count()
+        });
+        assert_eq!(count, Some(1));
+        assert!(restaurant.get_table(1).try_write().is_some());
+
+        assert_eq!(restaurant.with_table_read(1, |table| table.item_ids()), Some(vec![4]));
+        assert!(restaurant.get_table(1).try_write().is_some());
+        assert!(restaurant.with_table_read(3, |_| ()).is_none());
+
+        // Reading a table nobody has used does not create it
+        assert_eq!(restaurant.with_table_read(2, |table| table.is_empty()), Some(true));
+        assert!(restaurant.find_table(2).is_none());
+        assert_eq!(restaurant.iter_tables().count(), 1);
+    }
+
+    #[test]
+    fn test_iter_tables() {
+        let restaurant = Restaurant::new(4);
+        assert_eq!(restaurant.iter_tables().count(), 0);
+
+        restaurant.get_table(3);
+        restaurant.get_table(1);
+        let ids: Vec<u32> = restaurant.iter_tables().map(|(table_id, _)| table_id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(restaurant.uncreated_table_ids(), vec![0, 2]);
+
+        for (table_id, table) in restaurant.iter_tables() {
+            assert_eq!(table.read().summary().table_id, table_id);
+        }
+    }
+
+    #[test]
+    fn test_reset_tables() {
+        use std::sync::mpsc;
+
+        let restaurant = Restaurant::new(3);
+        restaurant.get_table(0).write().add_order(4);
+        restaurant.get_table(2).write().add_order(5);
+        restaurant.get_table(2).write().set_occupied(true);
+
+        // A request holding a table keeps the reset waiting until it is done
+        let held = restaurant.get_table(2);
+        let guard = held.write();
+        let (done_tx, done_rx) = mpsc::channel();
+        let resetting = {
+            let restaurant = restaurant.clone();
+            std::thread::spawn(move || done_tx.send(restaurant.reset_tables()).unwrap())
+        };
+        assert!(done_rx.recv_timeout(std::time::Duration::from_millis(50)).is_err());
+        drop(guard);
+
+        assert_eq!(done_rx.recv().unwrap(), 3);
+        resetting.join().unwrap();
+        for (table_id, table) in restaurant.iter_tables() {
+            let table = table.read();
+            assert!(table.is_empty());
+            assert_eq!(table.summary(), crate::table::TableSummary { table_id, occupied: false, order_count: 0 });
+        }
+    }
+
+    #[test]
+    fn test_fake_clock_ages_orders() {
+        let clock = Arc::new(crate::clock::FakeClock::new(0));
+        let restaurant = Restaurant::with_clock(3, clock.clone());
+        let t = restaurant.get_table(1);
+
+        t.write().add_order(4);
+        clock.advance(2_500);
+        assert_eq!(restaurant.now_ms(), 2_500);
+
+        let table = t.read();
+        let order = table.get_order(4).unwrap();
+        assert_eq!(order.age_ms(restaurant.now_ms()), 2_500);
+        assert_eq!(order.remaining_seconds(restaurant.now_ms()), order.waiting_time - 2);
+        assert!(table.has_order_older_than(2_500, restaurant.now_ms()));
+    }
+
+    #[test]
+    fn test_hot_table_writer_not_starved() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let restaurant = Restaurant::new(2);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Readers keep the table locked almost continuously
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let t = restaurant.get_table(1);
+                let stop = Arc::clone(&stop);
+                std::thread::spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let table = t.read();
+                        std::hint::black_box(table.order_count());
+                    }
+                })
+            })
+            .collect();
+
+        let (done, finished) = mpsc::channel();
+        let t = restaurant.get_table(1);
+        std::thread::spawn(move || {
+            for item_id in 0..500 {
+                t.write().add_order(item_id);
+            }
+            done.send(()).unwrap();
+        });
+
+        let result = finished.recv_timeout(Duration::from_secs(10));
+        stop.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert!(result.is_ok(), "writer starved by readers");
+        assert_eq!(restaurant.get_table(1).read().order_count(), 500);
     }
-}
\ No newline at end of file
+}
@@ -1,26 +1,190 @@
 
 use rand::{thread_rng, Rng};
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use super::order::Order;
+use super::clock::Clock;
+use super::order::{Modifier, Order, OrderStatus};
 
 pub struct Table {
     id: u32,
-    orders: HashMap<u32, Order>
+    orders: HashMap<u32, Order>,
+    occupied: bool,
+    clock: Arc<dyn Clock>,
+    /// Running total of orders, shared by every table of a restaurant.
+    order_count: Arc<AtomicUsize>,
+}
+
+/// A short overview of a table used in the table listing.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TableSummary {
+    pub table_id: u32,
+    pub occupied: bool,
+    pub order_count: usize,
+}
+
+/// How many of a table's orders are in each status.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct StatusCounts {
+    pub table_id: u32,
+    pub pending: usize,
+    pub cooking: usize,
+    pub served: usize,
+}
+
+/// Waiting time statistics over a table's orders.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WaitStats {
+    pub avg_remaining_seconds: f64,
+    pub max_remaining_seconds: u32,
+    pub order_count: usize,
 }
 
 impl Table {
+    #[cfg(test)]
     pub fn new(table_id: u32) -> Table {
+        Table::with_clock(table_id, Arc::new(super::clock::SystemClock))
+    }
+
+    /// Creates a table that timestamps its orders with `clock`.
+    pub fn with_clock(table_id: u32, clock: Arc<dyn Clock>) -> Table {
         Table {
             id: table_id,
-            orders: HashMap::new()
+            orders: HashMap::new(),
+            occupied: false,
+            clock,
+            order_count: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Counts the table's orders in `order_count`, which other tables may
+    /// share. Orders the table already has are not counted.
+    pub fn with_order_count(mut self, order_count: Arc<AtomicUsize>) -> Table {
+        self.order_count = order_count;
+        self
+    }
+
+    /// Adds an order placed now.
     pub fn add_order(&mut self, item_id: u32) {
+        let now = self.clock.now_ms();
+        self.add_order_at(item_id, now);
+    }
+
+    /// Adds an order placed now that takes `waiting_time` seconds to prepare.
+    pub fn add_order_with_wait(&mut self, item_id: u32, waiting_time: u32) {
+        let now = self.clock.now_ms();
+        self.insert_order(Order::new(item_id, self.id, waiting_time, now));
+    }
+
+    /// Adds an order placed at `created_at` (milliseconds since the Unix epoch).
+    pub fn add_order_at(&mut self, item_id: u32, created_at: u64) {
         let mut rng = thread_rng();
-        let order = Order::new(item_id, self.id, rng.gen_range(5..16));
-        self.orders.insert(item_id, order);
+        self.insert_order(Order::new(item_id, self.id, rng.gen_range(5..16), created_at));
+    }
+
+    /// Counts a repeat add of `item_id` towards its existing order if that
+    /// order was placed less than `window_ms` ago, so quick double taps do not
+    /// replace it. Returns true if the add was coalesced; a zero window never
+    /// coalesces.
+    pub fn coalesce_order(&mut self, item_id: u32, window_ms: u64) -> bool {
+        let now = self.clock.now_ms();
+        match self.orders.get_mut(&item_id) {
+            Some(order) if window_ms > 0 && order.age_ms(now) < window_ms => {
+                order.quantity += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the quantity each of `items` would reach if they were added now,
+    /// in order, with repeat adds coalescing as in `coalesce_order`.
+    pub fn quantities_after_adds(&self, items: &[u32], window_ms: u64) -> HashMap<u32, u32> {
+        let now = self.clock.now_ms();
+        let mut quantities = HashMap::new();
+        for item in items {
+            let current = quantities.get(item).copied().or_else(|| {
+                self.orders
+                    .get(item)
+                    .filter(|order| order.age_ms(now) < window_ms)
+                    .map(|order| order.quantity)
+            });
+            let quantity = match current {
+                Some(quantity) if window_ms > 0 => quantity + 1,
+                _ => 1,
+            };
+            quantities.insert(*item, quantity);
+        }
+        quantities
+    }
+
+    /// Adds a fully constructed order, replacing any order for the same item.
+    pub fn insert_order(&mut self, order: Order) {
+        if self.orders.insert(order.item_id, order).is_none() {
+            self.order_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Moves an order from another table onto this one. An order for an item
+    /// already on the table adds its quantity to the existing order.
+    pub fn merge_order(&mut self, mut order: Order) {
+        match self.orders.get_mut(&order.item_id) {
+            Some(existing) => existing.quantity += order.quantity,
+            None => {
+                order.table_id = self.id;
+                self.insert_order(order);
+            }
+        }
+    }
+
+    /// Replaces the order for `item_id` with an order for `new_item_id` placed
+    /// now, keeping its quantity but not its modifiers. If `new_item_id` is
+    /// already on the table the quantity is added to that order instead.
+    /// Without a `waiting_time` the new order gets a random one, as in `add_order`.
+    ///
+    /// Returns the replaced order, or `None` if `item_id` was not ordered.
+    pub fn swap_order(&mut self, item_id: u32, new_item_id: u32, waiting_time: Option<u32>) -> Option<Order> {
+        let old = self.remove_order(item_id)?;
+        let waiting_time = waiting_time.unwrap_or_else(|| thread_rng().gen_range(5..16));
+        let mut order = Order::new(new_item_id, self.id, waiting_time, self.clock.now_ms());
+        order.quantity = old.quantity;
+        self.merge_order(order);
+        Some(old)
+    }
+
+    /// Takes up to `seconds` off the remaining waiting time of the order for
+    /// `item_id`, never going below zero. Returns the updated order, or `None`
+    /// if the item was not ordered.
+    pub fn bump_order(&mut self, item_id: u32, seconds: u32) -> Option<&Order> {
+        let now = self.clock.now_ms();
+        let order = self.orders.get_mut(&item_id)?;
+        order.waiting_time -= seconds.min(order.remaining_seconds(now));
+        Some(order)
+    }
+
+    /// Removes and returns all of the table's orders.
+    pub fn take_orders(&mut self) -> Vec<Order> {
+        self.order_count.fetch_sub(self.orders.len(), Ordering::SeqCst);
+        self.orders.drain().map(|(_, order)| order).collect()
+    }
+
+    /// Returns the table to the state it was created in, returning the orders it had.
+    pub fn reset(&mut self) -> Vec<Order> {
+        self.occupied = false;
+        self.take_orders()
+    }
+
+    /// Replaces the modifiers of an order, returning false if the item was not ordered.
+    pub fn set_modifiers(&mut self, item_id: u32, modifiers: Vec<Modifier>) -> bool {
+        match self.orders.get_mut(&item_id) {
+            Some(order) => {
+                order.modifiers = modifiers;
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn get_order(&self, item_id: u32) -> Option<&Order> {
@@ -31,8 +195,129 @@ impl Table {
         self.orders.values().collect()
     }
 
+    /// Returns the ids of the ordered items in ascending order.
+    pub fn item_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.orders.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Returns the order placed first, if any.
+    pub fn oldest_order(&self) -> Option<&Order> {
+        self.orders.values().min_by_key(|order| (order.created_at, order.item_id))
+    }
+
     pub fn remove_order(&mut self, item_id: u32) -> Option<Order> {
-        self.orders.remove(&item_id)
+        let order = self.orders.remove(&item_id)?;
+        self.order_count.fetch_sub(1, Ordering::SeqCst);
+        Some(order)
+    }
+
+    /// Sends every pending order to the kitchen at `now`, returning the item
+    /// ids that changed.
+    pub fn fire_pending(&mut self, now: u64) -> Vec<u32> {
+        let mut fired = Vec::new();
+        for order in self.orders.values_mut() {
+            if order.status == OrderStatus::Pending {
+                order.status = OrderStatus::Cooking;
+                order.fired_at = Some(now);
+                fired.push(order.item_id);
+            }
+        }
+        fired.sort_unstable();
+        fired
+    }
+
+    /// Marks every cooking order as served, returning the item ids that changed.
+    pub fn serve_cooking(&mut self) -> Vec<u32> {
+        let mut served = Vec::new();
+        for order in self.orders.values_mut() {
+            if order.status == OrderStatus::Cooking {
+                order.status = OrderStatus::Served;
+                served.push(order.item_id);
+            }
+        }
+        served
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Returns true if the table is indistinguishable from a newly created one.
+    pub fn is_idle(&self) -> bool {
+        self.orders.is_empty() && !self.occupied
+    }
+
+    /// Returns true if any order is at least `min_age_ms` old at `now`.
+    pub fn has_order_older_than(&self, min_age_ms: u64, now: u64) -> bool {
+        self.orders.values().any(|order| order.age_ms(now) >= min_age_ms)
+    }
+
+    pub fn set_occupied(&mut self, occupied: bool) {
+        self.occupied = occupied;
+    }
+
+    /// Sums the remaining waiting time of every order at `now`.
+    pub fn total_remaining_seconds(&self, now: u64) -> u64 {
+        self.orders.values().map(|order| order.remaining_seconds(now) as u64).sum()
+    }
+
+    /// Computes the average and longest remaining waiting time at `now`.
+    /// An empty table reports all zeros.
+    pub fn wait_stats(&self, now: u64) -> WaitStats {
+        let remaining: Vec<u32> = self
+            .orders
+            .values()
+            .map(|order| order.remaining_seconds(now))
+            .collect();
+
+        if remaining.is_empty() {
+            return WaitStats {
+                avg_remaining_seconds: 0.0,
+                max_remaining_seconds: 0,
+                order_count: 0,
+            };
+        }
+
+        let total: u64 = remaining.iter().map(|&r| r as u64).sum();
+        WaitStats {
+            avg_remaining_seconds: total as f64 / remaining.len() as f64,
+            max_remaining_seconds: remaining.iter().copied().max().unwrap_or(0),
+            order_count: remaining.len(),
+        }
+    }
+
+    pub fn status_counts(&self) -> StatusCounts {
+        let mut counts = StatusCounts { table_id: self.id, ..StatusCounts::default() };
+        for order in self.orders.values() {
+            match order.status {
+                OrderStatus::Pending => counts.pending += 1,
+                OrderStatus::Cooking => counts.cooking += 1,
+                OrderStatus::Served => counts.served += 1,
+                // Voided orders are no longer on the table
+                OrderStatus::Voided => {}
+            }
+        }
+        counts
+    }
+
+    pub fn summary(&self) -> TableSummary {
+        TableSummary {
+            table_id: self.id,
+            occupied: self.occupied,
+            order_count: self.order_count(),
+        }
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        self.order_count.fetch_sub(self.orders.len(), Ordering::SeqCst);
     }
 }
 
@@ -78,4 +363,218 @@ mod tests {
         assert_eq!(removed_order.unwrap().item_id, 46);
         assert!(table.get_order(46).is_none());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_occupancy() {
+        let mut table = Table::new(5);
+        assert!(!table.summary().occupied);
+
+        table.set_occupied(true);
+        assert_eq!(
+            table.summary(),
+            TableSummary { table_id: 5, occupied: true, order_count: 0 }
+        );
+
+        table.set_occupied(false);
+        assert!(!table.summary().occupied);
+    }
+
+    #[test]
+    fn test_add_order_uses_clock() {
+        let clock = Arc::new(crate::clock::FakeClock::new(5_000));
+        let mut table = Table::with_clock(8, clock.clone());
+
+        table.add_order(1);
+        clock.advance(3_000);
+        table.add_order(2);
+
+        assert_eq!(table.get_order(1).unwrap().created_at, 5_000);
+        assert_eq!(table.get_order(2).unwrap().created_at, 8_000);
+        assert_eq!(table.get_order(1).unwrap().age_ms(clock.now_ms()), 3_000);
+    }
+
+    #[test]
+    fn test_has_order_older_than() {
+        let mut table = Table::new(6);
+        assert!(!table.has_order_older_than(0, 10_000));
+
+        table.add_order_at(1, 2_000);
+        table.add_order_at(2, 8_000);
+
+        assert!(table.has_order_older_than(8_000, 10_000));
+        assert!(!table.has_order_older_than(8_001, 10_000));
+    }
+
+    #[test]
+    fn test_wait_stats() {
+        let mut table = Table::new(7);
+        assert_eq!(
+            table.wait_stats(0),
+            WaitStats { avg_remaining_seconds: 0.0, max_remaining_seconds: 0, order_count: 0 }
+        );
+
+        table.insert_order(Order::new(1, 7, 10, 0));
+        table.insert_order(Order::new(2, 7, 6, 0));
+        table.insert_order(Order::new(3, 7, 15, 4_000));
+
+        // At 5s: 5s, 1s and 14s remain
+        assert_eq!(
+            table.wait_stats(5_000),
+            WaitStats { avg_remaining_seconds: 20.0 / 3.0, max_remaining_seconds: 14, order_count: 3 }
+        );
+    }
+
+    #[test]
+    fn test_serve_cooking() {
+        let mut table = Table::new(10);
+        let mut cooking = Order::new(1, 10, 5, 0);
+        cooking.status = OrderStatus::Cooking;
+        table.insert_order(cooking);
+        table.insert_order(Order::new(2, 10, 5, 0));
+
+        assert_eq!(table.serve_cooking(), vec![1]);
+        assert_eq!(table.get_order(1).unwrap().status, OrderStatus::Served);
+        assert_eq!(table.get_order(2).unwrap().status, OrderStatus::Pending);
+        assert!(table.serve_cooking().is_empty());
+    }
+
+    #[test]
+    fn test_fire_pending() {
+        let mut table = Table::new(10);
+        let mut cooking = Order::new(1, 10, 5, 0);
+        cooking.status = OrderStatus::Cooking;
+        table.insert_order(cooking);
+        let mut served = Order::new(2, 10, 5, 0);
+        served.status = OrderStatus::Served;
+        table.insert_order(served);
+        table.insert_order(Order::new(3, 10, 5, 0));
+        table.insert_order(Order::new(4, 10, 5, 0));
+
+        assert_eq!(table.fire_pending(7_000), vec![3, 4]);
+        assert_eq!(table.get_order(3).unwrap().status, OrderStatus::Cooking);
+        assert_eq!(table.get_order(4).unwrap().fired_at, Some(7_000));
+        assert_eq!(table.get_order(1).unwrap().fired_at, None);
+        assert_eq!(table.get_order(2).unwrap().status, OrderStatus::Served);
+        assert!(table.fire_pending(8_000).is_empty());
+    }
+
+    #[test]
+    fn test_shared_order_count() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut first = Table::new(1).with_order_count(Arc::clone(&count));
+        let mut second = Table::new(2).with_order_count(Arc::clone(&count));
+
+        first.add_order(1);
+        first.add_order(1);
+        first.add_order(2);
+        second.add_order(1);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        first.swap_order(1, 3, None);
+        first.swap_order(3, 2, None);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        second.merge_order(first.take_orders().pop().unwrap());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        drop(second);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_bump_order() {
+        let clock = Arc::new(crate::clock::FakeClock::new(0));
+        let mut table = Table::with_clock(1, clock.clone());
+        table.add_order_with_wait(5, 60);
+        clock.advance(10_000);
+
+        assert_eq!(table.bump_order(5, 20).unwrap().remaining_seconds(10_000), 30);
+        assert_eq!(table.bump_order(5, 100).unwrap().remaining_seconds(10_000), 0);
+        assert_eq!(table.get_order(5).unwrap().ready_at(), 10_000);
+        assert!(table.bump_order(6, 1).is_none());
+    }
+
+    #[test]
+    fn test_oldest_order() {
+        let mut table = Table::new(11);
+        assert!(table.oldest_order().is_none());
+
+        table.add_order_at(1, 5_000);
+        table.add_order_at(2, 1_000);
+        table.add_order_at(3, 3_000);
+
+        assert_eq!(table.oldest_order().unwrap().item_id, 2);
+    }
+
+    #[test]
+    fn test_coalesce_order() {
+        let clock = Arc::new(crate::clock::FakeClock::new(0));
+        let mut table = Table::with_clock(12, clock.clone());
+        assert!(!table.coalesce_order(1, 500));
+
+        table.add_order(1);
+        clock.advance(200);
+        assert!(table.coalesce_order(1, 500));
+        assert!(!table.coalesce_order(1, 0));
+        assert_eq!(table.get_order(1).unwrap().quantity, 2);
+
+        clock.advance(300);
+        assert!(!table.coalesce_order(1, 500));
+        assert_eq!(table.get_order(1).unwrap().quantity, 2);
+    }
+
+    #[test]
+    fn test_quantities_after_adds() {
+        let clock = Arc::new(crate::clock::FakeClock::new(0));
+        let mut table = Table::with_clock(12, clock.clone());
+        table.add_order(1);
+        table.coalesce_order(1, 500);
+        clock.advance(200);
+
+        let quantities = table.quantities_after_adds(&[1, 2, 2, 1], 500);
+        assert_eq!((quantities[&1], quantities[&2]), (4, 2));
+        let quantities = table.quantities_after_adds(&[1, 2, 2], 0);
+        assert_eq!((quantities[&1], quantities[&2]), (1, 1));
+
+        // An order outside the window is replaced, not added to
+        clock.advance(300);
+        assert_eq!(table.quantities_after_adds(&[1], 500)[&1], 1);
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let mut source = Table::new(2);
+        source.add_order(1);
+        source.add_order(5);
+        let mut target = Table::new(7);
+        target.add_order(1);
+
+        for order in source.take_orders() {
+            target.merge_order(order);
+        }
+
+        assert!(source.is_empty());
+        assert_eq!(target.get_order(1).unwrap().quantity, 2);
+        assert_eq!(target.get_order(1).unwrap().table_id, 7);
+        assert_eq!(target.get_order(5).unwrap().table_id, 7);
+    }
+
+    #[test]
+    fn test_swap_order() {
+        let mut table = Table::new(3);
+        let mut order = Order::new(1, 3, 10, 0);
+        order.quantity = 2;
+        table.insert_order(order);
+        table.add_order(4);
+
+        let old = table.swap_order(1, 9, Some(12)).unwrap();
+        assert_eq!(old.item_id, 1);
+        assert!(table.get_order(1).is_none());
+        let new = table.get_order(9).unwrap();
+        assert_eq!((new.quantity, new.waiting_time, new.table_id), (2, 12, 3));
+
+        // Swapping onto an item already ordered adds to it
+        table.swap_order(9, 4, None);
+        assert_eq!(table.get_order(4).unwrap().quantity, 3);
+        assert!(table.swap_order(9, 5, None).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use parking_lot::Mutex;
+
+/// A token bucket capping how many orders the whole restaurant accepts per minute.
+///
+/// The bucket holds up to a minute's worth of tokens and refills continuously,
+/// so short bursts are allowed as long as the per-minute rate is kept.
+pub struct OrderRateLimiter {
+    per_minute: u32,
+    state: Mutex<Bucket>,
+}
+
+/// A token is worth `MINUTE_MS` units, so a bucket refills by `per_minute` units
+/// per millisecond and the arithmetic stays exact.
+const MINUTE_MS: u64 = 60_000;
+
+struct Bucket {
+    units: u64,
+    /// When the bucket was last refilled, in milliseconds since the Unix epoch.
+    refilled_at: u64,
+}
+
+impl OrderRateLimiter {
+    /// Creates a full bucket allowing `per_minute` orders per minute, starting at `now_ms`.
+    pub fn new(per_minute: u32, now_ms: u64) -> OrderRateLimiter {
+        OrderRateLimiter {
+            per_minute,
+            state: Mutex::new(Bucket {
+                units: per_minute as u64 * MINUTE_MS,
+                refilled_at: now_ms,
+            }),
+        }
+    }
+
+    /// Takes a token at `now_ms`, or returns the seconds until one is available.
+    pub fn try_acquire(&self, now_ms: u64) -> Result<(), u64> {
+        let per_minute = self.per_minute as u64;
+        let mut bucket = self.state.lock();
+
+        let elapsed = now_ms.saturating_sub(bucket.refilled_at);
+        bucket.units = bucket
+            .units
+            .saturating_add(elapsed.saturating_mul(per_minute))
+            .min(per_minute * MINUTE_MS);
+        bucket.refilled_at = now_ms.max(bucket.refilled_at);
+
+        if bucket.units >= MINUTE_MS {
+            bucket.units -= MINUTE_MS;
+            return Ok(());
+        }
+        let wait_ms = (MINUTE_MS - bucket.units).div_ceil(per_minute.max(1));
+        Err(wait_ms.div_ceil(1000).max(1))
+    }
+
+    /// Puts back a token taken for an order that was then rejected.
+    pub fn refund(&self) {
+        let mut bucket = self.state.lock();
+        bucket.units = (bucket.units + MINUTE_MS).min(self.per_minute as u64 * MINUTE_MS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = OrderRateLimiter::new(2, 0);
+
+        assert_eq!(limiter.try_acquire(0), Ok(()));
+        assert_eq!(limiter.try_acquire(0), Ok(()));
+        // Two per minute refill one token every 30 seconds
+        assert_eq!(limiter.try_acquire(0), Err(30));
+        assert_eq!(limiter.try_acquire(20_000), Err(10));
+        assert_eq!(limiter.try_acquire(30_000), Ok(()));
+
+        // An idle bucket never holds more than a minute's worth
+        assert_eq!(limiter.try_acquire(600_000), Ok(()));
+        assert_eq!(limiter.try_acquire(600_000), Ok(()));
+        assert!(limiter.try_acquire(600_000).is_err());
+
+        // A refund never fills the bucket past a minute's worth
+        limiter.refund();
+        assert_eq!(limiter.try_acquire(600_000), Ok(()));
+        limiter.refund();
+        limiter.refund();
+        limiter.refund();
+        assert_eq!(limiter.try_acquire(600_000), Ok(()));
+        assert_eq!(limiter.try_acquire(600_000), Ok(()));
+        assert!(limiter.try_acquire(600_000).is_err());
+    }
+}
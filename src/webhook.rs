@@ -0,0 +1,286 @@
+use super::log::Logger;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+
+/// Maximum number of notifications waiting to be delivered.
+const QUEUE_CAPACITY: usize = 256;
+
+/// Number of delivery attempts per notification.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry; doubled after every failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Time allowed for a single delivery attempt.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Payload posted to the webhook URL when an order is ready.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderReady {
+    pub event: &'static str,
+    pub table_id: u32,
+    pub item_id: u32,
+}
+
+impl OrderReady {
+    pub fn new(table_id: u32, item_id: u32) -> OrderReady {
+        OrderReady {
+            event: "order_ready",
+            table_id,
+            item_id,
+        }
+    }
+}
+
+/// Sends order notifications to an external URL from a background task.
+///
+/// Notifications go through a bounded queue so a slow endpoint never blocks
+/// the request handlers; when the queue is full new notifications are dropped.
+#[derive(Clone)]
+pub struct Webhook {
+    sender: mpsc::Sender<OrderReady>,
+    logger: Logger,
+    timers: Arc<ReadyTimers>,
+}
+
+impl Webhook {
+    /// Starts the delivery task for `url`, which must be an `http://` or
+    /// `https://` URL.
+    ///
+    /// Failed and dropped deliveries are reported through `logger`.
+    pub fn spawn(url: &str, logger: Logger) -> Result<Webhook, String> {
+        let url = reqwest::Url::parse(url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or(format!("Unsupported webhook URL: {}", url))?;
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| format!("Failed to start webhook client: {}", e))?;
+        let (sender, mut receiver) = mpsc::channel::<OrderReady>(QUEUE_CAPACITY);
+
+        let task_logger = logger.clone();
+        tokio::spawn(async move {
+            while let Some(notification) = receiver.recv().await {
+                deliver(&client, &url, &notification, &task_logger).await;
+            }
+        });
+
+        Ok(Webhook {
+            sender,
+            logger,
+            timers: Arc::new(ReadyTimers::default()),
+        })
+    }
+
+    /// Deadlines of the ready notifications still to be sent.
+    pub fn timers(&self) -> &ReadyTimers {
+        &self.timers
+    }
+
+    /// Queues a notification without waiting for it to be delivered.
+    pub fn notify(&self, notification: OrderReady) {
+        if let Err(e) = self.sender.try_send(notification) {
+            self.logger.warn(&format!("Dropping webhook notification: {}", e));
+        }
+    }
+}
+
+/// When each order is due to be announced as ready, at most one deadline per
+/// table and item.
+///
+/// Deadlines are in milliseconds since the Unix epoch on the restaurant's
+/// clock. Each armed order has one task waiting on its deadline; moving or
+/// cancelling the deadline wakes that task so it can look again.
+#[derive(Default)]
+pub struct ReadyTimers {
+    timers: Mutex<HashMap<(u32, u32), ReadyTimer>>,
+}
+
+struct ReadyTimer {
+    ready_at: u64,
+    wake: Arc<Notify>,
+}
+
+impl ReadyTimers {
+    /// Sets the order's deadline to `ready_at`.
+    ///
+    /// Returns the waker for a new timer, whose task the caller must start,
+    /// or `None` if the order already had one and its task was woken instead.
+    pub fn arm(&self, table_id: u32, item_id: u32, ready_at: u64) -> Option<Arc<Notify>> {
+        let mut timers = self.timers.lock();
+        if let Some(timer) = timers.get_mut(&(table_id, item_id)) {
+            timer.ready_at = ready_at;
+            timer.wake.notify_one();
+            return None;
+        }
+        let wake = Arc::new(Notify::new());
+        timers.insert((table_id, item_id), ReadyTimer { ready_at, wake: Arc::clone(&wake) });
+        Some(wake)
+    }
+
+    /// Drops the order's deadline, if it has one.
+    pub fn cancel(&self, table_id: u32, item_id: u32) {
+        if let Some(timer) = self.timers.lock().remove(&(table_id, item_id)) {
+            timer.wake.notify_one();
+        }
+    }
+
+    /// Drops every deadline.
+    pub fn cancel_all(&self) {
+        for (_, timer) in self.timers.lock().drain() {
+            timer.wake.notify_one();
+        }
+    }
+
+    /// Returns the order's deadline, or `None` if it has none.
+    pub fn deadline(&self, table_id: u32, item_id: u32) -> Option<u64> {
+        self.timers.lock().get(&(table_id, item_id)).map(|timer| timer.ready_at)
+    }
+
+    /// Drops the order's deadline if it is still `ready_at`, returning
+    /// whether it did; `false` means it was moved and must be waited for again.
+    pub fn finish(&self, table_id: u32, item_id: u32, ready_at: u64) -> bool {
+        let mut timers = self.timers.lock();
+        match timers.get(&(table_id, item_id)) {
+            Some(timer) if timer.ready_at == ready_at => {
+                timers.remove(&(table_id, item_id));
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    /// Returns the armed orders as `(table_id, item_id)`, sorted.
+    #[cfg(test)]
+    pub fn armed(&self) -> Vec<(u32, u32)> {
+        let mut armed: Vec<(u32, u32)> = self.timers.lock().keys().copied().collect();
+        armed.sort_unstable();
+        armed
+    }
+}
+
+/// Posts the notification, retrying with exponential backoff on failure.
+async fn deliver(client: &reqwest::Client, url: &reqwest::Url, notification: &OrderReady, logger: &Logger) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        match post(client, url, notification).await {
+            Ok(()) => return,
+            Err(e) => logger.warn(&format!("Webhook attempt {} failed: {}", attempt, e)),
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    logger.error(&format!(
+        "Giving up on webhook notification for table {} item {}",
+        notification.table_id, notification.item_id
+    ));
+}
+
+/// Sends a single JSON POST and checks for a `2xx` status.
+async fn post(client: &reqwest::Client, url: &reqwest::Url, notification: &OrderReady) -> Result<(), String> {
+    let response = client
+        .post(url.clone())
+        .json(notification)
+        .timeout(ATTEMPT_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| if e.is_timeout() { "timed out".to_string() } else { e.to_string() })?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("endpoint returned {}", status.as_u16())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Level;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accepts one webhook request and answers with `status`, returning the request body.
+    async fn receive(listener: &TcpListener, status: &str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = String::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let n = stream.read(&mut buffer).await.unwrap();
+            request.push_str(&String::from_utf8_lossy(&buffer[..n]));
+            if n == 0 || request.ends_with('}') {
+                break;
+            }
+        }
+        let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        let body_start = request.find("\r\n\r\n").unwrap() + 4;
+        request[body_start..].to_string()
+    }
+
+    #[test]
+    fn test_ready_timers() {
+        let timers = ReadyTimers::default();
+
+        // One timer per order; arming again only moves the deadline
+        assert!(timers.arm(1, 7, 5_000).is_some());
+        assert!(timers.arm(1, 7, 3_000).is_none());
+        assert_eq!(timers.deadline(1, 7), Some(3_000));
+        assert!(!timers.finish(1, 7, 5_000));
+        assert!(timers.finish(1, 7, 3_000));
+        assert_eq!(timers.deadline(1, 7), None);
+
+        timers.arm(1, 7, 1_000);
+        timers.arm(2, 8, 1_000);
+        timers.cancel(1, 7);
+        assert_eq!(timers.armed(), [(2, 8)]);
+        timers.cancel_all();
+        assert!(timers.armed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_rejects_unsupported_urls() {
+        for url in ["ftp://kitchen.local/", "kitchen.local/ready", ""] {
+            assert!(Webhook::spawn(url, Logger::new(Level::Error)).is_err());
+        }
+        assert!(Webhook::spawn("https://kitchen.local/ready", Logger::new(Level::Error)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_payload() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/ready", listener.local_addr().unwrap());
+
+        let webhook = Webhook::spawn(&url, Logger::new(Level::Error)).unwrap();
+        webhook.notify(OrderReady::new(4, 16));
+
+        let body = receive(&listener, "200 OK").await;
+        assert_eq!(body, "{\"event\":\"order_ready\",\"table_id\":4,\"item_id\":16}");
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retries_on_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/ready", listener.local_addr().unwrap());
+
+        let (logger, lines) = Logger::capturing(Level::Warn);
+        let webhook = Webhook::spawn(&url, logger).unwrap();
+        webhook.notify(OrderReady::new(1, 2));
+
+        let first = receive(&listener, "500 Internal Server Error").await;
+        let second = receive(&listener, "200 OK").await;
+        assert_eq!(first, second);
+        assert_eq!(
+            *lines.lock().unwrap(),
+            ["[WARN] Webhook attempt 1 failed: endpoint returned 500"]
+        );
+    }
+}